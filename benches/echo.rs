@@ -1,66 +1,104 @@
-// use std::thread::spawn;
+use std::thread::spawn;
 
-// use async_timer_rs::hashed::Timeout;
-// use criterion::{async_executor::FuturesExecutor, *};
-// use futures::{
-//     channel::mpsc::{Receiver, SendError},
-//     executor::block_on,
-//     StreamExt,
-// };
-// use librpc::{dispatcher::Dispatcher, responder::Responder};
-// use thiserror::Error;
+use async_timer_rs::hashed::Timeout;
+use bytes::Bytes;
+use criterion::{async_executor::FuturesExecutor, *};
+use futures::{
+    channel::mpsc::{Receiver, SendError},
+    executor::block_on,
+    StreamExt,
+};
+use librpc::{dispatcher::Dispatcher, responder::Responder};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+enum TestError {
+    #[error(transparent)]
+    SendError(#[from] SendError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
+
+async fn echo<B>(mut receiver: Receiver<(Option<u64>, B)>, responder: Responder<TestError, B>) {
+    let mut i = 0;
+
+    while let Some((id, msg)) = receiver.next().await {
+        i += 1;
+
+        responder.complete(id.unwrap(), Ok(msg));
+    }
+
+    log::debug!("echo server exit with counter: {}", i)
+}
+
+async fn client<B>(mut dispatcher: Dispatcher<TestError, B>, payload: B)
+where
+    B: AsRef<[u8]> + Send + Sync + 'static,
+{
+    let len = payload.as_ref().len();
+
+    let echo = dispatcher
+        .call::<Timeout>(0, payload, None)
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+
+    assert_eq!(echo.as_ref().len(), len);
+}
+
+/// Start echo server thread, returns the dispatcher of its client.
+fn serve<B>() -> Dispatcher<TestError, B>
+where
+    B: AsRef<[u8]> + Send + Sync + 'static,
+{
+    let (dispatcher, receiver) = Dispatcher::new(100);
+
+    let responder = dispatcher.responder.clone();
+
+    spawn(move || block_on(echo(receiver, responder)));
+
+    dispatcher
+}
+
+fn bench_rpc(c: &mut Criterion) {
+    _ = pretty_env_logger::try_init();
+
+    let mut group = c.benchmark_group("echo rpc");
+
+    let vec = serve::<Vec<u8>>();
+
+    let bytes = serve::<Bytes>();
+
+    for len in [64, 64 * 1024] {
+        let payload = Bytes::from(vec![b'x'; len]);
+
+        group.throughput(Throughput::Bytes(len as u64));
+
+        // Payloads are built outside of the measurement, the dispatcher moves either
+        // buffer type without copy. See the jsonrpc echo bench for the whole pipeline.
+        group.bench_with_input(BenchmarkId::new("vec", len), &payload, |b, payload| {
+            b.to_async(FuturesExecutor).iter_batched(
+                || payload.to_vec(),
+                |payload| client(vec.clone(), payload),
+                BatchSize::SmallInput,
+            )
+        });
+
+        group.bench_with_input(BenchmarkId::new("bytes", len), &payload, |b, payload| {
+            b.to_async(FuturesExecutor).iter_batched(
+                || payload.clone(),
+                |payload| client(bytes.clone(), payload),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+
+    log::debug!("exit bench_rpc");
+}
 
-// #[derive(Debug, Error)]
-// enum TestError {
-//     #[error(transparent)]
-//     SendError(#[from] SendError),
-
-//     #[error(transparent)]
-//     IO(#[from] std::io::Error),
-// }
-
-// async fn echo(
-//     mut receiver: Receiver<(Option<u64>, String)>,
-//     responder: Responder<String, TestError>,
-// ) {
-//     let mut i = 0;
-
-//     while let Some((id, msg)) = receiver.next().await {
-//         i += 1;
-
-//         responder.complete(id.unwrap(), Ok(msg));
-//     }
-
-//     log::debug!("echo server exit with counter: {}", i)
-// }
-
-// async fn client(mut dispatcher: Dispatcher<String, String, TestError>) {
-//     let echo = dispatcher
-//         .call::<Timeout>(0, "hello".to_owned(), None)
-//         .await
-//         .unwrap()
-//         .await
-//         .unwrap();
-
-//     assert_eq!(echo, "hello");
-// }
-
-// fn bench_rpc(c: &mut Criterion) {
-//     _ = pretty_env_logger::try_init();
-
-//     let (dispatcher, receiver) = Dispatcher::new(100);
-
-//     let responder = dispatcher.responder.clone();
-
-//     spawn(move || block_on(echo(receiver, responder)));
-
-//     c.bench_function("echo rpc", |b| {
-//         b.to_async(FuturesExecutor)
-//             .iter(|| client(dispatcher.clone()))
-//     });
-
-//     log::debug!("exit bench_rpc");
-// }
-
-// criterion_group!(benches, bench_rpc);
-// criterion_main!(benches);
+criterion_group!(benches, bench_rpc);
+criterion_main!(benches);
@@ -0,0 +1,109 @@
+//! Terminal line editor with history and first word completion
+
+use rustyline::{
+    completion::Completer, highlight::Highlighter, hint::Hinter, history::DefaultHistory,
+    validate::Validator, CompletionType, Config, Context, Editor, Helper,
+};
+
+/// Line editor of the interactive mode.
+pub type LineEditor = Editor<Completions, DefaultHistory>;
+
+/// Create the line editor, Tab completes the method name and lists the candidates.
+pub fn line_editor() -> rustyline::Result<LineEditor> {
+    let config = Config::builder()
+        .completion_type(CompletionType::List)
+        .history_ignore_dups(true)?
+        .build();
+
+    let mut editor = LineEditor::with_config(config)?;
+
+    editor.set_helper(Some(Completions::default()));
+
+    Ok(editor)
+}
+
+/// Completion of the first word, the method name.
+#[derive(Debug, Default)]
+pub struct Completions {
+    words: Vec<String>,
+}
+
+impl Completions {
+    /// Set the words completed with Tab, sorted and deduplicated.
+    pub fn set(&mut self, mut words: Vec<String>) {
+        words.sort();
+        words.dedup();
+
+        self.words = words;
+    }
+
+    /// Returns the words starting with `prefix`, a single match is followed by a space.
+    fn candidates(&self, prefix: &str) -> Vec<String> {
+        if prefix.contains(char::is_whitespace) {
+            return vec![];
+        }
+
+        let mut candidates = self
+            .words
+            .iter()
+            .filter(|word| word.starts_with(prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if let [word] = candidates.as_mut_slice() {
+            word.push(' ');
+        }
+
+        candidates
+    }
+}
+
+impl Completer for Completions {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok((0, self.candidates(&line[..pos])))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+#[cfg(test)]
+mod tests {
+    use super::Completions;
+
+    #[test]
+    fn test_completions() {
+        let mut completions = Completions::default();
+
+        completions.set(vec![
+            "eth_call".to_owned(),
+            "eth_chainId".to_owned(),
+            "net_version".to_owned(),
+            "eth_call".to_owned(),
+        ]);
+
+        assert_eq!(completions.candidates("n"), vec!["net_version "]);
+        assert_eq!(
+            completions.candidates("eth_c"),
+            vec!["eth_call", "eth_chainId"]
+        );
+        assert!(completions.candidates("web3").is_empty());
+
+        // Only the method name is completed.
+        assert!(completions.candidates("net_version e").is_empty());
+    }
+}
@@ -0,0 +1,249 @@
+//! Endpoint addresses and connections
+
+use std::{future::Future, io, process::Stdio, str::FromStr};
+
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use bytes::Bytes;
+use http::{HeaderName, HeaderValue, Uri};
+use librpc::transport::Transport;
+use librpc_json::{
+    http::client::Http,
+    lines::Lines,
+    object::{Error, ErrorCode},
+    result::{RPCError, RPCResult},
+};
+use tokio::{
+    process::{Child, Command},
+    runtime::Runtime,
+};
+
+/// Server address, see [`Endpoint::from_str`] for the accepted forms.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    /// `tcp://host:port`, newline delimited frames.
+    Tcp(String),
+    /// `unix:/path/to/socket`, newline delimited frames.
+    #[cfg(unix)]
+    Unix(PathBuf),
+    /// `http://host[:port][/path]`, one POST per frame.
+    Http(Uri),
+    /// `exec:program args..`, newline delimited frames over the child stdio.
+    ///
+    /// The command line is split like a POSIX shell does, without any expansion.
+    Exec(Vec<String>),
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(addr) = s.strip_prefix("tcp://") {
+            return Ok(Endpoint::Tcp(addr.to_owned()));
+        }
+
+        #[cfg(unix)]
+        if let Some(path) = s.strip_prefix("unix:") {
+            let path = path.strip_prefix("//").unwrap_or(path);
+
+            return Ok(Endpoint::Unix(path.into()));
+        }
+
+        if s.starts_with("http://") {
+            let uri = s
+                .parse::<Uri>()
+                .map_err(|err| format!("invalid url {}: {}", s, err))?;
+
+            return Ok(Endpoint::Http(uri));
+        }
+
+        if let Some(command) = s.strip_prefix("exec:") {
+            let args = shell_words::split(command)
+                .map_err(|err| format!("invalid exec command {}: {}", command, err))?;
+
+            if args.is_empty() {
+                return Err("exec endpoint without program".to_owned());
+            }
+
+            return Ok(Endpoint::Exec(args));
+        }
+
+        Err(format!(
+            "unsupported endpoint {}, expect tcp://, unix:, http:// or exec:",
+            s
+        ))
+    }
+}
+
+/// Client transport of any endpoint.
+pub type Connection = Box<dyn Transport<Bytes> + Send>;
+
+/// Runtime driving a [`Connection`], see [`Link::run`].
+pub struct Link {
+    runtime: Runtime,
+    child: Option<Child>,
+}
+
+impl Link {
+    /// Run `future` using the connection until it completes, then stop the child
+    /// process if any.
+    pub fn run<F: Future>(self, future: F) -> F::Output {
+        let Link { runtime, child } = self;
+
+        runtime.block_on(async move {
+            let output = future.await;
+
+            if let Some(mut child) = child {
+                _ = child.kill().await;
+            }
+
+            output
+        })
+    }
+}
+
+/// Connect to `endpoint`, `headers` are added to HTTP requests.
+pub fn connect(endpoint: &Endpoint, headers: &[String]) -> RPCResult<(Connection, Link)> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(failed)?;
+
+    let (connection, child): (Connection, _) = match endpoint {
+        Endpoint::Tcp(addr) => {
+            let transport = runtime
+                .block_on(Lines::connect(addr.as_str()))
+                .map_err(failed)?;
+
+            (Box::new(transport), None)
+        }
+        #[cfg(unix)]
+        Endpoint::Unix(path) => {
+            let stream = runtime
+                .block_on(tokio::net::UnixStream::connect(path))
+                .map_err(failed)?;
+
+            (Box::new(Lines::new(stream)), None)
+        }
+        Endpoint::Http(uri) => {
+            let mut http = Http::new(uri.clone());
+
+            for header in headers {
+                let (name, value) = parse_header(header).map_err(failed)?;
+
+                http = http.header(name, value);
+            }
+
+            (Box::new(http), None)
+        }
+        Endpoint::Exec(args) => {
+            // The child pipes are driven by the runtime it is spawned within.
+            let mut child = {
+                let _guard = runtime.enter();
+
+                Command::new(&args[0])
+                    .args(&args[1..])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .map_err(failed)?
+            };
+
+            let stdin = child.stdin.take().expect("piped stdin");
+            let stdout = child.stdout.take().expect("piped stdout");
+
+            (
+                Box::new(Lines::new(tokio::io::join(stdout, stdin))),
+                Some(child),
+            )
+        }
+    };
+
+    Ok((connection, Link { runtime, child }))
+}
+
+/// Local [`ErrorCode::Io`] error of a failed connect.
+fn failed(err: io::Error) -> RPCError {
+    Error {
+        code: ErrorCode::Io(err.kind()),
+        message: format!("Connect failed: {}", err),
+        data: None,
+    }
+}
+
+/// Parse `Name: value` header.
+fn parse_header(header: &str) -> io::Result<(HeaderName, HeaderValue)> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid header {:?}, expect `Name: value`", header),
+        )
+    };
+
+    let (name, value) = header.split_once(':').ok_or_else(invalid)?;
+
+    let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?;
+
+    let value = HeaderValue::from_str(value.trim()).map_err(|_| invalid())?;
+
+    Ok((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Uri;
+
+    use super::{parse_header, Endpoint};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "tcp://127.0.0.1:1234".parse(),
+            Ok(Endpoint::Tcp("127.0.0.1:1234".to_owned()))
+        );
+
+        #[cfg(unix)]
+        assert_eq!(
+            "unix:///tmp/rpc.sock".parse(),
+            Ok(Endpoint::Unix("/tmp/rpc.sock".into()))
+        );
+
+        assert_eq!(
+            "http://localhost/rpc".parse(),
+            Ok(Endpoint::Http(Uri::from_static("http://localhost/rpc")))
+        );
+
+        assert_eq!(
+            "exec:server --stdio".parse(),
+            Ok(Endpoint::Exec(vec![
+                "server".to_owned(),
+                "--stdio".to_owned()
+            ]))
+        );
+
+        assert_eq!(
+            r#"exec:"/opt/my server" --name 'a b' c\ d"#.parse(),
+            Ok(Endpoint::Exec(vec![
+                "/opt/my server".to_owned(),
+                "--name".to_owned(),
+                "a b".to_owned(),
+                "c d".to_owned()
+            ]))
+        );
+
+        assert!("exec:server 'unterminated".parse::<Endpoint>().is_err());
+
+        assert!("ws://localhost".parse::<Endpoint>().is_err());
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let (name, value) = parse_header("X-Api-Key:  secret ").unwrap();
+
+        assert_eq!(name, "x-api-key");
+        assert_eq!(value, "secret");
+
+        assert!(parse_header("no separator").is_err());
+    }
+}
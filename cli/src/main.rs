@@ -0,0 +1,444 @@
+//! `librpc-cli`, send one JSON-RPC call or notification and print the result,
+//! or run calls interactively with `--interactive`.
+//!
+//! Exit code is `0` on success, `1` if the server answers an error and `2` if
+//! the call fails locally, e.g. the server can't be reached.
+
+mod editor;
+mod endpoint;
+mod repl;
+
+use std::{
+    io::{self, Read},
+    process::exit,
+    time::Duration,
+};
+
+use async_timer_rs::{hashed::Timeout, Timer};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use futures::future;
+use librpc_json::{client::Client, result::RPCError, session::Session};
+use serde_json::Value;
+
+use endpoint::{connect, Endpoint};
+
+/// Parsed command line.
+#[derive(Debug)]
+struct Options {
+    endpoint: Endpoint,
+    method: String,
+    params: Value,
+    notify: bool,
+    interactive: bool,
+    timeout: Duration,
+    headers: Vec<String>,
+    compact: bool,
+}
+
+fn command() -> Command {
+    Command::new("librpc-cli")
+        .about("Send a JSON-RPC call or notification")
+        .arg(
+            Arg::new("endpoint")
+                .required(true)
+                .value_parser(value_parser!(Endpoint))
+                .help(
+                    "tcp://host:port, unix:/path, http://host[:port][/path] or exec:program args..",
+                ),
+        )
+        .arg(
+            Arg::new("method")
+                .required_unless_present("interactive")
+                .value_parser(value_parser!(String))
+                .help("Method name"),
+        )
+        .arg(
+            Arg::new("args")
+                .num_args(1..)
+                .value_parser(value_parser!(String))
+                .conflicts_with("params")
+                .help("Positional params, each parsed as JSON or taken as string"),
+        )
+        .arg(
+            Arg::new("params")
+                .short('p')
+                .long("params")
+                .value_parser(value_parser!(String))
+                .help("Params as one JSON value, `-` reads it from stdin"),
+        )
+        .arg(
+            Arg::new("notify")
+                .short('n')
+                .long("notify")
+                .action(ArgAction::SetTrue)
+                .help("Send a notification, no response is expected"),
+        )
+        .arg(
+            Arg::new("interactive")
+                .short('i')
+                .long("interactive")
+                .action(ArgAction::SetTrue)
+                .help("Read calls from stdin, completing method names from rpc.discover"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .short('t')
+                .long("timeout")
+                .default_value("30")
+                .value_parser(value_parser!(u64))
+                .help("Call timeout in seconds"),
+        )
+        .arg(
+            Arg::new("header")
+                .short('H')
+                .long("header")
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String))
+                .help("Extra HTTP header, `Name: value`"),
+        )
+        .arg(
+            Arg::new("compact")
+                .short('c')
+                .long("compact")
+                .action(ArgAction::SetTrue)
+                .help("Print JSON on one line"),
+        )
+}
+
+impl Options {
+    fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        Ok(Self {
+            endpoint: matches
+                .get_one::<Endpoint>("endpoint")
+                .expect("required endpoint")
+                .clone(),
+            method: matches
+                .get_one::<String>("method")
+                .cloned()
+                .unwrap_or_default(),
+            params: params(matches)?,
+            notify: matches.get_flag("notify"),
+            interactive: matches.get_flag("interactive"),
+            timeout: Duration::from_secs(*matches.get_one::<u64>("timeout").unwrap_or(&30)),
+            headers: matches
+                .get_many::<String>("header")
+                .map(|headers| headers.cloned().collect())
+                .unwrap_or_default(),
+            compact: matches.get_flag("compact"),
+        })
+    }
+}
+
+/// Params from `--params` or the positional args, `null` if none.
+fn params(matches: &ArgMatches) -> Result<Value, String> {
+    if let Some(params) = matches.get_one::<String>("params") {
+        let params = match params.as_str() {
+            "-" => {
+                let mut params = String::new();
+
+                io::stdin()
+                    .read_to_string(&mut params)
+                    .map_err(|err| format!("read params from stdin failed: {}", err))?;
+
+                params
+            }
+            _ => params.clone(),
+        };
+
+        return serde_json::from_str(&params).map_err(|err| format!("invalid params: {}", err));
+    }
+
+    Ok(match matches.get_many::<String>("args") {
+        Some(args) => Value::Array(args.map(|arg| arg_value(arg)).collect()),
+        None => Value::Null,
+    })
+}
+
+/// Positional arg as JSON value, a string if not valid JSON.
+fn arg_value(arg: &str) -> Value {
+    serde_json::from_str(arg).unwrap_or_else(|_| Value::String(arg.to_owned()))
+}
+
+/// Send the call described by `options`, returns the result or `None` for notification.
+fn run(options: &Options) -> Result<Option<Value>, RPCError> {
+    let (transport, link) = connect(&options.endpoint, &options.headers)?;
+
+    let (mut client, output, responder) = Client::new(10);
+
+    let mut session = Session::new(output, responder);
+
+    let call = async move {
+        if options.notify {
+            client
+                .notification(&options.method, &options.params)
+                .await
+                .map(|_| None)
+        } else {
+            client
+                .call::<_, Value, Timeout>(
+                    &options.method,
+                    &options.params,
+                    Some(Timeout::new(options.timeout)),
+                )
+                .await
+                .map(Some)
+        }
+    };
+
+    let (session_result, result) = link.run(future::join(session.run(transport), call));
+
+    if let Err(err) = session_result {
+        log::debug!("session exit with error: {}", err);
+    }
+
+    result
+}
+
+fn print(value: &Value, compact: bool) -> String {
+    match compact {
+        true => value.to_string(),
+        false => serde_json::to_string_pretty(value).expect("serialize json value"),
+    }
+}
+
+/// Describe `err`, server errors with the [`ErrorCode`](librpc_json::object::ErrorCode) name and data.
+///
+/// Local errors, which never reached the server, are described by their message alone.
+fn describe(err: &RPCError) -> String {
+    if err.code.is_local() {
+        return err.message.clone();
+    }
+
+    let mut message = format!("{} ({}): {}", err.code.name(), err.code.code(), err.message);
+
+    if let Some(data) = &err.data {
+        message.push('\n');
+        message.push_str(&print(data, false));
+    }
+
+    message
+}
+
+fn main() {
+    _ = pretty_env_logger::try_init();
+
+    let matches = command().get_matches();
+
+    let options = match Options::from_matches(&matches) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            exit(2);
+        }
+    };
+
+    if options.interactive {
+        if let Err(err) = repl::run(&options) {
+            eprintln!("error: {}", describe(&err));
+            exit(2);
+        }
+
+        return;
+    }
+
+    match run(&options) {
+        Ok(Some(result)) => println!("{}", print(&result, options.compact)),
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("error: {}", describe(&err));
+            exit(if err.code.is_local() { 2 } else { 1 });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread::spawn, time::Duration};
+
+    use futures::future;
+    use librpc_json::{
+        http::server::Endpoint as HttpEndpoint,
+        lines::Lines,
+        object::{Error, ErrorCode},
+        server::Server,
+    };
+    use serde_json::{json, Value};
+
+    use crate::endpoint::Endpoint;
+
+    use super::{command, describe, run, Options};
+
+    #[test]
+    fn test_params() {
+        let matches = command().get_matches_from(["librpc-cli", "tcp://a:1", "add", "1", "x"]);
+
+        let options = Options::from_matches(&matches).unwrap();
+
+        assert_eq!(options.params, json!([1, "x"]));
+
+        let matches = command().get_matches_from([
+            "librpc-cli",
+            "tcp://a:1",
+            "get",
+            "--params",
+            r#"{"key":"a"}"#,
+        ]);
+
+        let options = Options::from_matches(&matches).unwrap();
+
+        assert_eq!(options.params, json!({"key": "a"}));
+    }
+
+    #[test]
+    fn test_tcp_call() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        listener.set_nonblocking(true).unwrap();
+
+        spawn(move || {
+            let mut server = Server::new();
+
+            server
+                .handle("add", |_, (lhs, rhs): (i64, i64)| {
+                    future::ready(Ok(lhs + rhs))
+                })
+                .handle("fail", |_, _: Value| {
+                    future::ready(Result::<(), _>::Err(Error {
+                        code: ErrorCode::InvalidParams,
+                        message: "Bad".to_owned(),
+                        data: Some(json!({"field": "a"})),
+                    }))
+                });
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+
+                    _ = server.accept(Lines::new(stream)).await;
+                }
+            })
+        });
+
+        let options = |method: &str, params: Value| Options {
+            endpoint: Endpoint::Tcp(addr.to_string()),
+            method: method.to_owned(),
+            params,
+            notify: false,
+            interactive: false,
+            timeout: Duration::from_secs(5),
+            headers: vec![],
+            compact: true,
+        };
+
+        assert_eq!(run(&options("add", json!([1, 2]))).unwrap(), Some(json!(3)));
+
+        let err = run(&options("fail", Value::Null)).unwrap_err();
+
+        assert_eq!(
+            describe(&err),
+            "InvalidParams (-32602): Bad\n{\n  \"field\": \"a\"\n}"
+        );
+    }
+
+    #[test]
+    fn test_connect_failed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        // Nothing listens anymore.
+        drop(listener);
+
+        let err = run(&Options {
+            endpoint: Endpoint::Tcp(addr.to_string()),
+            method: "add".to_owned(),
+            params: Value::Null,
+            notify: false,
+            interactive: false,
+            timeout: Duration::from_secs(5),
+            headers: vec![],
+            compact: true,
+        })
+        .unwrap_err();
+
+        assert!(err.code.is_local());
+        assert!(err.is_io(std::io::ErrorKind::ConnectionRefused));
+        assert!(describe(&err).starts_with("Connect failed: "));
+
+        // HTTP connects on each request.
+        let err = run(&Options {
+            endpoint: format!("http://{}/", addr).parse().unwrap(),
+            method: "add".to_owned(),
+            params: Value::Null,
+            notify: false,
+            interactive: false,
+            timeout: Duration::from_secs(5),
+            headers: vec![],
+            compact: true,
+        })
+        .unwrap_err();
+
+        assert_eq!(err.code, ErrorCode::ConnectionLost);
+        assert!(describe(&err).starts_with("HTTP request failed: "));
+    }
+
+    #[test]
+    fn test_http_call() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        listener.set_nonblocking(true).unwrap();
+
+        spawn(move || {
+            let mut server = Server::new();
+
+            server.handle("add", |_, (lhs, rhs): (i64, i64)| {
+                future::ready(Ok(lhs + rhs))
+            });
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+
+                HttpEndpoint::new(server).serve(listener).await
+            })
+        });
+
+        let options = |method: &str, params: Value, notify: bool| Options {
+            endpoint: format!("http://{}/", addr).parse().unwrap(),
+            method: method.to_owned(),
+            params,
+            notify,
+            interactive: false,
+            timeout: Duration::from_secs(5),
+            headers: vec!["X-Api-Key: secret".to_owned()],
+            compact: true,
+        };
+
+        assert_eq!(
+            run(&options("add", json!([1, 2]), false)).unwrap(),
+            Some(json!(3))
+        );
+
+        // Answered with an empty body.
+        assert_eq!(run(&options("add", json!([1, 2]), true)).unwrap(), None);
+
+        let err = run(&options("sub", json!([1, 2]), false)).unwrap_err();
+
+        assert_eq!(err.code, ErrorCode::MethodNotFound);
+    }
+}
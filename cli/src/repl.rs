@@ -0,0 +1,374 @@
+//! Interactive mode, one connection for many calls
+
+use std::{
+    io::{self, BufRead, IsTerminal},
+    sync::{Arc, Mutex},
+    thread::spawn,
+    time::Duration,
+};
+
+use async_timer_rs::{hashed::Timeout, Timer};
+use futures::{executor::block_on, future, StreamExt};
+use librpc_json::{
+    client::Client,
+    result::{RPCError, RPCResult},
+    session::Session,
+};
+use rustyline::{error::ReadlineError, ExternalPrinter};
+use serde_json::Value;
+
+use crate::{
+    arg_value, describe,
+    editor::{line_editor, LineEditor},
+    endpoint::connect,
+    print, Options,
+};
+
+const PROMPT: &str = "> ";
+
+const COMMANDS: &[&str] = &[":help", ":history", ":methods", ":notify", ":quit"];
+
+const HELP: &str = "\
+method [params]          call method, params are one JSON value or positional args
+:notify method [params]  send notification
+!N                       re-run call N of the history
+:history                 list calls and responses
+:methods                 list methods offered by rpc.discover
+:quit                    exit, same as Ctrl-D";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Empty,
+    Call(String, Value),
+    Notify(String, Value),
+    Rerun(usize),
+    History,
+    Methods,
+    Help,
+    Quit,
+}
+
+/// Parse one input line.
+fn parse(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+
+    if let Some(index) = line.strip_prefix('!') {
+        return index
+            .parse()
+            .map(Command::Rerun)
+            .map_err(|_| format!("invalid history index {}", index));
+    }
+
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+    match word {
+        "" => Ok(Command::Empty),
+        ":history" => Ok(Command::History),
+        ":methods" => Ok(Command::Methods),
+        ":help" => Ok(Command::Help),
+        ":quit" => Ok(Command::Quit),
+        ":notify" => match parse(rest)? {
+            Command::Call(method, params) => Ok(Command::Notify(method, params)),
+            _ => Err("usage: :notify method [params]".to_owned()),
+        },
+        word if word.starts_with(':') => Err(format!("unknown command {}, try :help", word)),
+        method => Ok(Command::Call(method.to_owned(), params(rest.trim()))),
+    }
+}
+
+/// Params of a call line, a JSON array or object is used as is.
+fn params(rest: &str) -> Value {
+    if rest.is_empty() {
+        return Value::Null;
+    }
+
+    match serde_json::from_str::<Value>(rest) {
+        Ok(params @ (Value::Array(_) | Value::Object(_))) => params,
+        _ => Value::Array(rest.split_whitespace().map(arg_value).collect()),
+    }
+}
+
+/// Method names listed in an OpenRPC document.
+fn method_names(document: &Value) -> Vec<String> {
+    document["methods"]
+        .as_array()
+        .map(|methods| {
+            methods
+                .iter()
+                .filter_map(|method| method["name"].as_str())
+                .map(ToOwned::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Output above the edited line, printed as is without terminal.
+#[derive(Clone, Default)]
+struct Console {
+    printer: Option<Arc<Mutex<dyn ExternalPrinter + Send>>>,
+}
+
+impl Console {
+    fn print(&self, text: &str) {
+        match &self.printer {
+            Some(printer) => _ = printer.lock().unwrap().print(format!("{}\n", text)),
+            None => println!("{}", text),
+        }
+    }
+}
+
+struct Entry {
+    method: String,
+    params: Value,
+    response: RPCResult<Value>,
+}
+
+struct Repl {
+    client: Client,
+    console: Console,
+    /// `None` if stdin is no terminal.
+    editor: Option<LineEditor>,
+    timeout: Duration,
+    compact: bool,
+    methods: Vec<String>,
+    history: Vec<Entry>,
+}
+
+impl Repl {
+    fn call(&mut self, method: &str, params: &Value) -> RPCResult<Value> {
+        block_on(self.client.call::<_, Value, Timeout>(
+            method,
+            params,
+            Some(Timeout::new(self.timeout)),
+        ))
+    }
+
+    /// Fetch method names for completion, the server may not offer `rpc.discover`.
+    fn discover(&mut self) {
+        match self.call("rpc.discover", &Value::Null) {
+            Ok(document) => self.methods = method_names(&document),
+            Err(err) => log::debug!("rpc.discover failed: {}", err),
+        }
+
+        let mut words = self.methods.clone();
+
+        words.extend(COMMANDS.iter().map(|command| command.to_string()));
+
+        if let Some(completions) = self.editor.as_mut().and_then(|editor| editor.helper_mut()) {
+            completions.set(words);
+        }
+    }
+
+    fn response(&self, index: usize, response: &RPCResult<Value>) -> String {
+        match response {
+            Ok(result) => format!("[{}] {}", index, print(result, self.compact)),
+            Err(err) => format!("[{}] error: {}", index, describe(err)),
+        }
+    }
+
+    /// Execute one input line, returns false to quit.
+    fn execute(&mut self, line: &str) -> bool {
+        let (method, params) = match parse(line) {
+            Ok(Command::Empty) => return true,
+            Ok(Command::Call(method, params)) => (method, params),
+            Ok(Command::Rerun(index)) => match self.history.get(index.wrapping_sub(1)) {
+                Some(entry) => (entry.method.clone(), entry.params.clone()),
+                None => {
+                    self.console.print(&format!("no call {} in history", index));
+                    return true;
+                }
+            },
+            Ok(Command::Notify(method, params)) => {
+                if let Err(err) = block_on(self.client.notification(&method, &params)) {
+                    self.console.print(&format!("error: {}", describe(&err)));
+                }
+
+                return true;
+            }
+            Ok(Command::History) => {
+                for (index, entry) in self.history.iter().enumerate() {
+                    let response = match &entry.response {
+                        Ok(result) => result.to_string(),
+                        Err(err) => format!("error {}", err.code.name()),
+                    };
+
+                    self.console.print(&format!(
+                        "[{}] {} {} -> {}",
+                        index + 1,
+                        entry.method,
+                        entry.params,
+                        response
+                    ));
+                }
+
+                return true;
+            }
+            Ok(Command::Methods) => {
+                match self.methods.is_empty() {
+                    true => self.console.print("server offers no rpc.discover"),
+                    false => self.console.print(&self.methods.join("\n")),
+                }
+
+                return true;
+            }
+            Ok(Command::Help) => {
+                self.console.print(HELP);
+                return true;
+            }
+            Ok(Command::Quit) => return false,
+            Err(err) => {
+                self.console.print(&format!("error: {}", err));
+                return true;
+            }
+        };
+
+        let response = self.call(&method, &params);
+
+        self.console
+            .print(&self.response(self.history.len() + 1, &response));
+
+        self.history.push(Entry {
+            method,
+            params,
+            response,
+        });
+
+        true
+    }
+
+    fn run(&mut self) -> io::Result<()> {
+        let Some(mut editor) = self.editor.take() else {
+            for line in io::stdin().lock().lines() {
+                if !self.execute(&line?) {
+                    break;
+                }
+            }
+
+            return Ok(());
+        };
+
+        loop {
+            let line = match editor.readline(PROMPT) {
+                Ok(line) => line,
+                // Ctrl-C discards the edited line.
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(io::Error::other(err)),
+            };
+
+            if !line.trim().is_empty() {
+                _ = editor.add_history_entry(line.as_str());
+            }
+
+            if !self.execute(&line) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Run interactive mode against `options.endpoint`.
+pub fn run(options: &Options) -> Result<(), RPCError> {
+    let mut editor = match io::stdin().is_terminal() {
+        true => Some(line_editor().map_err(io::Error::other)?),
+        false => None,
+    };
+
+    let (transport, link) = connect(&options.endpoint, &options.headers)?;
+
+    let (client, output, responder) = Client::new(10);
+
+    let mut session = Session::new(output, responder);
+
+    let notifications = session.notifications();
+
+    let session = spawn(move || link.run(session.run(transport)));
+
+    // Notifications are printed above the edited line.
+    let console = Console {
+        printer: editor
+            .as_mut()
+            .and_then(|editor| editor.create_external_printer().ok())
+            .map(|printer| Arc::new(Mutex::new(printer)) as _),
+    };
+
+    let printer = console.clone();
+
+    spawn(move || {
+        block_on(notifications.for_each(|notification| {
+            printer.print(&format!(
+                "<- {} {}",
+                notification.method, notification.params
+            ));
+
+            future::ready(())
+        }))
+    });
+
+    let mut repl = Repl {
+        client,
+        console,
+        editor,
+        timeout: options.timeout,
+        compact: options.compact,
+        methods: vec![],
+        history: vec![],
+    };
+
+    repl.discover();
+
+    let result = repl.run();
+
+    // Dropping the client stops the session.
+    drop(repl);
+
+    match session.join() {
+        Ok(Err(err)) => log::debug!("session exit with error: {}", err),
+        Err(_) => log::error!("session thread panicked"),
+        _ => {}
+    }
+
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{method_names, parse, Command};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("add 1 two").unwrap(),
+            Command::Call("add".to_owned(), json!([1, "two"]))
+        );
+        assert_eq!(
+            parse(r#"get {"key": "a b"}"#).unwrap(),
+            Command::Call("get".to_owned(), json!({"key": "a b"}))
+        );
+        assert_eq!(
+            parse("ping").unwrap(),
+            Command::Call("ping".to_owned(), Value::Null)
+        );
+        assert_eq!(
+            parse(":notify log [1]").unwrap(),
+            Command::Notify("log".to_owned(), json!([1]))
+        );
+        assert_eq!(parse("!2").unwrap(), Command::Rerun(2));
+        assert_eq!(parse("  ").unwrap(), Command::Empty);
+        assert!(parse(":unknown").is_err());
+    }
+
+    #[test]
+    fn test_method_names() {
+        let document = json!({
+            "openrpc": "1.2.6",
+            "methods": [{"name": "add", "params": []}, {"name": "echo", "params": []}]
+        });
+
+        assert_eq!(method_names(&document), vec!["add", "echo"]);
+    }
+}
@@ -0,0 +1,117 @@
+// Generated by librpc-codegen from OpenRPC document "Wallet" 1.0.0, do not edit.
+
+/// Wallet 1.0.0 client.
+#[derive(Debug, Clone)]
+pub struct WalletClient<C = ::librpc_json::codec::Json> {
+    client: ::librpc_json::client::Client<C>,
+}
+
+impl<C> WalletClient<C>
+where
+    C: ::librpc_json::codec::RPCCodec,
+{
+    /// Wrap `client`, connected to a server implementing this API.
+    pub fn new(client: ::librpc_json::client::Client<C>) -> Self {
+        Self { client }
+    }
+
+    /// Returns the wrapped client.
+    pub fn client(&mut self) -> &mut ::librpc_json::client::Client<C> {
+        &mut self.client
+    }
+
+    /// Add two integers.
+    pub async fn add<T>(
+        &mut self,
+        lhs: i64,
+        rhs: i64,
+        timeout: Option<T>,
+    ) -> ::librpc_json::result::RPCResult<i64>
+    where
+        T: ::async_timer_rs::Timer + Unpin,
+    {
+        self.client.call("add", (lhs, rhs), timeout).await
+    }
+
+    /// Transfer funds to another account.
+    ///
+    /// The transfer is pending until confirmed.
+    pub async fn transfer<T>(
+        &mut self,
+        params: TransferParams,
+        timeout: Option<T>,
+    ) -> ::librpc_json::result::RPCResult<Receipt>
+    where
+        T: ::async_timer_rs::Timer + Unpin,
+    {
+        self.client.call("transfer", params, timeout).await
+    }
+
+    /// Call `wallet.listAccounts`.
+    pub async fn wallet_list_accounts<T>(
+        &mut self,
+        timeout: Option<T>,
+    ) -> ::librpc_json::result::RPCResult<Vec<String>>
+    where
+        T: ::async_timer_rs::Timer + Unpin,
+    {
+        self.client.call("wallet.listAccounts", (), timeout).await
+    }
+
+    /// Call `wallet.balances`.
+    #[deprecated]
+    pub async fn wallet_balances<T>(
+        &mut self,
+        timeout: Option<T>,
+    ) -> ::librpc_json::result::RPCResult<::std::collections::BTreeMap<String, u64>>
+    where
+        T: ::async_timer_rs::Timer + Unpin,
+    {
+        self.client.call("wallet.balances", (), timeout).await
+    }
+}
+
+/// Supported currencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ::serde::Serialize, ::serde::Deserialize)]
+pub enum Currency {
+    #[serde(rename = "ETH")]
+    Eth,
+    #[serde(rename = "USDC")]
+    Usdc,
+}
+
+/// Params of `transfer`.
+#[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+pub struct TransferParams {
+    /// Recipient account.
+    pub to: String,
+    pub amount: u64,
+    pub currency: Currency,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+pub struct ReceiptFee {
+    pub amount: u64,
+    pub currency: Currency,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ::serde::Serialize, ::serde::Deserialize)]
+pub enum ReceiptStatus {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "confirmed")]
+    Confirmed,
+    #[serde(rename = "failed")]
+    Failed,
+}
+
+/// Transfer receipt.
+#[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+pub struct Receipt {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<ReceiptFee>,
+    pub id: String,
+    pub status: ReceiptStatus,
+}
@@ -0,0 +1,835 @@
+//! Typed client generator for [OpenRPC](https://spec.open-rpc.org) documents.
+//!
+//! The generated client wraps [`librpc_json::client::Client`](https://docs.rs/librpc-json),
+//! with one async method per RPC method and one type per param or result structure.
+//! Call [`Generator::generate_file`] from `build.rs` and `include!` the output, or run
+//! the `librpc-codegen` binary.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use serde_json::{Map, Value};
+
+/// Code generation error.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON, {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid OpenRPC document, {0}")]
+    Invalid(String),
+}
+
+const COMPONENTS: &str = "#/components/schemas/";
+
+const VALUE: &str = "::serde_json::Value";
+
+/// OpenRPC client generator.
+#[derive(Debug, Clone, Default)]
+pub struct Generator {
+    name: Option<String>,
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the client type name, default is the document title in PascalCase followed by `Client`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Generate the client source of OpenRPC `document`.
+    pub fn generate(&self, document: &str) -> Result<String, Error> {
+        self.generate_value(&serde_json::from_str(document)?)
+    }
+
+    /// Generate the client source of the OpenRPC document in file `input` into file `output`.
+    pub fn generate_file<I, O>(&self, input: I, output: O) -> Result<(), Error>
+    where
+        I: AsRef<Path>,
+        O: AsRef<Path>,
+    {
+        let source = self.generate(&fs::read_to_string(input)?)?;
+
+        fs::write(output, source)?;
+
+        Ok(())
+    }
+
+    /// Generate the client source of parsed OpenRPC `document`.
+    pub fn generate_value(&self, document: &Value) -> Result<String, Error> {
+        let title = document["info"]["title"].as_str().unwrap_or("JSON-RPC API");
+        let version = document["info"]["version"].as_str().unwrap_or_default();
+
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{}Client", pascal(title)),
+        };
+
+        let methods = document["methods"]
+            .as_array()
+            .ok_or_else(|| invalid("missing methods"))?;
+
+        let mut emitter = Emitter::new(document["components"]["schemas"].as_object(), &name);
+
+        let methods = methods
+            .iter()
+            .map(|method| emitter.method(method))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut source = format!(
+            "// Generated by librpc-codegen from OpenRPC document {:?} {}, do not edit.
+
+/// {} {} client.
+#[derive(Debug, Clone)]
+pub struct {name}<C = ::librpc_json::codec::Json> {{
+    client: ::librpc_json::client::Client<C>,
+}}
+
+impl<C> {name}<C>
+where
+    C: ::librpc_json::codec::RPCCodec,
+{{
+    /// Wrap `client`, connected to a server implementing this API.
+    pub fn new(client: ::librpc_json::client::Client<C>) -> Self {{
+        Self {{ client }}
+    }}
+
+    /// Returns the wrapped client.
+    pub fn client(&mut self) -> &mut ::librpc_json::client::Client<C> {{
+        &mut self.client
+    }}
+",
+            title,
+            version,
+            title,
+            version,
+            name = name
+        );
+
+        for method in methods {
+            source.push('\n');
+            source.push_str(&method);
+        }
+
+        source.push_str("}\n");
+
+        for item in emitter.types {
+            source.push('\n');
+            source.push_str(&item);
+        }
+
+        Ok(source)
+    }
+}
+
+fn invalid<S: Into<String>>(message: S) -> Error {
+    Error::Invalid(message.into())
+}
+
+struct Emitter<'a> {
+    components: Option<&'a Map<String, Value>>,
+    /// Type names of the components, reserved before any generated type.
+    component_names: HashMap<&'a str, String>,
+    /// Components defined or being defined.
+    referenced: HashSet<&'a str>,
+    /// Components being defined, references to them are boxed.
+    defining: Vec<&'a str>,
+    /// Type definitions in definition order.
+    types: Vec<String>,
+    /// Names of defined types, reserved before the definition is emitted.
+    defined: HashSet<String>,
+}
+
+impl<'a> Emitter<'a> {
+    /// Emitter of the types of a `client` using `components`.
+    ///
+    /// Components keep their name, generated types clashing with one are renamed.
+    fn new(components: Option<&'a Map<String, Value>>, client: &str) -> Self {
+        let mut emitter = Self {
+            components,
+            component_names: HashMap::new(),
+            referenced: HashSet::new(),
+            defining: vec![],
+            types: vec![],
+            defined: HashSet::from([client.to_owned()]),
+        };
+
+        for component in components.into_iter().flat_map(Map::keys) {
+            let name = emitter.reserve(&pascal(component));
+
+            emitter.component_names.insert(component, name);
+        }
+
+        emitter
+    }
+
+    /// Returns the client method calling OpenRPC `method`.
+    fn method(&mut self, method: &Value) -> Result<String, Error> {
+        let name = method["name"]
+            .as_str()
+            .ok_or_else(|| invalid("method without name"))?;
+
+        let type_name = pascal(name);
+
+        let params = method["params"].as_array().cloned().unwrap_or_default();
+
+        let mut args = String::new();
+
+        let call_params = match method["paramStructure"].as_str() {
+            Some("by-name") => {
+                let params_type = self.reserve(&format!("{}Params", type_name));
+
+                let properties = params
+                    .iter()
+                    .map(|param| {
+                        let name = param["name"]
+                            .as_str()
+                            .ok_or_else(|| invalid(format!("param of {} without name", name)))?;
+
+                        Ok((name, &param["schema"], param["required"] == true, param))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                let description = format!("Params of `{}`.", name);
+
+                self.structure(&params_type, Some(&description), properties)?;
+
+                args.push_str(&format!("        params: {},\n", params_type));
+
+                "params".to_owned()
+            }
+            _ => {
+                let mut values = vec![];
+
+                for param in &params {
+                    let param_name = param["name"]
+                        .as_str()
+                        .ok_or_else(|| invalid(format!("param of {} without name", name)))?;
+
+                    let ident = snake(param_name);
+
+                    let hint = format!("{}{}", type_name, pascal(param_name));
+
+                    let mut ty = self.rust_type(&param["schema"], &hint)?;
+
+                    if param["required"] != true && !ty.starts_with("Option<") {
+                        ty = format!("Option<{}>", ty);
+                    }
+
+                    args.push_str(&format!("        {}: {},\n", ident, ty));
+
+                    values.push(ident);
+                }
+
+                tuple(&values)
+            }
+        };
+
+        let result = match method.get("result") {
+            Some(result) => self.rust_type(&result["schema"], &format!("{}Result", type_name))?,
+            None => "()".to_owned(),
+        };
+
+        let mut source = String::new();
+
+        let docs = [&method["summary"], &method["description"]]
+            .into_iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>();
+
+        match docs.is_empty() {
+            true => source.push_str(&format!("    /// Call `{}`.\n", name)),
+            false => source.push_str(&doc("    ", &docs.join("\n\n"))),
+        }
+
+        if method["deprecated"] == true {
+            source.push_str("    #[deprecated]\n");
+        }
+
+        source.push_str(&format!(
+            "    pub async fn {}<T>(
+        &mut self,
+{}        timeout: Option<T>,
+    ) -> ::librpc_json::result::RPCResult<{}>
+    where
+        T: ::async_timer_rs::Timer + Unpin,
+    {{
+        self.client.call({:?}, {}, timeout).await
+    }}
+",
+            snake(name),
+            args,
+            result,
+            name,
+            call_params
+        ));
+
+        Ok(source)
+    }
+
+    /// Returns a unique type name starting with `name` and reserve it.
+    fn reserve(&mut self, name: &str) -> String {
+        let mut unique = name.to_owned();
+
+        let mut index = 1;
+
+        while self.defined.contains(&unique) {
+            index += 1;
+            unique = format!("{}{}", name, index);
+        }
+
+        self.defined.insert(unique.clone());
+
+        unique
+    }
+
+    /// Returns the Rust type of `schema`, `hint` names inline structures.
+    fn rust_type(&mut self, schema: &Value, hint: &str) -> Result<String, Error> {
+        let schema = match schema {
+            Value::Object(schema) => schema,
+            _ => return Ok(VALUE.to_owned()),
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            return self.reference(reference);
+        }
+
+        for key in ["allOf", "anyOf", "oneOf"] {
+            let schemas = match schema.get(key).and_then(Value::as_array) {
+                Some(schemas) => schemas,
+                None => continue,
+            };
+
+            let not_null = schemas
+                .iter()
+                .filter(|schema| schema["type"] != "null")
+                .collect::<Vec<_>>();
+
+            return match not_null.as_slice() {
+                [schema] if not_null.len() < schemas.len() => {
+                    Ok(format!("Option<{}>", self.rust_type(schema, hint)?))
+                }
+                [schema] => self.rust_type(schema, hint),
+                _ => Ok(VALUE.to_owned()),
+            };
+        }
+
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            let values = values.iter().map(Value::as_str).collect::<Option<Vec<_>>>();
+
+            return match values {
+                Some(values) => {
+                    let name = self.reserve(hint);
+
+                    self.enumeration(&name, schema.get("description"), &values);
+
+                    Ok(name)
+                }
+                None => Ok(VALUE.to_owned()),
+            };
+        }
+
+        let types = match schema.get("type") {
+            Some(Value::String(ty)) => vec![ty.as_str()],
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+
+        let nullable = types.contains(&"null") && types.len() == 2;
+
+        let ty = match types.iter().find(|ty| **ty != "null" || types.len() == 1) {
+            Some(ty) => *ty,
+            None => return Ok(VALUE.to_owned()),
+        };
+
+        let format = schema.get("format").and_then(Value::as_str);
+
+        let rust_type = match ty {
+            "string" => "String".to_owned(),
+            "boolean" => "bool".to_owned(),
+            "null" => "()".to_owned(),
+            "integer" => match format {
+                Some("int8") => "i8",
+                Some("int16") => "i16",
+                Some("int32") => "i32",
+                Some("uint8") => "u8",
+                Some("uint16") => "u16",
+                Some("uint32") => "u32",
+                Some("uint" | "uint64") => "u64",
+                _ => "i64",
+            }
+            .to_owned(),
+            "number" => match format {
+                Some("float") => "f32",
+                _ => "f64",
+            }
+            .to_owned(),
+            "array" => match schema.get("items") {
+                Some(Value::Array(items)) => {
+                    let items = items
+                        .iter()
+                        .enumerate()
+                        .map(|(index, item)| self.rust_type(item, &format!("{}{}", hint, index)))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    tuple(&items)
+                }
+                Some(item) => format!(
+                    "Vec<{}>",
+                    unbox(self.rust_type(item, &format!("{}Item", hint))?)
+                ),
+                None => format!("Vec<{}>", VALUE),
+            },
+            "object" => match schema.get("properties").and_then(Value::as_object) {
+                Some(properties) if !properties.is_empty() => {
+                    let name = self.reserve(hint);
+
+                    self.object(&name, schema)?;
+
+                    name
+                }
+                _ => match schema.get("additionalProperties") {
+                    Some(item @ Value::Object(_)) => format!(
+                        "::std::collections::BTreeMap<String, {}>",
+                        unbox(self.rust_type(item, &format!("{}Value", hint))?)
+                    ),
+                    _ => VALUE.to_owned(),
+                },
+            },
+            _ => VALUE.to_owned(),
+        };
+
+        match nullable {
+            true => Ok(format!("Option<{}>", rust_type)),
+            false => Ok(rust_type),
+        }
+    }
+
+    /// Returns the type of component `reference`, defining it on first use.
+    ///
+    /// References to a component from its own definition are boxed.
+    fn reference(&mut self, reference: &str) -> Result<String, Error> {
+        let component = reference
+            .strip_prefix(COMPONENTS)
+            .ok_or_else(|| invalid(format!("unsupported reference {}", reference)))?;
+
+        let (component, schema) = self
+            .components
+            .and_then(|components| components.get_key_value(component))
+            .ok_or_else(|| invalid(format!("undefined component {}", component)))?;
+
+        let component = component.as_str();
+
+        let name = self.component_names[component].clone();
+
+        if self.defining.contains(&component) {
+            return Ok(format!("Box<{}>", name));
+        }
+
+        if !self.referenced.insert(component) {
+            return Ok(name);
+        }
+
+        self.defining.push(component);
+
+        let is_object = schema["type"] == "object"
+            && schema["properties"]
+                .as_object()
+                .map(|properties| !properties.is_empty())
+                .unwrap_or_default();
+
+        let strings = schema["enum"]
+            .as_array()
+            .and_then(|values| values.iter().map(Value::as_str).collect::<Option<Vec<_>>>());
+
+        match (is_object, strings) {
+            (true, _) => self.object(&name, schema.as_object().expect("object schema"))?,
+            (false, Some(values)) => self.enumeration(&name, schema.get("description"), &values),
+            (false, None) => {
+                let ty = self.rust_type(schema, &format!("{}Inner", name))?;
+
+                let mut item = doc_of("", schema.get("description"));
+
+                item.push_str(&format!("pub type {} = {};\n", name, ty));
+
+                self.types.push(item);
+            }
+        }
+
+        self.defining.pop();
+
+        Ok(name)
+    }
+
+    fn object(&mut self, name: &str, schema: &Map<String, Value>) -> Result<(), Error> {
+        let required = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|required| {
+                required
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let properties = schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .map(|properties| {
+                properties
+                    .iter()
+                    .map(|(field, schema)| {
+                        (
+                            field.as_str(),
+                            schema,
+                            required.contains(&field.as_str()),
+                            schema,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let description = schema.get("description").and_then(Value::as_str);
+
+        self.structure(name, description, properties)
+    }
+
+    /// Define struct `name`, each field is `(name, schema, required, descriptor)`,
+    /// the field doc comes from the descriptor `description`.
+    fn structure(
+        &mut self,
+        name: &str,
+        description: Option<&str>,
+        fields: Vec<(&str, &Value, bool, &Value)>,
+    ) -> Result<(), Error> {
+        let mut body = String::new();
+
+        for (field, schema, required, descriptor) in fields {
+            let ident = snake(field);
+
+            let mut ty = self.rust_type(schema, &format!("{}{}", name, pascal(field)))?;
+
+            let mut attrs = vec![];
+
+            if ident.trim_start_matches("r#") != field {
+                attrs.push(format!("rename = {:?}", field));
+            }
+
+            if !required {
+                if !ty.starts_with("Option<") {
+                    ty = format!("Option<{}>", ty);
+                }
+
+                attrs.push("default, skip_serializing_if = \"Option::is_none\"".to_owned());
+            }
+
+            body.push_str(&doc_of("    ", descriptor.get("description")));
+
+            if !attrs.is_empty() {
+                body.push_str(&format!("    #[serde({})]\n", attrs.join(", ")));
+            }
+
+            body.push_str(&format!("    pub {}: {},\n", ident, ty));
+        }
+
+        let mut item = description.map(|d| doc("", d)).unwrap_or_default();
+
+        item.push_str(&format!(
+            "#[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+pub struct {} {{
+{}}}
+",
+            name, body
+        ));
+
+        self.types.push(item);
+
+        Ok(())
+    }
+
+    fn enumeration(&mut self, name: &str, description: Option<&Value>, values: &[&str]) {
+        let mut item = doc_of("", description);
+
+        item.push_str(&format!(
+            "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ::serde::Serialize, ::serde::Deserialize)]
+pub enum {} {{
+",
+            name
+        ));
+
+        for value in values {
+            let mut variant = pascal(value);
+
+            if !variant.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                variant = format!("V{}", variant);
+            }
+
+            if variant != *value {
+                item.push_str(&format!("    #[serde(rename = {:?})]\n", value));
+            }
+
+            item.push_str(&format!("    {},\n", variant));
+        }
+
+        item.push_str("}\n");
+
+        self.types.push(item);
+    }
+}
+
+/// Type `ty` without the `Box` of a recursive reference, e.g. as item of a `Vec`.
+fn unbox(ty: String) -> String {
+    match ty.strip_prefix("Box<").and_then(|ty| ty.strip_suffix('>')) {
+        Some(ty) => ty.to_owned(),
+        None => ty,
+    }
+}
+
+/// Tuple expression or type of `items`.
+fn tuple(items: &[String]) -> String {
+    match items {
+        [item] => format!("({},)", item),
+        items => format!("({})", items.join(", ")),
+    }
+}
+
+/// Doc comment lines of `text`, each prefixed with `indent`.
+fn doc(indent: &str, text: &str) -> String {
+    text.lines()
+        .map(|line| match line.is_empty() {
+            true => format!("{}///\n", indent),
+            false => format!("{}/// {}\n", indent, line),
+        })
+        .collect()
+}
+
+fn doc_of(indent: &str, description: Option<&Value>) -> String {
+    description
+        .and_then(Value::as_str)
+        .map(|description| doc(indent, description))
+        .unwrap_or_default()
+}
+
+/// Split `name` into lowercase words at separators and case changes.
+fn words(name: &str) -> Vec<String> {
+    let mut words = vec![];
+
+    let mut word = String::new();
+
+    let chars = name.chars().collect::<Vec<_>>();
+
+    for (index, c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+
+            continue;
+        }
+
+        let boundary = c.is_uppercase()
+            && index > 0
+            && (chars[index - 1].is_lowercase()
+                || chars[index - 1].is_ascii_digit()
+                || (chars[index - 1].is_uppercase()
+                    && chars.get(index + 1).is_some_and(|next| next.is_lowercase())));
+
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+
+        word.extend(c.to_lowercase());
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+fn pascal(name: &str) -> String {
+    words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try", "typeof",
+    "unsized", "virtual", "yield",
+];
+
+/// Snake case identifier of `name`, keywords are escaped.
+fn snake(name: &str) -> String {
+    let ident = words(name).join("_");
+
+    match ident.as_str() {
+        "" => "_".to_owned(),
+        "self" | "super" | "crate" => format!("{}_", ident),
+        ident if ident.starts_with(|c: char| c.is_ascii_digit()) => format!("_{}", ident),
+        ident if KEYWORDS.contains(&ident) => format!("r#{}", ident),
+        ident => ident.to_owned(),
+    }
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+mod wallet {
+    include!("fixtures/wallet.rs");
+}
+
+#[cfg(test)]
+mod tests {
+    use async_timer_rs::hashed::Timeout;
+    use futures::future;
+    use librpc_json::{harness::Harness, server::Server};
+    use serde_json::{json, Value};
+
+    use crate::wallet::{Currency, Receipt, TransferParams, WalletClient};
+
+    use super::{pascal, snake, Generator};
+
+    #[test]
+    fn test_names() {
+        assert_eq!(snake("eth_getBalance"), "eth_get_balance");
+        assert_eq!(snake("wallet.listAccounts"), "wallet_list_accounts");
+        assert_eq!(snake("getHTTPStatus"), "get_http_status");
+        assert_eq!(snake("type"), "r#type");
+        assert_eq!(pascal("eth_getBalance"), "EthGetBalance");
+    }
+
+    #[test]
+    fn test_golden() {
+        let source = Generator::new()
+            .generate(include_str!("fixtures/wallet.json"))
+            .unwrap();
+
+        assert_eq!(source, include_str!("fixtures/wallet.rs"));
+    }
+
+    #[test]
+    fn test_component_names() {
+        let document = json!({
+            "openrpc": "1.2.6",
+            "info": {"title": "tree", "version": "1.0.0"},
+            "methods": [
+                {
+                    "name": "get",
+                    "paramStructure": "by-name",
+                    "params": [{"name": "id", "schema": {"type": "string"}, "required": true}],
+                    "result": {"name": "result", "schema": {"$ref": "#/components/schemas/GetParams"}},
+                },
+                {
+                    "name": "owner",
+                    "params": [],
+                    "result": {"name": "result", "schema": {"$ref": "#/components/schemas/TreeClient"}},
+                },
+            ],
+            "components": {"schemas": {
+                "GetParams": {
+                    "type": "object",
+                    "properties": {"root": {"$ref": "#/components/schemas/Node"}},
+                },
+                "Node": {
+                    "type": "object",
+                    "properties": {
+                        "next": {"$ref": "#/components/schemas/Node"},
+                        "children": {"type": "array", "items": {"$ref": "#/components/schemas/Node"}},
+                    },
+                    "required": ["children"],
+                },
+                "TreeClient": {
+                    "type": "object",
+                    "properties": {"name": {"type": "string"}},
+                },
+            }},
+        });
+
+        let source = Generator::new().generate_value(&document).unwrap();
+
+        // Generated types clashing with a component are renamed.
+        assert!(source.contains("pub struct TreeClient<C"));
+        assert!(source.contains("pub struct TreeClient2 {"));
+        assert!(source.contains("RPCResult<TreeClient2>"));
+        assert!(source.contains("pub struct GetParams {\n"));
+        assert!(source.contains("pub struct GetParams2 {\n"));
+        assert!(source.contains("params: GetParams2,"));
+        assert!(source.contains("RPCResult<GetParams>"));
+
+        // Self-references are boxed, unless held by a collection.
+        assert!(source.contains("pub next: Option<Box<Node>>,"));
+        assert!(source.contains("pub children: Vec<Node>,"));
+        assert!(source.contains("pub root: Option<Node>,"));
+    }
+
+    #[futures_test::test]
+    async fn test_generated_client() {
+        let mut server = Server::new();
+
+        server
+            .handle("add", |_, (lhs, rhs): (i64, i64)| {
+                future::ready(Ok(lhs + rhs))
+            })
+            .handle("transfer", |_, params: Value| {
+                future::ready(Ok(json!({
+                    "id": format!("{}-{}", params["to"].as_str().unwrap(), params["amount"]),
+                    "status": "pending",
+                    "fee": {"amount": 1, "currency": params["currency"]},
+                })))
+            });
+
+        Harness::new(server)
+            .run(|client| async move {
+                let mut wallet = WalletClient::new(client);
+
+                assert_eq!(wallet.add(1, 2, None::<Timeout>).await.unwrap(), 3);
+
+                let receipt = wallet
+                    .transfer(
+                        TransferParams {
+                            to: "alice".to_owned(),
+                            amount: 10,
+                            currency: Currency::Eth,
+                            memo: None,
+                        },
+                        None::<Timeout>,
+                    )
+                    .await
+                    .unwrap();
+
+                assert_eq!(
+                    receipt,
+                    Receipt {
+                        id: "alice-10".to_owned(),
+                        status: crate::wallet::ReceiptStatus::Pending,
+                        fee: Some(crate::wallet::ReceiptFee {
+                            amount: 1,
+                            currency: Currency::Eth,
+                        }),
+                    }
+                );
+            })
+            .await;
+    }
+}
@@ -0,0 +1,59 @@
+//! `librpc-codegen`, generate a typed client from an OpenRPC document.
+
+use std::{fs, path::PathBuf, process::exit};
+
+use clap::{value_parser, Arg, Command};
+use librpc_codegen::Generator;
+
+fn command() -> Command {
+    Command::new("librpc-codegen")
+        .about("Generate a typed JSON-RPC client from an OpenRPC document")
+        .arg(
+            Arg::new("input")
+                .required(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("OpenRPC document"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_parser(value_parser!(PathBuf))
+                .help("Output file, default to stdout"),
+        )
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .value_parser(value_parser!(String))
+                .help("Client type name, default to the document title followed by Client"),
+        )
+}
+
+fn main() {
+    let matches = command().get_matches();
+
+    let mut generator = Generator::new();
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        generator = generator.name(name);
+    }
+
+    let input = matches.get_one::<PathBuf>("input").expect("required");
+
+    let result = fs::read_to_string(input)
+        .map_err(Into::into)
+        .and_then(|document| generator.generate(&document));
+
+    let result = result.and_then(|source| match matches.get_one::<PathBuf>("output") {
+        Some(output) => fs::write(output, source).map_err(Into::into),
+        None => {
+            print!("{}", source);
+            Ok(())
+        }
+    });
+
+    if let Err(err) = result {
+        eprintln!("{}: {}", input.display(), err);
+        exit(1);
+    }
+}
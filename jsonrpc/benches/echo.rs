@@ -1,30 +1,21 @@
-use std::thread::spawn;
+use std::{thread::spawn, time::Instant};
 
 use async_timer_rs::hashed::Timeout;
+use bytes::Bytes;
 use criterion::{async_executor::FuturesExecutor, *};
-use futures::{channel::mpsc::Receiver, executor::block_on, StreamExt};
+use futures::{executor::block_on, StreamExt};
+use librpc::{buffer::BufferPool, dispatcher::Dispatcher};
+use serde::Serialize;
+use serde_json::Value;
 
 use librpc_json::{
-    client::{Client, Responder},
-    object::Request,
+    client::Client,
+    harness::Harness,
+    object::{Request, Response, Version},
+    result::RPCError,
+    server::Server,
 };
 
-async fn echo(mut receiver: Receiver<(Option<u64>, Vec<u8>)>, responder: Responder) {
-    let mut i = 0;
-
-    while let Some((id, msg)) = receiver.next().await {
-        i += 1;
-
-        let request: Request<String, String> = serde_json::from_slice(&msg).unwrap();
-
-        let data = serde_json::to_vec(&request.params).unwrap();
-
-        responder.complete(id.unwrap(), Ok(data));
-    }
-
-    log::debug!("echo server exit with counter: {}", i)
-}
-
 async fn client(mut c: Client) {
     let echo = c
         .call::<String, String, Timeout>("hello", "world".to_string(), None)
@@ -37,17 +28,163 @@ async fn client(mut c: Client) {
 fn bench_jsonrpc(c: &mut Criterion) {
     _ = pretty_env_logger::try_init();
 
-    let (dispatcher, receiver, responder) = Client::new(100);
+    let mut group = c.benchmark_group("echo");
+
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("jsonrpc", |b| {
+        b.iter_custom(|iters| {
+            let mut server = Server::new();
 
-    spawn(move || block_on(echo(receiver, responder)));
+            server.handle("hello", |_, params: String| async move { Ok(params) });
 
-    c.bench_function("echo jsonrpc", |b| {
-        b.to_async(FuturesExecutor)
-            .iter(|| client(dispatcher.clone()))
+            block_on(Harness::new(server).cache_size(100).run(|c| async move {
+                let start = Instant::now();
+
+                for _ in 0..iters {
+                    client(c.clone()).await;
+                }
+
+                start.elapsed()
+            }))
+        })
     });
 
+    group.finish();
+
     log::debug!("exit bench_rpc");
 }
 
-criterion_group!(benches, bench_jsonrpc);
+fn bench_serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+
+    let buffers = BufferPool::default();
+
+    for len in [16, 4096] {
+        let request = Request {
+            id: Some(1),
+            method: "hello",
+            params: vec!["world"; len],
+            jsonrpc: Version,
+        };
+
+        // One allocation per frame.
+        group.bench_with_input(BenchmarkId::new("vec", len), &request, |b, request| {
+            b.iter(|| Bytes::from(serde_json::to_vec(request).unwrap()))
+        });
+
+        // Frames split off a reused chunk.
+        group.bench_with_input(BenchmarkId::new("pooled", len), &request, |b, request| {
+            b.iter(|| {
+                buffers
+                    .frame(|buf| serde_json::to_writer(buf, request))
+                    .unwrap()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Frame buffer type of the pipeline bench.
+trait Frame: AsRef<[u8]> + Send + Sync + Sized + 'static {
+    fn encode<T: Serialize>(buffers: &BufferPool, value: &T) -> Self;
+}
+
+/// Serialized into a fresh allocation, as before the switch to [`Bytes`].
+impl Frame for Vec<u8> {
+    fn encode<T: Serialize>(_: &BufferPool, value: &T) -> Self {
+        serde_json::to_vec(value).unwrap()
+    }
+}
+
+impl Frame for Bytes {
+    fn encode<T: Serialize>(buffers: &BufferPool, value: &T) -> Self {
+        buffers
+            .frame(|buf| serde_json::to_writer(buf, value))
+            .unwrap()
+    }
+}
+
+/// Start echo server thread decoding requests and encoding responses, returns the
+/// dispatcher of its client.
+fn serve<B: Frame>() -> Dispatcher<RPCError, B> {
+    let (dispatcher, mut receiver) = Dispatcher::<RPCError, B>::new(100);
+
+    let responder = dispatcher.responder.clone();
+
+    spawn(move || {
+        block_on(async move {
+            let buffers = BufferPool::default();
+
+            while let Some((id, frame)) = receiver.next().await {
+                let request: Request<&str, Vec<&str>> =
+                    serde_json::from_slice(frame.as_ref()).unwrap();
+
+                let response = Response::<&str, _, Value> {
+                    id: request.id.unwrap(),
+                    jsonrpc: Version,
+                    result: Some(request.params),
+                    error: None,
+                };
+
+                responder.complete(id.unwrap(), Ok(B::encode(&buffers, &response)));
+            }
+        })
+    });
+
+    dispatcher
+}
+
+/// Encode one echo request, dispatch it and decode the response.
+async fn echo<B: Frame>(mut dispatcher: Dispatcher<RPCError, B>, buffers: BufferPool, len: usize) {
+    let request = Request {
+        id: Some(1),
+        method: "echo",
+        params: vec!["world"; len],
+        jsonrpc: Version,
+    };
+
+    let frame = dispatcher
+        .call::<Timeout>(1, B::encode(&buffers, &request), None)
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+
+    let response: Response<String, Vec<&str>, Value> =
+        serde_json::from_slice(frame.as_ref()).unwrap();
+
+    assert_eq!(response.result.unwrap().len(), len);
+}
+
+/// Whole JSON-RPC frame pipeline with either buffer type: request encoding, dispatch,
+/// request decoding, response encoding and response decoding.
+fn bench_pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pipeline");
+
+    let vec = serve::<Vec<u8>>();
+
+    let bytes = serve::<Bytes>();
+
+    let buffers = BufferPool::default();
+
+    for len in [16, 4096] {
+        group.throughput(Throughput::Elements(1));
+
+        group.bench_with_input(BenchmarkId::new("vec", len), &len, |b, len| {
+            b.to_async(FuturesExecutor)
+                .iter(|| echo(vec.clone(), buffers.clone(), *len))
+        });
+
+        group.bench_with_input(BenchmarkId::new("bytes", len), &len, |b, len| {
+            b.to_async(FuturesExecutor)
+                .iter(|| echo(bytes.clone(), buffers.clone(), *len))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_jsonrpc, bench_serialize, bench_pipeline);
 criterion_main!(benches);
@@ -0,0 +1,96 @@
+//! Exponential backoff with random jitter
+
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Exponential backoff configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    /// Delay of the first attempt.
+    pub initial: Duration,
+    /// Upper bound of the delay.
+    pub max: Duration,
+    /// Factor the delay is multiplied by on every attempt.
+    pub multiplier: f64,
+    /// Jitter ratio in `[0, 1]`, the delay is scaled down by a random factor in `[1 - jitter, 1]`.
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns delay before retry `attempt`, counting from 0.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.initial.as_secs_f64() * self.multiplier.powi(attempt as i32);
+
+        // A negative or NaN multiplier must not panic, clamp the delay to `[0, max]`.
+        let delay = delay.max(0.0).min(self.max.as_secs_f64());
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+
+        let factor = if jitter > 0.0 {
+            rand::thread_rng().gen_range((1.0 - jitter)..=1.0)
+        } else {
+            1.0
+        };
+
+        Duration::try_from_secs_f64(delay * factor).unwrap_or(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Backoff;
+
+    #[test]
+    fn test_delay() {
+        let backoff = Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+            multiplier: 2.0,
+            jitter: 0.0,
+        };
+
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(400));
+        assert_eq!(backoff.delay(10), Duration::from_secs(1));
+
+        let backoff = Backoff {
+            jitter: 0.5,
+            ..backoff
+        };
+
+        for attempt in 0..10 {
+            let delay = backoff.delay(attempt);
+
+            assert!(delay <= Duration::from_secs(1));
+            assert!(delay >= Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn test_invalid_multiplier() {
+        for multiplier in [-2.0, f64::NAN, f64::INFINITY] {
+            let backoff = Backoff {
+                multiplier,
+                ..Default::default()
+            };
+
+            for attempt in 0..4 {
+                assert!(backoff.delay(attempt) <= backoff.max);
+            }
+        }
+    }
+}
@@ -0,0 +1,664 @@
+//! Multi-endpoint client with load balancing and failover
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_timer_rs::Timer;
+use futures::future::{self, Either};
+use rand::seq::index;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    breaker::CircuitBreaker,
+    client::Client,
+    codec::{Json, RPCCodec},
+    hedge::HedgePolicy,
+    object::{Error, ErrorCode},
+    result::{RPCError, RPCResult},
+};
+
+/// Endpoint selection strategy of [`Balancer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Rotate over the healthy endpoints in order.
+    #[default]
+    RoundRobin,
+    /// Pick the healthy endpoint with the fewest pending calls.
+    LeastPending,
+    /// Pick two random healthy endpoints, use the one with fewer pending calls.
+    PowerOfTwoChoices,
+}
+
+#[derive(Debug)]
+struct Endpoint<C> {
+    client: Client<C>,
+    /// Out of rotation until this instant.
+    ejected_until: Option<Instant>,
+    breaker: Option<CircuitBreaker>,
+}
+
+impl<C> Endpoint<C> {
+    fn is_healthy(&mut self, now: Instant) -> bool {
+        if let Some(breaker) = &self.breaker {
+            if !breaker.is_available() {
+                return false;
+            }
+        }
+
+        match self.ejected_until {
+            Some(until) if until > now => false,
+            Some(_) => {
+                // Cooldown elapsed, put back into rotation.
+                self.ejected_until = None;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Returns true if the circuit breaker of this endpoint rejects calls.
+    fn is_open(&self) -> bool {
+        self.breaker
+            .as_ref()
+            .map(|breaker| !breaker.is_available())
+            .unwrap_or(false)
+    }
+}
+
+/// JSONRPC client spreading calls across several endpoints.
+///
+/// An endpoint whose call fails with one of the failover error codes is removed from
+/// rotation for the cooldown period, and added back once it elapses.
+#[derive(Debug, Clone)]
+pub struct Balancer<C = Json> {
+    endpoints: Arc<Mutex<Vec<Endpoint<C>>>>,
+    next: Arc<AtomicUsize>,
+    strategy: Strategy,
+    cooldown: Duration,
+    failover: Vec<ErrorCode>,
+}
+
+impl<C> Balancer<C>
+where
+    C: RPCCodec,
+{
+    /// Create new balancer over `clients`, one per endpoint.
+    pub fn new(clients: Vec<Client<C>>, strategy: Strategy) -> Self {
+        let endpoints = clients
+            .into_iter()
+            .map(|client| Endpoint {
+                client,
+                ejected_until: None,
+                breaker: None,
+            })
+            .collect();
+
+        Self {
+            endpoints: Arc::new(Mutex::new(endpoints)),
+            next: Default::default(),
+            strategy,
+            cooldown: Duration::from_secs(10),
+            failover: vec![ErrorCode::ConnectionLost, ErrorCode::Timeout],
+        }
+    }
+
+    /// Set how long an unhealthy endpoint stays out of rotation.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Set error codes that remove the endpoint from rotation, see [`ErrorCode::matches`].
+    pub fn failover(mut self, codes: Vec<ErrorCode>) -> Self {
+        self.failover = codes;
+        self
+    }
+
+    /// Guard each endpoint with the circuit breaker created by `breaker` from the endpoint index.
+    ///
+    /// Endpoints with open breaker are out of rotation.
+    pub fn breakers<F>(self, breaker: F) -> Self
+    where
+        F: Fn(usize) -> CircuitBreaker,
+    {
+        for (index, endpoint) in self.endpoints.lock().unwrap().iter_mut().enumerate() {
+            endpoint.breaker = Some(breaker(index));
+        }
+
+        self
+    }
+
+    /// Returns the circuit breaker of endpoint `index`.
+    pub fn breaker(&self, index: usize) -> Option<CircuitBreaker> {
+        self.endpoints
+            .lock()
+            .unwrap()
+            .get(index)
+            .and_then(|endpoint| endpoint.breaker.clone())
+    }
+
+    /// Returns the number of endpoints, including the ones out of rotation.
+    pub fn len(&self) -> usize {
+        self.endpoints.lock().unwrap().len()
+    }
+
+    /// Returns true if this balancer has no endpoint.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove endpoint `index` from rotation for the cooldown period.
+    pub fn eject(&self, index: usize) {
+        if let Some(endpoint) = self.endpoints.lock().unwrap().get_mut(index) {
+            log::warn!("eject endpoint {} for {:?}", index, self.cooldown);
+            endpoint.ejected_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    /// Put endpoint `index` back into rotation immediately.
+    pub fn restore(&self, index: usize) {
+        if let Some(endpoint) = self.endpoints.lock().unwrap().get_mut(index) {
+            endpoint.ejected_until = None;
+        }
+    }
+
+    /// Select one healthy endpoint with the balance strategy.
+    pub fn pick(&self) -> RPCResult<(usize, Client<C>)> {
+        self.pick_except(None)
+    }
+
+    /// Select one healthy endpoint other than `except` with the balance strategy.
+    pub fn pick_except(&self, except: Option<usize>) -> RPCResult<(usize, Client<C>)> {
+        let mut endpoints = self.endpoints.lock().unwrap();
+
+        let now = Instant::now();
+
+        let healthy = endpoints
+            .iter_mut()
+            .enumerate()
+            .filter(|(index, _)| Some(*index) != except)
+            .filter_map(|(index, endpoint)| endpoint.is_healthy(now).then_some(index))
+            .collect::<Vec<_>>();
+
+        if healthy.is_empty() {
+            let all_open = endpoints
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| Some(*index) != except)
+                .map(|(_, endpoint)| endpoint.is_open())
+                .reduce(|lhs, rhs| lhs && rhs);
+
+            // Endpoints failing fast on their open breakers, not disconnected.
+            if all_open == Some(true) {
+                return Err(Error {
+                    code: ErrorCode::CircuitOpen,
+                    message: "All circuit breakers are open".to_owned(),
+                    data: None,
+                });
+            }
+
+            return Err(Error {
+                code: ErrorCode::ConnectionLost,
+                message: "No healthy endpoint".to_owned(),
+                data: None,
+            });
+        }
+
+        let pending = |index: &usize| endpoints[*index].client.pending();
+
+        let index = match self.strategy {
+            Strategy::RoundRobin => {
+                healthy[self.next.fetch_add(1, Ordering::SeqCst) % healthy.len()]
+            }
+            Strategy::LeastPending => *healthy.iter().min_by_key(|index| pending(index)).unwrap(),
+            Strategy::PowerOfTwoChoices => match healthy.as_slice() {
+                [index] => *index,
+                _ => {
+                    let picks = index::sample(&mut rand::thread_rng(), healthy.len(), 2);
+
+                    let (first, second) = (healthy[picks.index(0)], healthy[picks.index(1)]);
+
+                    if pending(&second) < pending(&first) {
+                        second
+                    } else {
+                        first
+                    }
+                }
+            },
+        };
+
+        Ok((index, endpoints[index].client.clone()))
+    }
+
+    /// Asynchronous send a JSONRPC v2.0 request to one endpoint and wait response.
+    pub async fn call<P, R, T>(&self, method: &str, params: P, timeout: Option<T>) -> RPCResult<R>
+    where
+        P: Serialize,
+        for<'b> R: Deserialize<'b> + Send + 'static,
+        T: Timer + Unpin,
+    {
+        let (index, mut client) = self.pick()?;
+
+        let id = client.next_id();
+
+        self.call_endpoint(index, &mut client, id, method, params, timeout)
+            .await
+    }
+
+    /// Asynchronous send a JSONRPC v2.0 request, hedged with `policy`.
+    ///
+    /// If the first endpoint has not answered after the hedge delay, the same call is sent to
+    /// another endpoint. The first success wins, the other call is cancelled with a
+    /// [`CANCEL_REQUEST`](crate::server::CANCEL_REQUEST) notification.
+    pub async fn hedged_call<P, R, T>(
+        &self,
+        method: &str,
+        params: P,
+        timeout: Option<Duration>,
+        policy: &HedgePolicy,
+    ) -> RPCResult<R>
+    where
+        P: Serialize,
+        for<'b> R: Deserialize<'b> + Send + 'static,
+        T: Timer + Unpin,
+    {
+        if !policy.is_hedged(method) {
+            return self.call(method, params, timeout.map(T::new)).await;
+        }
+
+        let (first_index, mut first) = self.pick()?;
+
+        let first_id = first.next_id();
+
+        let started = Instant::now();
+
+        let first_call = Box::pin(self.call_endpoint(
+            first_index,
+            &mut first,
+            first_id,
+            method,
+            &params,
+            timeout.map(T::new),
+        ));
+
+        let first_call = match future::select(first_call, T::new(policy.hedge_delay())).await {
+            Either::Left((result, _)) => {
+                if result.is_ok() {
+                    policy.observe(started.elapsed());
+                }
+
+                return result;
+            }
+            Either::Right((_, first_call)) => first_call,
+        };
+
+        let (second_index, mut second) = match self.pick_except(Some(first_index)) {
+            Ok(endpoint) => endpoint,
+            Err(_) => return first_call.await,
+        };
+
+        let second_id = second.next_id();
+
+        let second_started = Instant::now();
+
+        log::debug!(
+            "hedge call {} to endpoint {}, id {}",
+            method,
+            second_index,
+            second_id
+        );
+
+        let second_call = Box::pin(self.call_endpoint(
+            second_index,
+            &mut second,
+            second_id,
+            method,
+            &params,
+            timeout.map(T::new),
+        ));
+
+        let (result, latency, loser_index, loser_id) = match future::select(first_call, second_call)
+            .await
+        {
+            Either::Left((Ok(result), _)) => (result, started.elapsed(), second_index, second_id),
+            Either::Right((Ok(result), _)) => {
+                (result, second_started.elapsed(), first_index, first_id)
+            }
+            // Take the first success, wait for the other one.
+            Either::Left((Err(_), second_call)) => return second_call.await,
+            Either::Right((Err(_), first_call)) => return first_call.await,
+        };
+
+        // Latency of the winning attempt only, the hedge delay must not feed on itself.
+        policy.observe(latency);
+
+        // The losing call is dropped, ask its endpoint to stop working on it.
+        let mut loser = self.endpoints.lock().unwrap()[loser_index].client.clone();
+
+        if let Err(err) = loser.cancel(loser_id).await {
+            log::debug!("cancel hedged call {} failed: {}", loser_id, err);
+        }
+
+        Ok(result)
+    }
+
+    /// Asynchronous send a JSONRPC v2.0 notification to one endpoint.
+    pub async fn notification<P>(&self, method: &str, params: P) -> RPCResult<()>
+    where
+        P: Serialize,
+    {
+        let (index, mut client) = self.pick()?;
+
+        let result = client.notification(method, params).await;
+
+        if let Err(err) = &result {
+            self.on_error(index, err);
+        }
+
+        result
+    }
+
+    async fn call_endpoint<P, R, T>(
+        &self,
+        index: usize,
+        client: &mut Client<C>,
+        id: u64,
+        method: &str,
+        params: P,
+        timeout: Option<T>,
+    ) -> RPCResult<R>
+    where
+        P: Serialize,
+        for<'b> R: Deserialize<'b> + Send + 'static,
+        T: Timer + Unpin,
+    {
+        let call = client.call_with_id(id, method, params, timeout);
+
+        let result = match self.breaker(index) {
+            Some(breaker) => breaker.call(call).await,
+            None => call.await,
+        };
+
+        if let Err(err) = &result {
+            self.on_error(index, err);
+        }
+
+        result
+    }
+
+    fn on_error(&self, index: usize, err: &RPCError) {
+        if self.failover.iter().any(|code| code.matches(&err.code)) {
+            self.eject(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use async_timer_rs::{hashed::Timeout, Timer};
+    use futures::{future, FutureExt};
+    use librpc::transport::memory::{pair, pipe};
+
+    use crate::{
+        breaker::{BreakerConfig, CircuitBreaker},
+        client::Client,
+        hedge::{HedgeDelay, HedgePolicy},
+        object::{Error, ErrorCode},
+        result::RPCResult,
+        server::{Context, Server},
+        session::Session,
+    };
+
+    use super::{Balancer, Strategy};
+
+    #[test]
+    fn test_round_robin() {
+        let clients = (0..3).map(|_| Client::new(10).0).collect();
+
+        let balancer = Balancer::new(clients, Strategy::RoundRobin);
+
+        let picked = (0..4)
+            .map(|_| balancer.pick().unwrap().0)
+            .collect::<Vec<_>>();
+
+        assert_eq!(picked, vec![0, 1, 2, 0]);
+
+        balancer.eject(1);
+
+        assert_ne!(balancer.pick().unwrap().0, 1);
+        assert_ne!(balancer.pick().unwrap().0, 1);
+
+        balancer.restore(1);
+
+        let picked = (0..3)
+            .map(|_| balancer.pick().unwrap().0)
+            .collect::<Vec<_>>();
+
+        assert!(picked.contains(&1));
+    }
+
+    #[futures_test::test]
+    async fn test_all_breakers_open() {
+        let clients = (0..2).map(|_| Client::new(10).0).collect();
+
+        let balancer = Balancer::new(clients, Strategy::RoundRobin).breakers(|_| {
+            CircuitBreaker::new(BreakerConfig {
+                window: 1,
+                min_calls: 1,
+                ..Default::default()
+            })
+        });
+
+        let failure = || {
+            future::ready(RPCResult::<()>::Err(Error {
+                code: ErrorCode::Timeout,
+                message: "Timeout".to_owned(),
+                data: None,
+            }))
+        };
+
+        let breaker = balancer.breaker(0).unwrap();
+
+        breaker.call(failure()).await.unwrap_err();
+
+        assert_eq!(balancer.pick().unwrap().0, 1);
+
+        balancer.eject(1);
+
+        assert_eq!(balancer.pick().unwrap_err().code, ErrorCode::ConnectionLost);
+
+        balancer.restore(1);
+
+        let breaker = balancer.breaker(1).unwrap();
+
+        breaker.call(failure()).await.unwrap_err();
+
+        assert_eq!(balancer.pick().unwrap_err().code, ErrorCode::CircuitOpen);
+    }
+
+    #[futures_test::test]
+    async fn test_least_pending() {
+        let (busy, _output, _) = Client::new(10);
+        let (idle, _idle_output, _) = Client::new(10);
+
+        let mut caller = busy.clone();
+
+        // Keep one call in flight, nobody answers it.
+        let mut call = caller
+            .call::<_, String, Timeout>("hello", "world", None)
+            .boxed_local();
+
+        assert!(futures::poll!(&mut call).is_pending());
+
+        assert_eq!(busy.pending(), 1);
+
+        for strategy in [Strategy::LeastPending, Strategy::PowerOfTwoChoices] {
+            let balancer = Balancer::new(vec![busy.clone(), idle.clone()], strategy);
+
+            // Both endpoints are compared each time.
+            for _ in 0..10 {
+                assert_eq!(balancer.pick().unwrap().0, 1);
+            }
+
+            balancer.eject(1);
+
+            assert_eq!(balancer.pick().unwrap().0, 0);
+        }
+    }
+
+    #[futures_test::test]
+    async fn test_failover() {
+        let mut server = Server::new();
+
+        server.handle("echo", |_, params: String| async move { Ok(params) });
+
+        let (dead, dead_output, dead_responder) = Client::new(10);
+        let (alive, alive_output, alive_responder) = Client::new(10);
+
+        // Nobody answers calls to the dead endpoint.
+        let (dead_transport, _dead_input, _dead_peer) = pipe(10);
+        let (client_transport, server_transport) = pair(10);
+
+        let mut dead_session = Session::new(dead_output, dead_responder);
+        let mut alive_session = Session::new(alive_output, alive_responder);
+
+        let balancer = Balancer::new(vec![dead, alive], Strategy::RoundRobin)
+            .cooldown(Duration::from_millis(100));
+
+        let test = async move {
+            let err = balancer
+                .call::<_, String, Timeout>(
+                    "echo",
+                    "hello",
+                    Some(Timeout::new(Duration::from_millis(50))),
+                )
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::Timeout);
+
+            for _ in 0..3 {
+                let result = balancer
+                    .call::<_, String, Timeout>("echo", "hello", None)
+                    .await;
+
+                assert_eq!(result.unwrap(), "hello");
+            }
+
+            Timeout::new(Duration::from_millis(150)).await;
+
+            let picked = (0..2)
+                .map(|_| balancer.pick().unwrap().0)
+                .collect::<Vec<_>>();
+
+            assert!(picked.contains(&0));
+        };
+
+        let (dead_result, alive_result, server_result, _) = future::join4(
+            dead_session.run(dead_transport),
+            alive_session.run(client_transport),
+            server.accept(server_transport),
+            test,
+        )
+        .await;
+
+        dead_result.unwrap();
+        alive_result.unwrap();
+        server_result.unwrap();
+    }
+
+    #[futures_test::test]
+    async fn test_hedged_call() {
+        let tokens = Arc::new(Mutex::new(vec![]));
+
+        let handler_tokens = tokens.clone();
+
+        // The slow server never answers.
+        let mut slow = Server::new();
+
+        slow.handle("get", move |context: Context, _: String| {
+            handler_tokens
+                .lock()
+                .unwrap()
+                .push(context.cancellation.clone());
+
+            future::pending::<RPCResult<String>>()
+        });
+
+        let mut fast = Server::new();
+
+        fast.handle("get", |_, _: String| async move {
+            RPCResult::Ok("fast".to_owned())
+        });
+
+        let (slow_client, slow_output, slow_responder) = Client::new(10);
+        let (fast_client, fast_output, fast_responder) = Client::new(10);
+
+        let (slow_transport, slow_server_transport) = pair(10);
+        let (fast_transport, fast_server_transport) = pair(10);
+
+        let loser_responder = slow_responder.clone();
+
+        let mut slow_session = Session::new(slow_output, slow_responder);
+        let mut fast_session = Session::new(fast_output, fast_responder);
+
+        let balancer = Balancer::new(vec![slow_client, fast_client], Strategy::RoundRobin);
+
+        // Hedge after 20ms, then after the slowest observed latency.
+        let mut policy = HedgePolicy::new(HedgeDelay::Percentile(1.0)).method("get");
+
+        policy.fallback = Duration::from_millis(20);
+        policy.min_samples = 1;
+
+        let test = async move {
+            let result = balancer
+                .hedged_call::<_, String, Timeout>("get", "key", None, &policy)
+                .await;
+
+            assert_eq!(result.unwrap(), "fast");
+
+            // Latency of the hedged copy, not counted from the first send.
+            assert!(policy.hedge_delay() < Duration::from_millis(20));
+
+            // The losing call is cancelled on the slow server.
+            for _ in 0..10 {
+                if tokens.lock().unwrap()[0].is_cancelled() {
+                    // Its late "Request cancelled" response is dropped, not kept forever.
+                    Timeout::new(Duration::from_millis(20)).await;
+
+                    assert_eq!(loser_responder.buffered(), 0);
+
+                    return;
+                }
+
+                Timeout::new(Duration::from_millis(20)).await;
+            }
+
+            panic!("hedged call not cancelled");
+        };
+
+        let (slow_result, fast_result, slow_server_result, fast_server_result, _) = future::join5(
+            slow_session.run(slow_transport),
+            fast_session.run(fast_transport),
+            slow.accept(slow_server_transport),
+            fast.accept(fast_server_transport),
+            test,
+        )
+        .await;
+
+        slow_result.unwrap();
+        fast_result.unwrap();
+        slow_server_result.unwrap();
+        fast_server_result.unwrap();
+    }
+}
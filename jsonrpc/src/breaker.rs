@@ -0,0 +1,522 @@
+//! Client side circuit breaker
+
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Formatter},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    object::{Error, ErrorCode},
+    result::{RPCError, RPCResult},
+};
+
+/// State of [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Calls pass through, outcomes are recorded.
+    Closed,
+    /// Calls fail fast with [`ErrorCode::CircuitOpen`].
+    Open,
+    /// A limited number of probe calls pass through to test the endpoint.
+    HalfOpen,
+}
+
+/// [`CircuitBreaker`] configuration.
+#[derive(Debug, Clone)]
+pub struct BreakerConfig {
+    /// Number of recent call outcomes the error rate is computed on.
+    pub window: usize,
+    /// Minimum number of outcomes in the window before the error rate may trip the breaker.
+    pub min_calls: usize,
+    /// Trip when the ratio of failed calls in the window reaches this value, `0.0` trips on
+    /// the first failure.
+    pub error_rate: f64,
+    /// Trip after this number of consecutive timeouts, regardless of the error rate.
+    pub consecutive_timeouts: usize,
+    /// How long the breaker stays open before letting probe calls through.
+    pub open_duration: Duration,
+    /// Maximum number of concurrent probe calls in half-open state.
+    pub probes: usize,
+    /// Error codes counted as failures, see [`ErrorCode::matches`].
+    pub failures: Vec<ErrorCode>,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            min_calls: 10,
+            error_rate: 0.5,
+            consecutive_timeouts: 5,
+            open_duration: Duration::from_secs(30),
+            probes: 1,
+            failures: vec![
+                ErrorCode::Timeout,
+                ErrorCode::ConnectionLost,
+                ErrorCode::InternalError,
+            ],
+        }
+    }
+}
+
+/// Snapshot of [`CircuitBreaker`] counters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakerMetrics {
+    /// Current state.
+    pub state: BreakerState,
+    /// Calls passed through the breaker.
+    pub calls: u64,
+    /// Calls failed with one of the failure codes.
+    pub failures: u64,
+    /// Calls rejected while open.
+    pub rejected: u64,
+    /// Number of transitions into [`BreakerState::Open`].
+    pub trips: u64,
+}
+
+type Listener = Arc<dyn Fn(BreakerState, BreakerState) + Send + Sync>;
+
+#[derive(Debug)]
+struct BreakerImpl {
+    state: BreakerState,
+    opened_at: Option<Instant>,
+    /// Recent outcomes, true for failure.
+    outcomes: VecDeque<bool>,
+    timeouts: usize,
+    probes: usize,
+    metrics: BreakerMetrics,
+}
+
+/// Circuit breaker guarding the calls to one endpoint.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    config: Arc<BreakerConfig>,
+    inner: Arc<Mutex<BreakerImpl>>,
+    listener: Option<Listener>,
+}
+
+impl Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("config", &self.config)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl CircuitBreaker {
+    /// Create new breaker in closed state.
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            inner: Arc::new(Mutex::new(BreakerImpl {
+                state: BreakerState::Closed,
+                opened_at: None,
+                outcomes: Default::default(),
+                timeouts: 0,
+                probes: 0,
+                metrics: BreakerMetrics {
+                    state: BreakerState::Closed,
+                    calls: 0,
+                    failures: 0,
+                    rejected: 0,
+                    trips: 0,
+                },
+            })),
+            listener: None,
+        }
+    }
+
+    /// Set state change callback, invoked with the old and the new state.
+    pub fn on_state_change<F>(mut self, listener: F) -> Self
+    where
+        F: Fn(BreakerState, BreakerState) + Send + Sync + 'static,
+    {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Returns current state.
+    pub fn state(&self) -> BreakerState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// Returns snapshot of breaker counters.
+    pub fn metrics(&self) -> BreakerMetrics {
+        self.inner.lock().unwrap().metrics.clone()
+    }
+
+    /// Returns true if a call would pass through the breaker now.
+    pub fn is_available(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+
+        match inner.state {
+            BreakerState::Closed => true,
+            BreakerState::Open => self.open_elapsed(&inner),
+            BreakerState::HalfOpen => inner.probes < self.config.probes,
+        }
+    }
+
+    /// Run `call` through the breaker, fails fast with [`ErrorCode::CircuitOpen`] while open.
+    pub async fn call<F, R>(&self, call: F) -> RPCResult<R>
+    where
+        F: Future<Output = RPCResult<R>>,
+    {
+        let permit = self.acquire()?;
+
+        let result = call.await;
+
+        permit.complete(result.as_ref().err());
+
+        result
+    }
+
+    fn open_elapsed(&self, inner: &BreakerImpl) -> bool {
+        inner
+            .opened_at
+            .map(|opened_at| opened_at.elapsed() >= self.config.open_duration)
+            .unwrap_or(true)
+    }
+
+    fn acquire(&self) -> RPCResult<Permit<'_>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let mut transition = None;
+
+        if inner.state == BreakerState::Open && self.open_elapsed(&inner) {
+            transition = self.transition(&mut inner, BreakerState::HalfOpen);
+        }
+
+        let probe = match inner.state {
+            BreakerState::Closed => false,
+            BreakerState::HalfOpen if inner.probes < self.config.probes => {
+                inner.probes += 1;
+                true
+            }
+            _ => {
+                inner.metrics.rejected += 1;
+
+                drop(inner);
+
+                self.notify(transition);
+
+                return Err(Error {
+                    code: ErrorCode::CircuitOpen,
+                    message: "Circuit breaker is open".to_owned(),
+                    data: None,
+                });
+            }
+        };
+
+        inner.metrics.calls += 1;
+
+        drop(inner);
+
+        self.notify(transition);
+
+        Ok(Permit {
+            breaker: self,
+            probe,
+            completed: false,
+        })
+    }
+
+    fn record(&self, probe: bool, err: Option<&RPCError>) {
+        let code = err.map(|err| &err.code);
+
+        let failure = code
+            .map(|code| {
+                self.config
+                    .failures
+                    .iter()
+                    .any(|failure| failure.matches(code))
+            })
+            .unwrap_or(false);
+
+        let timeout = code == Some(&ErrorCode::Timeout);
+
+        let mut inner = self.inner.lock().unwrap();
+
+        if probe {
+            inner.probes -= 1;
+        }
+
+        if failure {
+            inner.metrics.failures += 1;
+        }
+
+        let transition = match inner.state {
+            // Outcome of a call started before the breaker opened, only probes decide.
+            BreakerState::HalfOpen if !probe => None,
+            BreakerState::HalfOpen if failure => self.transition(&mut inner, BreakerState::Open),
+            BreakerState::HalfOpen => self.transition(&mut inner, BreakerState::Closed),
+            BreakerState::Closed => {
+                if inner.outcomes.len() == self.config.window {
+                    inner.outcomes.pop_front();
+                }
+
+                inner.outcomes.push_back(failure);
+
+                inner.timeouts = if timeout { inner.timeouts + 1 } else { 0 };
+
+                let failures = inner.outcomes.iter().filter(|failure| **failure).count();
+
+                // Successful calls never trip the breaker, whatever the thresholds.
+                let tripped = (timeout && inner.timeouts >= self.config.consecutive_timeouts)
+                    || (failure
+                        && inner.outcomes.len() >= self.config.min_calls
+                        && failures as f64 >= self.config.error_rate * inner.outcomes.len() as f64);
+
+                if tripped {
+                    self.transition(&mut inner, BreakerState::Open)
+                } else {
+                    None
+                }
+            }
+            // Outcome of a call started before the breaker opened.
+            BreakerState::Open => None,
+        };
+
+        drop(inner);
+
+        self.notify(transition);
+    }
+
+    fn release(&self, probe: bool) {
+        if probe {
+            self.inner.lock().unwrap().probes -= 1;
+        }
+    }
+
+    fn transition(
+        &self,
+        inner: &mut BreakerImpl,
+        state: BreakerState,
+    ) -> Option<(BreakerState, BreakerState)> {
+        let from = inner.state;
+
+        inner.state = state;
+        inner.metrics.state = state;
+
+        match state {
+            BreakerState::Open => {
+                inner.opened_at = Some(Instant::now());
+                inner.metrics.trips += 1;
+            }
+            BreakerState::Closed => {
+                inner.outcomes.clear();
+                inner.timeouts = 0;
+            }
+            BreakerState::HalfOpen => {}
+        }
+
+        log::debug!("circuit breaker {:?} => {:?}", from, state);
+
+        Some((from, state))
+    }
+
+    fn notify(&self, transition: Option<(BreakerState, BreakerState)>) {
+        if let (Some(listener), Some((from, to))) = (&self.listener, transition) {
+            listener(from, to);
+        }
+    }
+}
+
+/// Call admitted by the breaker, releases the probe slot if dropped before completion.
+struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    completed: bool,
+}
+
+impl<'a> Permit<'a> {
+    fn complete(mut self, err: Option<&RPCError>) {
+        self.completed = true;
+        self.breaker.record(self.probe, err);
+    }
+}
+
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.breaker.release(self.probe);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use async_timer_rs::{hashed::Timeout, Timer};
+    use futures::{channel::oneshot, future};
+
+    use crate::{
+        object::{Error, ErrorCode},
+        result::RPCResult,
+    };
+
+    use super::{BreakerConfig, BreakerState, CircuitBreaker};
+
+    fn failure() -> future::Ready<RPCResult<()>> {
+        future::ready(Err(Error {
+            code: ErrorCode::Timeout,
+            message: "Timeout".to_owned(),
+            data: None,
+        }))
+    }
+
+    #[futures_test::test]
+    async fn test_breaker() {
+        let transitions = Arc::new(Mutex::new(vec![]));
+
+        let listener_transitions = transitions.clone();
+
+        let breaker = CircuitBreaker::new(BreakerConfig {
+            window: 4,
+            min_calls: 4,
+            error_rate: 0.5,
+            consecutive_timeouts: 3,
+            open_duration: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .on_state_change(move |from, to| listener_transitions.lock().unwrap().push((from, to)));
+
+        breaker
+            .call(future::ready(RPCResult::Ok(())))
+            .await
+            .unwrap();
+        breaker.call(failure()).await.unwrap_err();
+        breaker
+            .call(future::ready(RPCResult::Ok(())))
+            .await
+            .unwrap();
+
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        // 2 of 4 failed
+        breaker.call(failure()).await.unwrap_err();
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        let err = breaker
+            .call(future::ready(RPCResult::Ok(())))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code, ErrorCode::CircuitOpen);
+
+        Timeout::new(Duration::from_millis(100)).await;
+
+        // The probe fails, open again.
+        breaker.call(failure()).await.unwrap_err();
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        Timeout::new(Duration::from_millis(100)).await;
+
+        breaker
+            .call(future::ready(RPCResult::Ok(())))
+            .await
+            .unwrap();
+
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        // Consecutive timeouts trip the breaker before min calls reached.
+        for _ in 0..3 {
+            breaker.call(failure()).await.unwrap_err();
+        }
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        let metrics = breaker.metrics();
+
+        assert_eq!(metrics.trips, 3);
+        assert_eq!(metrics.rejected, 1);
+
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![
+                (BreakerState::Closed, BreakerState::Open),
+                (BreakerState::Open, BreakerState::HalfOpen),
+                (BreakerState::HalfOpen, BreakerState::Open),
+                (BreakerState::Open, BreakerState::HalfOpen),
+                (BreakerState::HalfOpen, BreakerState::Closed),
+                (BreakerState::Closed, BreakerState::Open),
+            ]
+        );
+    }
+
+    #[futures_test::test]
+    async fn test_zero_thresholds() {
+        let breaker = CircuitBreaker::new(BreakerConfig {
+            min_calls: 0,
+            error_rate: 0.0,
+            consecutive_timeouts: 0,
+            ..Default::default()
+        });
+
+        for _ in 0..3 {
+            breaker
+                .call(future::ready(RPCResult::Ok(())))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.call(failure()).await.unwrap_err();
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+    }
+
+    #[futures_test::test]
+    async fn test_half_open_probe() {
+        let breaker = CircuitBreaker::new(BreakerConfig {
+            window: 1,
+            min_calls: 1,
+            open_duration: Duration::from_millis(50),
+            ..Default::default()
+        });
+
+        let (sender, receiver) = oneshot::channel::<RPCResult<()>>();
+
+        // In flight while the breaker opens.
+        let mut call = Box::pin(breaker.call(async { receiver.await.unwrap() }));
+
+        assert!(futures::poll!(&mut call).is_pending());
+
+        breaker.call(failure()).await.unwrap_err();
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        Timeout::new(Duration::from_millis(100)).await;
+
+        let (probe_sender, probe_receiver) = oneshot::channel::<RPCResult<()>>();
+
+        let mut probe = Box::pin(breaker.call(async { probe_receiver.await.unwrap() }));
+
+        assert!(futures::poll!(&mut probe).is_pending());
+
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+
+        sender.send(Ok(())).unwrap();
+
+        call.await.unwrap();
+
+        // Not a probe, the breaker is still waiting for the probe outcome.
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+
+        probe_sender.send(Ok(())).unwrap();
+
+        probe.await.unwrap();
+
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+}
@@ -0,0 +1,82 @@
+//! Cancellation token for JSONRPC server handlers
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+#[derive(Debug, Default)]
+struct CancellationImpl {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+/// Shared cancellation flag, fired when the client cancels the request or disconnects.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationImpl>,
+}
+
+impl CancellationToken {
+    /// Create new token in uncancelled state.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Fire this token and wake up all [`Cancelled`] futures.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        let wakers = std::mem::take(&mut *self.inner.wakers.lock().unwrap());
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Returns true if this token has been fired.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Create a future that completes when this token is fired.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            token: self.clone(),
+        }
+    }
+}
+
+/// Future returned by [`CancellationToken::cancelled`]
+#[derive(Debug)]
+pub struct Cancelled {
+    token: CancellationToken,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let mut wakers = self.token.inner.wakers.lock().unwrap();
+
+        // Check again with the lock held, `cancel` may fire between the two checks.
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
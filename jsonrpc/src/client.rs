@@ -1,31 +1,54 @@
-use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc,
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use async_timer_rs::Timer;
+use bytes::Bytes;
 use futures::channel::mpsc::Receiver;
-use librpc::dispatcher::Dispatcher;
+use librpc::{buffer::BufferPool, dispatcher::Dispatcher};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::{
-    object::{Request, Version},
+    codec::{Json, RPCCodec},
+    object::{Protocol, Request, RequestV1, Version},
     result::{RPCError, RPCResult},
+    retry::RetryPolicy,
+    server::{CancelParams, CANCEL_REQUEST},
 };
 
-/// JSONRPC V2.0 client
+/// JSONRPC V2.0 client, objects are encoded with codec `C`.
 #[derive(Debug, Clone)]
-pub struct Client {
+pub struct Client<C = Json> {
     id_gen: Arc<AtomicU64>,
-    dispatcher: Dispatcher<Vec<u8>, Vec<u8>, RPCError>,
+    pending: Arc<AtomicUsize>,
+    buffers: BufferPool,
+    codec: C,
+    protocol: Protocol,
+    dispatcher: Dispatcher<RPCError>,
 }
 
-pub type Responder = librpc::responder::Responder<Vec<u8>, RPCError>;
-pub type Output = Receiver<(Option<u64>, Vec<u8>)>;
+pub type Responder = librpc::responder::Responder<RPCError>;
+pub type Output = Receiver<(Option<u64>, Bytes)>;
 
 impl Client {
     /// Create new JSONRPC client instance with sending cache quene length.
     pub fn new(cache_size: usize) -> (Self, Output, Responder) {
+        Self::with_codec(cache_size, Json)
+    }
+}
+
+impl<C> Client<C>
+where
+    C: RPCCodec,
+{
+    /// Create new JSONRPC client instance encoding objects with `codec`.
+    pub fn with_codec(cache_size: usize, codec: C) -> (Self, Output, Responder) {
         let (dispatcher, receiver) = Dispatcher::new(cache_size);
 
         let responder = dispatcher.responder.clone();
@@ -33,6 +56,10 @@ impl Client {
         (
             Client {
                 id_gen: Default::default(),
+                pending: Default::default(),
+                buffers: Default::default(),
+                codec,
+                protocol: Default::default(),
                 dispatcher,
             },
             receiver,
@@ -40,6 +67,25 @@ impl Client {
         )
     }
 
+    /// Set the protocol generation of sent requests, default is [`Protocol::V2`].
+    ///
+    /// Responses of both generations are accepted regardless.
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+
+        self
+    }
+
+    /// Returns the number of calls waiting for response, shared by all clones of this client.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Generate a new request id.
+    pub fn next_id(&self) -> u64 {
+        self.id_gen.fetch_add(1, Ordering::SeqCst)
+    }
+
     /// Asynchronous send a JSONRPC v2.0 request and wait response
     pub async fn call<P, R, T>(
         &mut self,
@@ -52,20 +98,116 @@ impl Client {
         for<'b> R: Deserialize<'b> + Send + 'static,
         T: Timer + Unpin,
     {
-        let id = self.id_gen.fetch_add(1, Ordering::SeqCst);
+        let id = self.next_id();
+
+        self.call_with_id(id, method, params, timeout).await
+    }
+
+    /// Asynchronous send a JSONRPC v2.0 request with `id` from [`Client::next_id`] and wait response
+    pub async fn call_with_id<P, R, T>(
+        &mut self,
+        id: u64,
+        method: &str,
+        params: P,
+        timeout: Option<T>,
+    ) -> RPCResult<R>
+    where
+        P: Serialize,
+        for<'b> R: Deserialize<'b> + Send + 'static,
+        T: Timer + Unpin,
+    {
+        let result = self.call_frame(id, method, params, timeout).await?;
+
+        Ok(self.codec.decode(&result)?)
+    }
+
+    /// Asynchronous send a JSONRPC v2.0 request and returns the `result` as the
+    /// [`RPCCodec::Value`] of the client codec, [`RawValue`](serde_json::value::RawValue)
+    /// for [`Json`].
+    ///
+    /// Useful to forward results without decoding them to a typed value.
+    pub async fn call_raw<P, T>(
+        &mut self,
+        method: &str,
+        params: P,
+        timeout: Option<T>,
+    ) -> RPCResult<C::Value>
+    where
+        P: Serialize,
+        T: Timer + Unpin,
+    {
+        let id = self.next_id();
+
+        let result = self.call_frame(id, method, params, timeout).await?;
+
+        Ok(self.codec.decode(&result)?)
+    }
+
+    /// Send request `id` and returns the raw `result` frame.
+    async fn call_frame<P, T>(
+        &mut self,
+        id: u64,
+        method: &str,
+        params: P,
+        timeout: Option<T>,
+    ) -> RPCResult<Bytes>
+    where
+        P: Serialize,
+        T: Timer + Unpin,
+    {
+        let _pending = PendingGuard::new(&self.pending);
+
+        let data = self
+            .request_frame(Some(id), method, params)
+            .expect("Inner error, assembly json request");
+
+        self.dispatcher
+            .call(id, data, timeout)
+            .await?
+            .await
+            .map_err(RPCError::map_timeout)
+    }
+
+    /// Asynchronous send a JSONRPC v2.0 request, retrying transient failures with `policy`.
+    ///
+    /// Each attempt gets a fresh request id and its own `timeout` timer.
+    pub async fn call_with_retry<P, R, T>(
+        &mut self,
+        method: &str,
+        params: P,
+        timeout: Option<Duration>,
+        policy: &RetryPolicy,
+    ) -> RPCResult<R>
+    where
+        P: Serialize,
+        for<'b> R: Deserialize<'b> + Send + 'static,
+        T: Timer + Unpin,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let err = match self.call(method, &params, timeout.map(T::new)).await {
+                Ok(result) => return Ok(result),
+                Err(err) => err,
+            };
 
-        let request = Request {
-            id: Some(id),
-            method,
-            params,
-            jsonrpc: Version::default(),
-        };
+            if !policy.should_retry(method, attempt, &err) {
+                return Err(err);
+            }
 
-        let data = serde_json::to_vec(&request).expect("Inner error, assembly json request");
+            let delay = policy.backoff.delay(attempt);
 
-        let result = self.dispatcher.call(id, data, timeout).await?.await?;
+            log::debug!("retry {} in {:?}, {}", method, delay, err);
 
-        Ok(serde_json::from_slice(&result)?)
+            T::new(delay).await;
+
+            attempt += 1;
+        }
+    }
+
+    /// Ask the server to cancel request `id` with a [`CANCEL_REQUEST`] notification.
+    pub async fn cancel(&mut self, id: u64) -> RPCResult<()> {
+        self.notification(CANCEL_REQUEST, CancelParams { id }).await
     }
 
     /// Asynchronous send a JSONRPC v2.0 notification
@@ -73,17 +215,58 @@ impl Client {
     where
         P: Serialize,
     {
-        let request = Request {
-            method,
-            params,
-            id: None,
-            jsonrpc: Version::default(),
-        };
-
-        let data = serde_json::to_vec(&request)?;
+        let data = self.request_frame(None, method, params)?;
 
         self.dispatcher.notification(data).await?;
 
         Ok(())
     }
+
+    /// Assembly request frame of [`Client::protocol`] generation, `id` is `None` for notification.
+    fn request_frame<P>(&self, id: Option<u64>, method: &str, params: P) -> io::Result<Bytes>
+    where
+        P: Serialize,
+    {
+        match self.protocol {
+            Protocol::V1 => {
+                // JSON-RPC 1.0 params are always an array.
+                let params = match serde_json::to_value(params)? {
+                    Value::Array(params) => params,
+                    Value::Null => vec![],
+                    params => vec![params],
+                };
+
+                let request = RequestV1 { id, method, params };
+
+                self.buffers.frame(|buf| self.codec.encode(&request, buf))
+            }
+            Protocol::V2 => {
+                let request = Request {
+                    id,
+                    method,
+                    params,
+                    jsonrpc: Version,
+                };
+
+                self.buffers.frame(|buf| self.codec.encode(&request, buf))
+            }
+        }
+    }
+}
+
+/// Count one pending call until dropped.
+struct PendingGuard(Arc<AtomicUsize>);
+
+impl PendingGuard {
+    fn new(pending: &Arc<AtomicUsize>) -> Self {
+        pending.fetch_add(1, Ordering::SeqCst);
+
+        Self(pending.clone())
+    }
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
@@ -0,0 +1,345 @@
+//! JSONRPC object encoding
+
+use std::{fmt::Debug, io};
+
+use bytes::Bytes;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{
+    value::{to_raw_value, RawValue},
+    Value,
+};
+
+pub use librpc::codec::*;
+
+use crate::{
+    object::{Error, ErrorCode, Protocol, Response, ResponseV1, Version},
+    result::{RPCError, RPCResult},
+};
+
+/// Response of either protocol generation, 1.0 errors may be any value.
+#[derive(Deserialize)]
+struct AnyResponse<R> {
+    id: u64,
+    result: Option<R>,
+    error: Option<Value>,
+}
+
+fn response_error(error: Value) -> RPCError {
+    serde_json::from_value(error.clone()).unwrap_or_else(|_| Error {
+        code: ErrorCode::InternalError,
+        message: match &error {
+            Value::String(message) => message.clone(),
+            _ => "JSON-RPC 1.0 error".to_owned(),
+        },
+        data: Some(error),
+    })
+}
+
+fn encode_response<C, R, W>(
+    codec: &C,
+    protocol: Protocol,
+    id: u64,
+    result: R,
+    writer: W,
+) -> io::Result<()>
+where
+    C: Codec,
+    R: serde::Serialize,
+    W: io::Write,
+{
+    match protocol {
+        Protocol::V1 => codec.encode(
+            &ResponseV1::<String, R, Value> {
+                id,
+                result: Some(result),
+                error: None,
+            },
+            writer,
+        ),
+        Protocol::V2 => codec.encode(
+            &Response::<String, R, Value> {
+                id,
+                jsonrpc: Version,
+                result: Some(result),
+                error: None,
+            },
+            writer,
+        ),
+    }
+}
+
+/// [`Codec`] able to carry JSONRPC objects.
+///
+/// Params and results are exchanged as [`RPCCodec::Value`], the dynamic value of the
+/// codec, so everything the format can carry, e.g. binary strings or integer map keys,
+/// survives without conversion to JSON.
+pub trait RPCCodec: Codec {
+    /// Dynamic value of this codec.
+    type Value: Serialize + DeserializeOwned + Clone + Debug + Send + Sync + 'static;
+
+    /// Convert `value` to the dynamic value of this codec.
+    fn encode_value<T: Serialize>(&self, value: &T) -> io::Result<Self::Value> {
+        let mut buf = vec![];
+
+        self.encode(value, &mut buf)?;
+
+        self.decode(&buf)
+    }
+
+    /// Convert the dynamic `value` of this codec to `T`.
+    fn decode_value<T: DeserializeOwned>(&self, value: Self::Value) -> io::Result<T> {
+        let mut buf = vec![];
+
+        self.encode(&value, &mut buf)?;
+
+        self.decode(&buf)
+    }
+
+    /// Write the `protocol` success response to request `id` to `writer`.
+    fn encode_result<W: io::Write>(
+        &self,
+        protocol: Protocol,
+        id: u64,
+        result: &Self::Value,
+        writer: W,
+    ) -> io::Result<()> {
+        encode_response(self, protocol, id, result, writer)
+    }
+
+    /// Decode response `frame` of either protocol generation, returns the request id
+    /// and the result encoded by this codec.
+    fn decode_response(&self, frame: &Bytes) -> io::Result<(u64, RPCResult<Bytes>)> {
+        let response = self.decode::<AnyResponse<Self::Value>>(frame)?;
+
+        let result = match response.error {
+            Some(err) => Err(response_error(err)),
+            None => {
+                let mut buf = vec![];
+
+                // Omitted result is encoded as null.
+                self.encode(&response.result, &mut buf)?;
+
+                Ok(Bytes::from(buf))
+            }
+        };
+
+        Ok((response.id, result))
+    }
+}
+
+impl RPCCodec for Json {
+    type Value = Box<RawValue>;
+
+    fn encode_value<T: Serialize>(&self, value: &T) -> io::Result<Self::Value> {
+        Ok(to_raw_value(value)?)
+    }
+
+    fn decode_value<T: DeserializeOwned>(&self, value: Self::Value) -> io::Result<T> {
+        Ok(serde_json::from_str(value.get())?)
+    }
+
+    /// The result is sliced out of `frame` without copy.
+    fn decode_response(&self, frame: &Bytes) -> io::Result<(u64, RPCResult<Bytes>)> {
+        let response = serde_json::from_slice::<AnyResponse<&RawValue>>(frame)?;
+
+        let result = match (response.error, response.result) {
+            (Some(err), _) => Err(response_error(err)),
+            (None, Some(result)) => Ok(frame.slice_ref(result.get().as_bytes())),
+            (None, None) => Ok(Bytes::from_static(b"null")),
+        };
+
+        Ok((response.id, result))
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl RPCCodec for MessagePack {
+    type Value = rmpv::Value;
+}
+
+#[cfg(feature = "cbor")]
+impl RPCCodec for Cbor {
+    type Value = ciborium::Value;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use async_timer_rs::hashed::Timeout;
+    use futures::future;
+    use librpc::transport::memory::pair;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+
+    use crate::{
+        client::Client,
+        object::{Error, ErrorCode},
+        result::RPCResult,
+        server::{Context, Server},
+        session::Session,
+    };
+
+    use super::RPCCodec;
+
+    /// Payload JSON can't carry as is.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Blob {
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+        tags: BTreeMap<u32, String>,
+    }
+
+    /// Echo calls over `codec`, `check` receives the [`Blob`] params as seen by the server.
+    async fn echo<C: RPCCodec>(codec: C, check: fn(C::Value)) {
+        let mut server = Server::with_codec(codec.clone());
+
+        server
+            .handle("echo", |_: Context, params: Value| {
+                future::ready(Ok(params))
+            })
+            .handle("blob", |_: Context, blob: Blob| future::ready(Ok(blob)))
+            .handle("raw", |_: Context, params: C::Value| {
+                future::ready(Ok(params))
+            })
+            .handle("fail", |_: Context, _: ()| {
+                future::ready(RPCResult::<()>::Err(Error {
+                    code: ErrorCode::ServerError(-32000, "".to_owned()),
+                    message: "Failed".to_owned(),
+                    data: Some(json!({"retry": false})),
+                }))
+            });
+
+        let (client_transport, server_transport) = pair(10);
+
+        let (mut client, output, responder) = Client::with_codec(10, codec.clone());
+
+        let mut session = Session::with_codec(output, responder, codec);
+
+        let test = async move {
+            let params = json!({"name": "world", "list": [1, -32601], "none": null});
+
+            let result = client
+                .call::<_, Value, Timeout>("echo", &params, None)
+                .await
+                .unwrap();
+
+            assert_eq!(result, params);
+
+            let blob = Blob {
+                data: vec![0, 1, 255],
+                tags: BTreeMap::from([(7, "seven".to_owned())]),
+            };
+
+            let result = client
+                .call::<_, Blob, Timeout>("blob", &blob, None)
+                .await
+                .unwrap();
+
+            assert_eq!(result, blob);
+
+            let result = client
+                .call_raw::<_, Timeout>("raw", &blob, None)
+                .await
+                .unwrap();
+
+            check(result);
+
+            let err = client
+                .call::<_, (), Timeout>("fail", (), None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::ServerError(-32000, "".to_owned()));
+            assert_eq!(err.data, Some(json!({"retry": false})));
+
+            let err = client
+                .call::<_, (), Timeout>("unknown", (), None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::MethodNotFound);
+        };
+
+        let (_, _, _) = future::join3(
+            server.accept(server_transport),
+            session.run(client_transport),
+            test,
+        )
+        .await;
+    }
+
+    #[futures_test::test]
+    async fn test_json() {
+        echo(super::Json, |result| {
+            assert_eq!(result.get(), r#"{"data":[0,1,255],"tags":{"7":"seven"}}"#)
+        })
+        .await;
+    }
+
+    #[test]
+    fn test_decode_error() {
+        // Error objects of the wire never decode to local errors.
+        for error in [
+            json!({"code": -32603, "message": "failed", "local": "Timeout"}),
+            json!({"code": -32603, "message": "failed", "data": {"io": "TimedOut"}}),
+        ] {
+            let frame = json!({"jsonrpc": "2.0", "id": 1, "error": error}).to_string();
+
+            let (id, result) = super::Json.decode_response(&frame.into()).unwrap();
+
+            let err = result.unwrap_err();
+
+            assert_eq!(id, 1);
+            assert_eq!(err.code, ErrorCode::InternalError);
+            assert!(!err.code.is_local());
+        }
+
+        // 1.0 errors may be any value.
+        let frame = json!({"id": 2, "result": null, "error": "failed"}).to_string();
+
+        let (_, result) = super::Json.decode_response(&frame.into()).unwrap();
+
+        assert_eq!(result.unwrap_err().message, "failed");
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[futures_test::test]
+    async fn test_msgpack() {
+        use rmpv::Value;
+
+        echo(super::MessagePack, |result| {
+            assert_eq!(
+                result,
+                Value::Map(vec![
+                    (Value::from("data"), Value::Binary(vec![0, 1, 255])),
+                    (
+                        Value::from("tags"),
+                        Value::Map(vec![(Value::from(7), Value::from("seven"))])
+                    ),
+                ])
+            )
+        })
+        .await;
+    }
+
+    #[cfg(feature = "cbor")]
+    #[futures_test::test]
+    async fn test_cbor() {
+        use ciborium::Value;
+
+        echo(super::Cbor, |result| {
+            assert_eq!(
+                result,
+                Value::Map(vec![
+                    (Value::from("data"), Value::Bytes(vec![0, 1, 255])),
+                    (
+                        Value::from("tags"),
+                        Value::Map(vec![(Value::from(7), Value::from("seven"))])
+                    ),
+                ])
+            )
+        })
+        .await;
+    }
+}
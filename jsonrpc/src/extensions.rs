@@ -0,0 +1,111 @@
+//! Values attached to the connection or request carrying a call
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+
+/// Type map of the handler [`Context::extensions`](crate::server::Context::extensions),
+/// holding at most one value per type.
+///
+/// Values are shared by the clones of a map.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+    /// Extensions of the HTTP request, looked up after `map`.
+    #[cfg(feature = "http")]
+    http: Option<http::Extensions>,
+}
+
+impl Extensions {
+    /// Create new empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert `value`, replacing the previous value of type `T`.
+    pub fn insert<T>(&mut self, value: T)
+    where
+        T: Send + Sync + 'static,
+    {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Returns the value of type `T`.
+    pub fn get<T>(&self) -> Option<&T>
+    where
+        T: Send + Sync + 'static,
+    {
+        let value = self
+            .map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref());
+
+        #[cfg(feature = "http")]
+        let value = value.or_else(|| self.http.as_ref().and_then(|http| http.get()));
+
+        value
+    }
+}
+
+impl Debug for Extensions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Wrap the extensions of an HTTP request, e.g. inserted by a middleware.
+#[cfg(feature = "http")]
+impl From<http::Extensions> for Extensions {
+    fn from(extensions: http::Extensions) -> Self {
+        Self {
+            map: Default::default(),
+            http: Some(extensions),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Extensions;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Identity(&'static str);
+
+    #[test]
+    fn test_extensions() {
+        let mut extensions = Extensions::new();
+
+        assert_eq!(extensions.get::<Identity>(), None);
+
+        extensions.insert(Identity("alice"));
+        extensions.insert(Identity("bob"));
+        extensions.insert(1u32);
+
+        let clone = extensions.clone();
+
+        assert_eq!(clone.get::<Identity>(), Some(&Identity("bob")));
+        assert_eq!(clone.get::<u32>(), Some(&1));
+        assert_eq!(clone.get::<u64>(), None);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_extensions() {
+        let mut http = http::Extensions::new();
+
+        http.insert(Identity("alice"));
+        http.insert(1u32);
+
+        let mut extensions = Extensions::from(http);
+
+        extensions.insert(2u32);
+
+        assert_eq!(extensions.get::<Identity>(), Some(&Identity("alice")));
+        assert_eq!(extensions.get::<u32>(), Some(&2));
+    }
+}
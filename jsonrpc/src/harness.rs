@@ -0,0 +1,260 @@
+//! In memory test harness, running a [`Server`] and a connected [`Client`] without sockets
+
+use std::{
+    fmt::{Debug, Formatter},
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::{
+    future::{self, Either},
+    pin_mut, Sink, Stream,
+};
+use librpc::transport::{memory::pair, Direction, Transport};
+
+use crate::{client::Client, codec::RPCCodec, server::Server, session::Session};
+
+type Hook = Arc<dyn Fn(Direction, &Bytes) + Send + Sync>;
+
+/// Frames exchanged over a [`Harness`] connection, with direction relative to the client.
+#[derive(Debug, Clone, Default)]
+pub struct Frames(Arc<Mutex<Vec<(Direction, Bytes)>>>);
+
+impl Frames {
+    /// Take all frames recorded so far, in exchange order.
+    pub fn take(&self) -> Vec<(Direction, Bytes)> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    fn push(&self, direction: Direction, frame: &Bytes) {
+        self.0.lock().unwrap().push((direction, frame.clone()));
+    }
+}
+
+/// Connect a [`Client`] to a [`Server`] in memory and run a test against it.
+///
+/// Every frame is recorded, see [`Harness::frames`], and passed to the
+/// [`Harness::on_frame`] hooks.
+pub struct Harness<C: RPCCodec> {
+    server: Server<C>,
+    cache_size: usize,
+    hooks: Vec<Hook>,
+    frames: Frames,
+}
+
+impl<C: RPCCodec> Debug for Harness<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Harness")
+            .field("cache_size", &self.cache_size)
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+impl<C> Harness<C>
+where
+    C: RPCCodec,
+{
+    /// Create new harness serving `server`, the client uses the server codec.
+    pub fn new(server: Server<C>) -> Self {
+        Self {
+            server,
+            cache_size: 10,
+            hooks: vec![],
+            frames: Default::default(),
+        }
+    }
+
+    /// Set the client sending cache and the transport buffer length, default is 10.
+    pub fn cache_size(mut self, cache_size: usize) -> Self {
+        self.cache_size = cache_size;
+
+        self
+    }
+
+    /// Add `hook` called with every frame before it is delivered, e.g. to assert on it.
+    pub fn on_frame<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Direction, &Bytes) + Send + Sync + 'static,
+    {
+        self.hooks.push(Arc::new(hook));
+
+        self
+    }
+
+    /// Returns the frames recorded by this harness.
+    pub fn frames(&self) -> Frames {
+        self.frames.clone()
+    }
+
+    /// Run `test` with a connected client, returns the `test` output.
+    ///
+    /// The server and the client session are dropped once `test` completes.
+    pub async fn run<F, Fut>(self, test: F) -> Fut::Output
+    where
+        F: FnOnce(Client<C>) -> Fut,
+        Fut: Future,
+    {
+        let codec = self.server.codec().clone();
+
+        let (client, output, responder) = Client::with_codec(self.cache_size, codec.clone());
+
+        let mut session = Session::with_codec(output, responder, codec);
+
+        let (client_transport, server_transport) = pair(self.cache_size);
+
+        let client_transport = Tap {
+            inner: client_transport,
+            hooks: self.hooks,
+            frames: self.frames,
+        };
+
+        let server = self.server;
+
+        let connection = future::join(
+            server.accept(server_transport),
+            session.run(client_transport),
+        );
+
+        let test = test(client);
+
+        pin_mut!(connection, test);
+
+        match future::select(test, connection).await {
+            Either::Left((output, _)) => output,
+            Either::Right(((server, session), test)) => {
+                log::debug!(
+                    "harness connection closed, server {:?}, session {:?}",
+                    server,
+                    session
+                );
+
+                test.await
+            }
+        }
+    }
+}
+
+/// Transport calling the harness hooks with every frame.
+struct Tap<T> {
+    inner: T,
+    hooks: Vec<Hook>,
+    frames: Frames,
+}
+
+impl<T> Tap<T> {
+    fn tap(&self, direction: Direction, frame: &Bytes) {
+        self.frames.push(direction, frame);
+
+        for hook in &self.hooks {
+            hook(direction, frame);
+        }
+    }
+}
+
+impl<T> Stream for Tap<T>
+where
+    T: Transport<Bytes>,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            self.tap(Direction::Inbound, frame);
+        }
+
+        poll
+    }
+}
+
+impl<T> Sink<Bytes> for Tap<T>
+where
+    T: Transport<Bytes>,
+{
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> io::Result<()> {
+        self.tap(Direction::Outbound, &item);
+
+        Pin::new(&mut self.inner).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use async_timer_rs::hashed::Timeout;
+    use bytes::Bytes;
+    use librpc::transport::Direction;
+
+    use crate::server::Server;
+
+    use super::Harness;
+
+    #[futures_test::test]
+    async fn test_harness() {
+        let mut server = Server::new();
+
+        server.handle("echo", |_, params: String| async move { Ok(params) });
+
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let hook_counter = counter.clone();
+
+        let harness = Harness::new(server).on_frame(move |_, _| {
+            hook_counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let frames = harness.frames();
+
+        let echo = harness
+            .run(|mut client| async move {
+                client
+                    .call::<_, String, Timeout>("echo", "hello", None)
+                    .await
+            })
+            .await;
+
+        assert_eq!(echo.unwrap(), "hello");
+
+        assert_eq!(
+            frames.take(),
+            vec![
+                (
+                    Direction::Outbound,
+                    Bytes::from_static(
+                        br#"{"id":0,"jsonrpc":"2.0","method":"echo","params":"hello"}"#
+                    )
+                ),
+                (
+                    Direction::Inbound,
+                    Bytes::from_static(br#"{"id":0,"jsonrpc":"2.0","result":"hello"}"#)
+                ),
+            ]
+        );
+
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+}
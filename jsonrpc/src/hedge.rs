@@ -0,0 +1,125 @@
+//! Hedged request policy
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// When to send the hedged copy of a call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HedgeDelay {
+    /// After a fixed delay.
+    Fixed(Duration),
+    /// After the given percentile, in `(0, 1]`, of recently observed latencies.
+    Percentile(f64),
+}
+
+/// Hedged request policy of [`Balancer::hedged_call`](crate::balance::Balancer::hedged_call).
+///
+/// Only read-only methods in the allowlist are hedged, clones of a policy share
+/// the observed latencies.
+#[derive(Debug, Clone)]
+pub struct HedgePolicy {
+    /// Hedge delay.
+    pub delay: HedgeDelay,
+    /// Delay used by [`HedgeDelay::Percentile`] until enough latencies are observed.
+    pub fallback: Duration,
+    /// Minimum observed latencies before [`HedgeDelay::Percentile`] applies.
+    pub min_samples: usize,
+    /// Number of recent latencies kept.
+    pub window: usize,
+    /// Read-only methods safe to be sent twice.
+    pub methods: HashSet<String>,
+    latencies: Arc<Mutex<VecDeque<Duration>>>,
+}
+
+impl HedgePolicy {
+    /// Create new policy with `delay`.
+    pub fn new(delay: HedgeDelay) -> Self {
+        Self {
+            delay,
+            fallback: Duration::from_millis(100),
+            min_samples: 20,
+            window: 1000,
+            methods: Default::default(),
+            latencies: Default::default(),
+        }
+    }
+
+    /// Add read-only `method` to the allowlist.
+    pub fn method(mut self, method: &str) -> Self {
+        self.methods.insert(method.to_owned());
+        self
+    }
+
+    /// Returns true if `method` may be hedged.
+    pub fn is_hedged(&self, method: &str) -> bool {
+        self.methods.contains(method)
+    }
+
+    /// Record the latency of a successful call.
+    pub fn observe(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+
+        if latencies.len() >= self.window {
+            latencies.pop_front();
+        }
+
+        latencies.push_back(latency);
+    }
+
+    /// Returns how long to wait before sending the hedged copy.
+    pub fn hedge_delay(&self) -> Duration {
+        let percentile = match self.delay {
+            HedgeDelay::Fixed(delay) => return delay,
+            HedgeDelay::Percentile(percentile) => percentile.clamp(0.0, 1.0),
+        };
+
+        let mut latencies = self
+            .latencies
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+
+        if latencies.is_empty() || latencies.len() < self.min_samples {
+            return self.fallback;
+        }
+
+        latencies.sort();
+
+        let rank = (percentile * latencies.len() as f64).ceil() as usize;
+
+        latencies[rank.clamp(1, latencies.len()) - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{HedgeDelay, HedgePolicy};
+
+    #[test]
+    fn test_percentile_delay() {
+        let policy = HedgePolicy {
+            min_samples: 10,
+            fallback: Duration::from_millis(5),
+            ..HedgePolicy::new(HedgeDelay::Percentile(0.9))
+        };
+
+        assert_eq!(policy.hedge_delay(), Duration::from_millis(5));
+
+        for i in (1..=100).rev() {
+            policy.observe(Duration::from_millis(i));
+        }
+
+        assert_eq!(policy.hedge_delay(), Duration::from_millis(90));
+
+        let policy = HedgePolicy::new(HedgeDelay::Fixed(Duration::from_millis(20)));
+
+        assert_eq!(policy.hedge_delay(), Duration::from_millis(20));
+    }
+}
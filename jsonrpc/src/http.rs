@@ -0,0 +1,8 @@
+//! JSON-RPC over HTTP, requests are POST bodies answered by the response bodies.
+//!
+//! All types run within a tokio runtime.
+
+#[cfg(feature = "axum")]
+pub mod axum;
+pub mod client;
+pub mod server;
@@ -0,0 +1,123 @@
+//! [axum](https://docs.rs/axum) integration, serving JSON-RPC next to other routes of an app.
+
+use std::net::SocketAddr;
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    response::Response,
+    routing::any,
+    Router,
+};
+
+use super::server::{Endpoint, PeerAddr};
+
+impl Endpoint {
+    /// Returns a router serving this endpoint at its path, to merge into the app router.
+    ///
+    /// The request extensions, e.g. an identity inserted by an auth middleware, reach the
+    /// handler [`Context`](crate::server::Context), with the [`PeerAddr`] if the app is
+    /// served with `into_make_service_with_connect_info::<SocketAddr>`.
+    pub fn router<S>(self) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let path = self.path.clone();
+
+        Router::new().route(&path, any(handler)).with_state(self)
+    }
+}
+
+/// Axum handler answering JSON-RPC requests with the [`Endpoint`] state, whatever the
+/// request path.
+pub async fn handler(State(endpoint): State<Endpoint>, mut request: Request) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| PeerAddr(*addr));
+
+    if let Some(peer) = peer {
+        request.extensions_mut().insert(peer);
+    }
+
+    endpoint.dispatch(request).await.map(Body::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use async_timer_rs::hashed::Timeout;
+    use axum::{routing::get, Extension, Router};
+    use futures::future;
+    use http_body_util::{BodyExt, Empty};
+    use hyper::body::Bytes;
+    use hyper_util::{client::legacy::Client as HttpClient, rt::TokioExecutor};
+    use tokio::net::TcpListener;
+
+    use crate::{
+        client::Client,
+        http::{
+            client::Http,
+            server::{Endpoint, PeerAddr},
+        },
+        server::{Context, Server},
+        session::Session,
+    };
+
+    /// Identity inserted by the auth layer.
+    #[derive(Debug, Clone)]
+    struct Identity(String);
+
+    #[tokio::test]
+    async fn test_router() {
+        let mut server = Server::new();
+
+        server.handle("whoami", |context: Context, _: ()| {
+            let identity = context.extensions.get::<Identity>().unwrap().0.clone();
+
+            let peer = context.extensions.get::<PeerAddr>().unwrap().0;
+
+            future::ready(Ok(format!("{}@{}", identity, peer.ip())))
+        });
+
+        let app = Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .merge(Endpoint::new(server).path("/rpc").router())
+            .layer(Extension(Identity("alice".to_owned())));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+
+        let health = HttpClient::builder(TokioExecutor::new())
+            .build_http::<Empty<Bytes>>()
+            .get(format!("http://{}/health", addr).parse().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(health.into_body().collect().await.unwrap().to_bytes(), "ok");
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let transport = Http::new(format!("http://{}/rpc", addr).parse().unwrap());
+
+        let test = async move {
+            let whoami = client.call::<_, String, Timeout>("whoami", (), None).await;
+
+            assert_eq!(whoami.unwrap(), "alice@127.0.0.1");
+        };
+
+        let (_, _) = future::join(session.run(transport), test).await;
+    }
+}
@@ -0,0 +1,465 @@
+//! HTTP client transport
+
+use std::{
+    collections::VecDeque,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll, Waker},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream::FuturesUnordered, FutureExt, Sink, Stream, StreamExt};
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE},
+    Method, Request, StatusCode, Uri,
+};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use serde_json::{json, value::RawValue, Value};
+
+use crate::{
+    object::{Error, ErrorCode},
+    result::RPCError,
+    session::Failed,
+};
+
+type HttpClient = Client<HttpConnector, Full<Bytes>>;
+
+/// Client transport POSTing each outbound frame to one HTTP endpoint.
+///
+/// The response body is fed back as inbound frame, a batch response is split into
+/// one frame per response and an empty body, e.g. `204 No Content`, is skipped.
+/// Connections are kept alive and reused by a pool.
+///
+/// A request failure or an HTTP error status fails the calls of the frame instead, unless
+/// the body holds JSON-RPC responses. The stream yields a [`Failed`] error then, with:
+///
+/// * `408` and `504` are [`ErrorCode::Timeout`].
+/// * `502`, `503` and request failures are [`ErrorCode::ConnectionLost`].
+/// * Any other status is [`ErrorCode::HttpStatus`], with `{"status", "body"}` data.
+pub struct Http {
+    uri: Uri,
+    headers: HeaderMap,
+    idle_timeout: Option<Duration>,
+    max_idle: usize,
+    /// Built on first send, with the pool settings.
+    client: Option<HttpClient>,
+    calls: FuturesUnordered<BoxFuture<'static, io::Result<Vec<Bytes>>>>,
+    frames: VecDeque<io::Result<Bytes>>,
+    waker: Option<Waker>,
+}
+
+impl Http {
+    /// Create new transport sending requests to `uri`.
+    pub fn new(uri: Uri) -> Self {
+        let mut headers = HeaderMap::new();
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+
+        Self {
+            uri,
+            headers,
+            idle_timeout: Some(Duration::from_secs(90)),
+            max_idle: usize::MAX,
+            client: None,
+            calls: Default::default(),
+            frames: Default::default(),
+            waker: None,
+        }
+    }
+
+    /// Send `name: value` header with each request, replacing any previous value.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Close kept alive connections idle for `timeout`, `None` to keep them forever.
+    /// Default is 90 seconds.
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Keep at most `max` idle connections alive, `0` disables keep-alive.
+    pub fn max_idle(mut self, max: usize) -> Self {
+        self.max_idle = max;
+        self
+    }
+
+    fn client(&mut self) -> HttpClient {
+        let (idle_timeout, max_idle) = (self.idle_timeout, self.max_idle);
+
+        self.client
+            .get_or_insert_with(|| {
+                Client::builder(TokioExecutor::new())
+                    .pool_idle_timeout(idle_timeout)
+                    .pool_max_idle_per_host(max_idle)
+                    .build_http()
+            })
+            .clone()
+    }
+
+    /// Queue the inbound frames or the failure of one request.
+    fn received(&mut self, result: io::Result<Vec<Bytes>>) {
+        match result {
+            Ok(frames) => self.frames.extend(frames.into_iter().map(Ok)),
+            Err(err) => self.frames.push_back(Err(err)),
+        }
+    }
+}
+
+impl Stream for Http {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(frame) = self.frames.pop_front() {
+                return Poll::Ready(Some(frame));
+            }
+
+            match self.calls.poll_next_unpin(cx) {
+                Poll::Ready(Some(result)) => self.received(result),
+                // HTTP has no connection to lose, wait for the next request.
+                Poll::Ready(None) | Poll::Pending => {
+                    self.waker = Some(cx.waker().clone());
+
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+impl Sink<Bytes> for Http {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, frame: Bytes) -> io::Result<()> {
+        let ids = ids(&frame);
+
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(self.uri.clone())
+            .body(Full::new(frame))
+            .map_err(io::Error::other)?;
+
+        *request.headers_mut() = self.headers.clone();
+
+        let call = post(self.client(), request, ids).boxed();
+
+        self.calls.push(call);
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Wait for the requests in flight, their responses are still read from the stream.
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while let Some(result) = ready!(self.calls.poll_next_unpin(cx)) {
+            self.received(result);
+
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Send `request` of calls `ids`, returns the inbound frames.
+async fn post(
+    client: HttpClient,
+    request: Request<Full<Bytes>>,
+    ids: Vec<u64>,
+) -> io::Result<Vec<Bytes>> {
+    let (status, body) = match send(client, request).await {
+        Ok(response) => response,
+        Err(err) => {
+            let err = Error {
+                message: format!("HTTP request failed: {}", err),
+                ..Error::connection_lost()
+            };
+
+            return failed(ids, err);
+        }
+    };
+
+    if status.is_success() || is_response(&body) {
+        return Ok(split(body));
+    }
+
+    let code = match status {
+        StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => ErrorCode::Timeout,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ErrorCode::ConnectionLost,
+        _ => ErrorCode::HttpStatus,
+    };
+
+    let err = Error {
+        code,
+        message: format!("HTTP status {}", status),
+        data: Some(json!({
+            "status": status.as_u16(),
+            "body": String::from_utf8_lossy(&body),
+        })),
+    };
+
+    failed(ids, err)
+}
+
+/// Send `request`, returns the response status and body.
+async fn send(
+    client: HttpClient,
+    request: Request<Full<Bytes>>,
+) -> Result<(StatusCode, Bytes), Box<dyn std::error::Error + Send + Sync>> {
+    let response = client.request(request).await?;
+
+    let status = response.status();
+
+    let body = response.into_body().collect().await?.to_bytes();
+
+    Ok((status, body))
+}
+
+/// Returns true if `body` holds a JSON-RPC response or a batch of them.
+fn is_response(body: &[u8]) -> bool {
+    let is_response = |value: &Value| value.get("result").is_some() || value.get("error").is_some();
+
+    match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Array(values)) => !values.is_empty() && values.iter().all(is_response),
+        Ok(value) => is_response(&value),
+        Err(_) => false,
+    }
+}
+
+/// Split batch response `body` into one frame per response.
+fn split(body: Bytes) -> Vec<Bytes> {
+    match body.iter().find(|c| !c.is_ascii_whitespace()) {
+        None => vec![],
+        Some(b'[') => match serde_json::from_slice::<Vec<Box<RawValue>>>(&body) {
+            Ok(responses) => responses
+                .into_iter()
+                .map(|response| Bytes::from(response.get().to_owned()))
+                .collect(),
+            Err(_) => vec![body],
+        },
+        Some(_) => vec![body],
+    }
+}
+
+/// Returns the ids of the calls of request or batch `frame`.
+fn ids(frame: &[u8]) -> Vec<u64> {
+    match serde_json::from_slice::<Value>(frame) {
+        Ok(Value::Array(requests)) => requests.iter().filter_map(|r| r["id"].as_u64()).collect(),
+        Ok(request) => request["id"].as_u64().into_iter().collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Fail the calls `ids` with `err`, dropped if the request holds notifications only.
+fn failed(ids: Vec<u64>, err: RPCError) -> io::Result<Vec<Bytes>> {
+    if ids.is_empty() {
+        log::warn!("drop notification error: {}", err);
+
+        return Ok(vec![]);
+    }
+
+    Err(Failed { ids, error: err }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use async_timer_rs::hashed::Timeout;
+    use bytes::Bytes;
+    use futures::{future, SinkExt, StreamExt};
+    use http_body_util::{BodyExt, Full};
+    use hyper::{
+        body::Incoming, header::HeaderValue, server::conn::http1, service::service_fn, Request,
+        Response,
+    };
+    use hyper_util::rt::TokioIo;
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    use crate::{
+        client::Client,
+        object::ErrorCode,
+        session::{Failed, Session},
+    };
+
+    use super::Http;
+
+    /// Answer `add` calls, `unavailable` with 503, `forbidden` with 403 and
+    /// notifications with 204.
+    async fn handle(request: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+        assert_eq!(request.headers()["x-api-key"], "secret");
+        assert_eq!(request.headers()["content-type"], "application/json");
+
+        let body = request.into_body().collect().await.unwrap().to_bytes();
+
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        let add = |request: &Value| {
+            let params = &request["params"];
+
+            json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": params[0].as_i64().unwrap() + params[1].as_i64().unwrap(),
+            })
+        };
+
+        let (status, body) = match &body {
+            Value::Array(requests) => (200, json!(requests.iter().map(add).collect::<Vec<_>>())),
+            request => match request["method"].as_str().unwrap() {
+                "unavailable" => (503, json!("busy")),
+                "forbidden" => (403, json!("denied")),
+                _ if request.get("id").is_none() => (204, Value::Null),
+                _ => (200, add(request)),
+            },
+        };
+
+        let body = match body {
+            Value::Null => Bytes::new(),
+            Value::String(text) => text.into(),
+            body => body.to_string().into(),
+        };
+
+        Ok(Response::builder()
+            .status(status)
+            .body(Full::new(body))
+            .unwrap())
+    }
+
+    /// Serve [`handle`] on a local port, returns the address and the accepted connections counter.
+    async fn serve() -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let counter = connections.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                tokio::spawn(
+                    http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service_fn(handle)),
+                );
+            }
+        });
+
+        (addr, connections)
+    }
+
+    fn transport(addr: SocketAddr) -> Http {
+        Http::new(format!("http://{}/rpc", addr).parse().unwrap()).header(
+            "x-api-key".parse().unwrap(),
+            HeaderValue::from_static("secret"),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        let (addr, connections) = serve().await;
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let test = async move {
+            for i in 0..3 {
+                let sum = client.call::<_, i64, Timeout>("add", (i, 2), None).await;
+
+                assert_eq!(sum.unwrap(), i + 2);
+            }
+
+            // Sequential calls reuse the kept alive connection.
+            assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+            client.notification("add", (1, 2)).await.unwrap();
+
+            let err = client
+                .call::<_, i64, Timeout>("unavailable", (), None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::ConnectionLost);
+
+            let err = client
+                .call::<_, i64, Timeout>("forbidden", (), None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::HttpStatus);
+            assert_eq!(err.data, Some(json!({"status": 403, "body": "denied"})));
+        };
+
+        let (_, _) = future::join(session.run(transport(addr)), test).await;
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let (addr, _) = serve().await;
+
+        let mut transport = transport(addr);
+
+        let batch = json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "add", "params": [1, 2]},
+            {"jsonrpc": "2.0", "id": 2, "method": "add", "params": [3, 4]},
+        ]);
+
+        transport.send(batch.to_string().into()).await.unwrap();
+
+        for (id, result) in [(1, 3), (2, 7)] {
+            let frame = transport.next().await.unwrap().unwrap();
+
+            let response: Value = serde_json::from_slice(&frame).unwrap();
+
+            assert_eq!(response["id"], id);
+            assert_eq!(response["result"], result);
+        }
+
+        let request = json!({"jsonrpc": "2.0", "id": 3, "method": "unavailable"});
+
+        transport.send(request.to_string().into()).await.unwrap();
+
+        // Reported out of band, no response frame is made up.
+        let err = transport.next().await.unwrap().unwrap_err();
+
+        let failed = Failed::of(&err).unwrap();
+
+        assert_eq!(failed.ids, vec![3]);
+        assert_eq!(failed.error.code, ErrorCode::ConnectionLost);
+    }
+}
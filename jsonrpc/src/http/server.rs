@@ -0,0 +1,569 @@
+//! HTTP server endpoint
+
+use std::{convert::Infallible, io, net::SocketAddr, sync::Arc};
+
+use bytes::Bytes;
+use futures::future;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::{
+    body::Body,
+    header::{HeaderValue, ALLOW, CONTENT_LENGTH, CONTENT_TYPE},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde_json::{json, value::RawValue, Map, Value};
+use tokio::net::TcpListener;
+
+use crate::{
+    extensions::Extensions,
+    object::{Error, ErrorCode, Version},
+    server::Server,
+};
+
+/// Address of the HTTP client, found in the [`Context::extensions`](crate::server::Context::extensions)
+/// of handlers served by [`Endpoint::serve`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerAddr(pub SocketAddr);
+
+/// HTTP endpoint mounting a [`Server`] at one path.
+///
+/// The request or batch is the POST body, answered with `200 OK` and the response
+/// body, or `204 No Content` if the body holds only notifications. JSON-RPC errors
+/// are answered with `200 OK` too. HTTP errors are:
+///
+/// * `404 Not Found` for other paths.
+/// * `405 Method Not Allowed` for other methods.
+/// * `415 Unsupported Media Type` if `Content-Type` is not `application/json`.
+/// * `413 Payload Too Large` if the body exceeds the [size cap](Endpoint::max_body_size).
+///
+/// Use [`Endpoint::serve`] standalone, or call [`Endpoint::handle`] from an existing service.
+/// The HTTP request extensions are passed to the handler [`Context`](crate::server::Context).
+#[derive(Clone)]
+pub struct Endpoint {
+    server: Arc<Server>,
+    pub(crate) path: String,
+    max_body_size: usize,
+    get: bool,
+}
+
+impl Endpoint {
+    /// Create new endpoint routing requests to `server`, mounted at `/`.
+    pub fn new(server: Server) -> Self {
+        Self {
+            server: Arc::new(server),
+            path: "/".to_owned(),
+            max_body_size: 1024 * 1024,
+            get: false,
+        }
+    }
+
+    /// Mount this endpoint at `path`.
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_owned();
+        self
+    }
+
+    /// Reject bodies larger than `size` bytes, default is 1 MiB.
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Accept the GET form, `?method=add&params=[1,2]&id=1` with URL encoded JSON params.
+    /// Default is false.
+    ///
+    /// The GET form carries calls only, a request without `id` is answered with
+    /// [`ErrorCode::InvalidRequest`] instead of running as a notification.
+    pub fn get(mut self, enabled: bool) -> Self {
+        self.get = enabled;
+        self
+    }
+
+    /// Serve the connections accepted by `listener`, returns on accept error.
+    pub async fn serve(self, listener: TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, addr) = listener.accept().await?;
+
+            let endpoint = self.clone();
+
+            let service = service_fn(move |mut request: Request<_>| {
+                let endpoint = endpoint.clone();
+
+                request.extensions_mut().insert(PeerAddr(addr));
+
+                async move { Ok::<_, Infallible>(endpoint.handle(request).await) }
+            });
+
+            tokio::spawn(async move {
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    log::debug!("http connection error: {}", err);
+                }
+            });
+        }
+    }
+
+    /// Handle one HTTP request.
+    pub async fn handle<B>(&self, request: Request<B>) -> Response<Full<Bytes>>
+    where
+        B: Body<Data = Bytes>,
+        B::Error: std::error::Error + Send + Sync + 'static,
+    {
+        if request.uri().path() != self.path {
+            return status(StatusCode::NOT_FOUND);
+        }
+
+        self.dispatch(request).await
+    }
+
+    /// Handle one HTTP request whatever its path.
+    pub(crate) async fn dispatch<B>(&self, request: Request<B>) -> Response<Full<Bytes>>
+    where
+        B: Body<Data = Bytes>,
+        B::Error: std::error::Error + Send + Sync + 'static,
+    {
+        match *request.method() {
+            Method::POST => {}
+            Method::GET if self.get => {
+                return match query_request(request.uri().query().unwrap_or_default()) {
+                    Ok(frame) => {
+                        let (parts, _) = request.into_parts();
+
+                        self.respond(&frame, &parts.extensions.into()).await
+                    }
+                    Err(message) => json_response(&error(ErrorCode::InvalidRequest, message)),
+                };
+            }
+            _ => {
+                let allow = match self.get {
+                    true => "POST, GET",
+                    false => "POST",
+                };
+
+                let mut response = status(StatusCode::METHOD_NOT_ALLOWED);
+
+                response
+                    .headers_mut()
+                    .insert(ALLOW, HeaderValue::from_static(allow));
+
+                return response;
+            }
+        }
+
+        let is_json = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+            .unwrap_or_default();
+
+        if !is_json {
+            return status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        }
+
+        let length = request
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<usize>().ok());
+
+        if length.is_some_and(|length| length > self.max_body_size) {
+            return status(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+
+        let (parts, body) = request.into_parts();
+
+        let body = match Limited::new(body, self.max_body_size).collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(err) if err.is::<LengthLimitError>() => {
+                return status(StatusCode::PAYLOAD_TOO_LARGE)
+            }
+            Err(err) => {
+                log::debug!("read http body error: {}", err);
+
+                return status(StatusCode::BAD_REQUEST);
+            }
+        };
+
+        self.respond(&body, &parts.extensions.into()).await
+    }
+
+    /// Handle the request or batch `body`.
+    async fn respond(&self, body: &[u8], extensions: &Extensions) -> Response<Full<Bytes>> {
+        if body.iter().find(|c| !c.is_ascii_whitespace()) != Some(&b'[') {
+            return match self.server.respond_with(body, extensions).await {
+                Some(frame) => response(frame),
+                None => status(StatusCode::NO_CONTENT),
+            };
+        }
+
+        let requests = match serde_json::from_slice::<Vec<Box<RawValue>>>(body) {
+            Ok(requests) if requests.is_empty() => {
+                return json_response(&error(ErrorCode::InvalidRequest, "Empty batch".to_owned()))
+            }
+            Ok(requests) => requests,
+            Err(err) => {
+                return json_response(&error(
+                    ErrorCode::ParseError,
+                    format!("Invalid batch: {}", err),
+                ))
+            }
+        };
+
+        let frames = future::join_all(requests.iter().map(|request| {
+            self.server
+                .respond_with(request.get().as_bytes(), extensions)
+        }))
+        .await;
+
+        let frames = frames.into_iter().flatten().collect::<Vec<_>>();
+
+        if frames.is_empty() {
+            return status(StatusCode::NO_CONTENT);
+        }
+
+        let mut batch =
+            Vec::with_capacity(frames.iter().map(|frame| frame.len() + 1).sum::<usize>() + 1);
+
+        batch.push(b'[');
+
+        for (index, frame) in frames.iter().enumerate() {
+            if index > 0 {
+                batch.push(b',');
+            }
+
+            batch.extend_from_slice(frame);
+        }
+
+        batch.push(b']');
+
+        response(batch.into())
+    }
+}
+
+/// Build the request frame of the GET form `query`.
+fn query_request(query: &str) -> Result<Bytes, String> {
+    let mut request = Map::new();
+
+    request.insert("jsonrpc".to_owned(), json!(Version));
+
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+
+        let value = decode(value).ok_or_else(|| format!("Invalid encoding of {}", key))?;
+
+        let value = match key {
+            "method" => Value::String(value),
+            "params" | "id" => {
+                serde_json::from_str(&value).map_err(|err| format!("Invalid {}: {}", key, err))?
+            }
+            _ => continue,
+        };
+
+        request.insert(key.to_owned(), value);
+    }
+
+    if !request.contains_key("method") {
+        return Err("Missing method".to_owned());
+    }
+
+    if request.get("id").is_none_or(Value::is_null) {
+        return Err("Missing id".to_owned());
+    }
+
+    Ok(Value::Object(request).to_string().into())
+}
+
+/// Decode URL encoded query component `value`.
+fn decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+
+    let mut input = value.bytes();
+
+    while let Some(c) = input.next() {
+        match c {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [input.next()?, input.next()?];
+
+                let hex = std::str::from_utf8(&hex).ok()?;
+
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            c => bytes.push(c),
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Error response with `null` id.
+fn error(code: ErrorCode, message: String) -> Value {
+    json!({
+        "id": null,
+        "jsonrpc": Version,
+        "error": Error::<String, Value> {
+            code,
+            message,
+            data: None,
+        },
+    })
+}
+
+fn json_response(value: &Value) -> Response<Full<Bytes>> {
+    response(value.to_string().into())
+}
+
+fn response(body: Bytes) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body));
+
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    response
+}
+
+fn status(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::default());
+
+    *response.status_mut() = status;
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use async_timer_rs::hashed::Timeout;
+    use bytes::Bytes;
+    use futures::future;
+    use http_body_util::{BodyExt, Full};
+    use hyper::{header::CONTENT_TYPE, Method, Request, StatusCode};
+    use hyper_util::{client::legacy::Client as HttpClient, rt::TokioExecutor};
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    use crate::{
+        client::Client,
+        http::client::Http,
+        object::ErrorCode,
+        server::{Context, Server},
+        session::Session,
+    };
+
+    use super::{Endpoint, PeerAddr};
+
+    async fn serve() -> SocketAddr {
+        let mut server = Server::new();
+
+        server
+            .handle("add", |_, (lhs, rhs): (i64, i64)| {
+                future::ready(Ok(lhs + rhs))
+            })
+            .handle("peer", |context: Context, _: ()| {
+                let peer = context.extensions.get::<PeerAddr>().map(|peer| peer.0.ip());
+
+                future::ready(Ok(peer.map(|ip| ip.to_string())))
+            });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        let endpoint = Endpoint::new(server)
+            .path("/rpc")
+            .max_body_size(256)
+            .get(true);
+
+        tokio::spawn(endpoint.serve(listener));
+
+        addr
+    }
+
+    /// Send one HTTP request, returns the status and the body.
+    async fn send(
+        addr: SocketAddr,
+        method: Method,
+        path: &str,
+        content_type: &str,
+        body: &str,
+    ) -> (StatusCode, Bytes) {
+        let client = HttpClient::builder(TokioExecutor::new()).build_http();
+
+        let request = Request::builder()
+            .method(method)
+            .uri(format!("http://{}{}", addr, path))
+            .header(CONTENT_TYPE, content_type)
+            .body(Full::new(Bytes::from(body.to_owned())))
+            .unwrap();
+
+        let response = client.request(request).await.unwrap();
+
+        let status = response.status();
+
+        (
+            status,
+            response.into_body().collect().await.unwrap().to_bytes(),
+        )
+    }
+
+    async fn post(addr: SocketAddr, body: Value) -> (StatusCode, Value) {
+        let (status, body) = send(
+            addr,
+            Method::POST,
+            "/rpc",
+            "application/json; charset=utf-8",
+            &body.to_string(),
+        )
+        .await;
+
+        match body.is_empty() {
+            true => (status, Value::Null),
+            false => (status, serde_json::from_slice(&body).unwrap()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client() {
+        let addr = serve().await;
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let transport = Http::new(format!("http://{}/rpc", addr).parse().unwrap());
+
+        let test = async move {
+            let sum = client.call::<_, i64, Timeout>("add", (1, 2), None).await;
+
+            assert_eq!(sum.unwrap(), 3);
+
+            let err = client
+                .call::<_, i64, Timeout>("sub", (1, 2), None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::MethodNotFound);
+
+            let peer = client
+                .call::<_, Option<String>, Timeout>("peer", (), None)
+                .await;
+
+            assert_eq!(peer.unwrap().as_deref(), Some("127.0.0.1"));
+        };
+
+        let (_, _) = future::join(session.run(transport), test).await;
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let addr = serve().await;
+
+        let (status, body) = post(
+            addr,
+            json!([
+                {"jsonrpc": "2.0", "id": 1, "method": "add", "params": [1, 2]},
+                {"jsonrpc": "2.0", "method": "add", "params": [1, 2]},
+                {"jsonrpc": "2.0", "id": 2, "method": "sub", "params": [1, 2]},
+            ]),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0], json!({"jsonrpc": "2.0", "id": 1, "result": 3}));
+        assert_eq!(body[1]["id"], 2);
+        assert_eq!(body[1]["error"]["code"], -32601);
+
+        // Notifications only.
+        let (status, _) = post(
+            addr,
+            json!([{"jsonrpc": "2.0", "method": "add", "params": [1, 2]}]),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, _) = post(
+            addr,
+            json!({"jsonrpc": "2.0", "method": "add", "params": [1, 2]}),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, body) = post(addr, json!([])).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["error"]["code"], -32600);
+    }
+
+    #[tokio::test]
+    async fn test_http_errors() {
+        let addr = serve().await;
+
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"add","params":[1,2]}"#;
+
+        let (status, _) = send(addr, Method::POST, "/rpc", "text/plain", request).await;
+
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let (status, _) = send(addr, Method::POST, "/", "application/json", request).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(addr, Method::PUT, "/rpc", "application/json", request).await;
+
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+
+        let large = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"add","params":"{}"}}"#,
+            "x".repeat(256)
+        );
+
+        let (status, _) = send(addr, Method::POST, "/rpc", "application/json", &large).await;
+
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let addr = serve().await;
+
+        let (status, body) = send(
+            addr,
+            Method::GET,
+            "/rpc?method=add&params=%5B1%2C2%5D&id=7",
+            "",
+            "",
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!({"jsonrpc": "2.0", "id": 7, "result": 3})
+        );
+
+        // Neither a notification nor a call without method.
+        for query in [
+            "/rpc?method=add&params=[1,2]",
+            "/rpc?method=add&params=[1,2]&id=null",
+            "/rpc?params=[1,2]&id=7",
+        ] {
+            let (status, body) = send(addr, Method::GET, query, "", "").await;
+
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                serde_json::from_slice::<Value>(&body).unwrap()["error"]["code"],
+                -32600
+            );
+        }
+    }
+}
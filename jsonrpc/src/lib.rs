@@ -1,3 +1,29 @@
+pub mod backoff;
+pub mod balance;
+pub mod breaker;
+pub mod cancel;
 pub mod client;
+pub mod codec;
+pub mod extensions;
+pub mod harness;
+pub mod hedge;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "tcp")]
+pub mod lines;
 pub mod object;
+#[cfg(feature = "openrpc")]
+pub mod openrpc;
+#[cfg(feature = "quic")]
+pub mod quic;
+pub mod reconnect;
 pub mod result;
+pub mod retry;
+pub mod server;
+pub mod session;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "validation")]
+pub mod validation;
+#[cfg(feature = "ws")]
+pub mod ws;
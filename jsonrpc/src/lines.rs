@@ -0,0 +1,240 @@
+//! Newline delimited transport over a byte stream, e.g. TCP or TLS.
+//!
+//! Runs within a tokio runtime.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{Sink, Stream};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+/// Default max size of one inbound frame, 16 MiB.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Buffered outbound bytes flushed before accepting more frames.
+const WRITE_HIGH_WATER: usize = 64 * 1024;
+
+/// Transport carrying one frame per line over byte stream `S`.
+///
+/// Frames must not contain newlines, which holds for compact JSON. Empty lines are
+/// skipped and a trailing `\r` is stripped.
+pub struct Lines<S> {
+    inner: S,
+    read: BytesMut,
+    /// Length of the `read` prefix already scanned for newline.
+    scanned: usize,
+    write: BytesMut,
+    max_frame_size: usize,
+    eof: bool,
+}
+
+impl<S> Lines<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Wrap connected byte stream `inner`.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            read: Default::default(),
+            scanned: 0,
+            write: Default::default(),
+            max_frame_size: MAX_FRAME_SIZE,
+            eof: false,
+        }
+    }
+
+    /// Fail the stream with [`io::ErrorKind::InvalidData`] on frames larger than `size`,
+    /// default is [`MAX_FRAME_SIZE`].
+    pub fn max_frame_size(mut self, size: usize) -> Self {
+        self.max_frame_size = size;
+        self
+    }
+
+    /// Returns the wrapped byte stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Returns the next complete line of the read buffer, without newline.
+    fn line(&mut self) -> Option<Bytes> {
+        let position = self.read[self.scanned..].iter().position(|c| *c == b'\n')?;
+
+        let mut line = self.read.split_to(self.scanned + position + 1);
+
+        self.scanned = 0;
+
+        line.truncate(line.len() - 1);
+
+        if line.last() == Some(&b'\r') {
+            line.truncate(line.len() - 1);
+        }
+
+        Some(line.freeze())
+    }
+}
+
+impl Lines<TcpStream> {
+    /// Connect to TCP server `addr`.
+    pub async fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+
+        stream.set_nodelay(true)?;
+
+        Ok(Self::new(stream))
+    }
+}
+
+impl<S> Stream for Lines<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(line) = self.line() {
+                if line.is_empty() {
+                    continue;
+                }
+
+                return Poll::Ready(Some(Ok(line)));
+            }
+
+            self.scanned = self.read.len();
+
+            if self.eof {
+                // Last line without newline.
+                return match self.read.is_empty() {
+                    true => Poll::Ready(None),
+                    false => {
+                        self.scanned = 0;
+
+                        Poll::Ready(Some(Ok(self.read.split().freeze())))
+                    }
+                };
+            }
+
+            if self.read.len() > self.max_frame_size {
+                return Poll::Ready(Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("frame larger than {} bytes", self.max_frame_size),
+                ))));
+            }
+
+            let mut buf = [0; 8 * 1024];
+
+            let mut read_buf = ReadBuf::new(&mut buf);
+
+            if let Err(err) = ready!(Pin::new(&mut self.inner).poll_read(cx, &mut read_buf)) {
+                return Poll::Ready(Some(Err(err)));
+            }
+
+            match read_buf.filled() {
+                [] => self.eof = true,
+                filled => {
+                    let filled = filled.to_vec();
+
+                    self.read.extend_from_slice(&filled);
+                }
+            }
+        }
+    }
+}
+
+impl<S> Sink<Bytes> for Lines<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.write.len() < WRITE_HIGH_WATER {
+            true => Poll::Ready(Ok(())),
+            false => self.poll_flush(cx),
+        }
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, frame: Bytes) -> io::Result<()> {
+        self.write.extend_from_slice(&frame);
+        self.write.extend_from_slice(b"\n");
+
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        while !this.write.is_empty() {
+            let written = ready!(Pin::new(&mut this.inner).poll_write(cx, &this.write))?;
+
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+
+            this.write.advance(written);
+        }
+
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_timer_rs::hashed::Timeout;
+    use futures::future;
+    use tokio::net::TcpListener;
+
+    use crate::{client::Client, server::Server, session::Session};
+
+    use super::Lines;
+
+    #[tokio::test]
+    async fn test_tcp() {
+        let mut server = Server::new();
+
+        server.handle("echo", |_, params: String| future::ready(Ok(params)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+
+            server.accept(Lines::new(stream)).await
+        });
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let test = async move {
+            // Larger than one read.
+            let large = "x".repeat(20 * 1024);
+
+            for params in ["hello", large.as_str()] {
+                let result = client
+                    .call::<_, String, Timeout>("echo", params, None)
+                    .await;
+
+                assert_eq!(result.unwrap(), params);
+            }
+        };
+
+        let (_, _) = future::join(session.run(Lines::connect(addr).await.unwrap()), test).await;
+    }
+}
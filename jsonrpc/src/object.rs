@@ -2,6 +2,7 @@ use std::fmt::{Display, Formatter};
 
 use futures::channel::mpsc::SendError;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
 
 /// A rpc call is represented by sending a Request object to a Server.  
 ///
@@ -76,7 +77,53 @@ where
     pub error: Option<Error<S, D>>,
 }
 
+/// JSON-RPC protocol generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protocol {
+    /// JSON-RPC 1.0, see [`RequestV1`] and [`ResponseV1`].
+    V1,
+    /// JSON-RPC 2.0
+    #[default]
+    V2,
+}
+
+/// JSON-RPC 1.0 request.
+///
+/// There is no `jsonrpc` member, `params` MUST be an array and notifications have `id: null`.
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct RequestV1<S, P>
+where
+    S: AsRef<str>,
+{
+    /// Request id, `null` for notification.
+    pub id: Option<u64>,
+    /// Method name.
+    pub method: S,
+    /// Array of parameters.
+    pub params: P,
+}
+
+/// JSON-RPC 1.0 response.
+///
+/// `result` and `error` are both present, the unused one is `null`.
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct ResponseV1<S, R, D>
+where
+    S: AsRef<str>,
+{
+    /// Id of the request responding to.
+    pub id: u64,
+    /// `null` on error.
+    pub result: Option<R>,
+    /// `null` on success.
+    pub error: Option<Error<S, D>>,
+}
+
+/// [`Response`] with undecoded `result` and error `data`, forwarded as is when serialized again.
+pub type RawResponse = Response<String, Box<RawValue>, Box<RawValue>>;
+
 /// JSONRPC type compatible with both [`Request`] and [`Response`] data structures
+#[allow(dead_code, clippy::upper_case_acronyms)]
 #[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
 struct JSONRPC<S, P, R, D> {
     /// An identifier established by the Client that MUST contain a String, Number,
@@ -138,10 +185,11 @@ impl From<serde_json::Error> for Error<String, serde_json::Value> {
     }
 }
 
+/// Local [`ErrorCode::Io`] error keeping the [`std::io::ErrorKind`], see [`Error::is_io`].
 impl From<std::io::Error> for Error<String, serde_json::Value> {
     fn from(err: std::io::Error) -> Self {
         Self {
-            code: ErrorCode::InternalError,
+            code: ErrorCode::Io(err.kind()),
             message: format!("Underly io error: {}", err),
             data: None,
         }
@@ -159,6 +207,43 @@ impl From<SendError> for Error<String, serde_json::Value> {
 }
 
 impl Error<String, serde_json::Value> {
+    /// Returns the error to send to a peer, local errors become [`ErrorCode::InternalError`].
+    pub fn to_remote(&self) -> Self {
+        let mut err = self.clone();
+
+        if err.code.is_local() {
+            err.code = ErrorCode::InternalError;
+        }
+
+        err
+    }
+
+    /// Returns true if this local error was converted from an io error of `kind`.
+    pub fn is_io(&self, kind: std::io::ErrorKind) -> bool {
+        self.code == ErrorCode::Io(kind)
+    }
+
+    /// Returns this error with [`ErrorCode::Timeout`] if converted from an io
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut) error, e.g. the timeout of a
+    /// [`Response`](librpc::responder::Response).
+    pub fn map_timeout(mut self) -> Self {
+        if self.is_io(std::io::ErrorKind::TimedOut) {
+            self.code = ErrorCode::Timeout;
+        }
+
+        self
+    }
+
+    /// Returns the [`ErrorCode::ConnectionLost`] error of the calls in flight when the
+    /// connection drops.
+    pub fn connection_lost() -> Self {
+        Self {
+            code: ErrorCode::ConnectionLost,
+            message: "Connection lost".to_owned(),
+            data: None,
+        }
+    }
+
     pub fn from_std_error<E>(e: E) -> Self
     where
         E: Display,
@@ -196,24 +281,123 @@ pub enum ErrorCode {
     InvalidParams,
     #[error("Internal JSON-RPC error.")]
     InternalError,
+    /// The request was cancelled before the server produced a result (LSP `RequestCancelled`).
+    #[error("The request was cancelled.")]
+    RequestCancelled,
+    /// Local error, the connection dropped before the response arrived.
+    #[error("The connection to the server was lost.")]
+    ConnectionLost,
+    /// Local error, the response did not arrive in time.
+    #[error("The request timed out.")]
+    Timeout,
+    /// Local error, the circuit breaker of the endpoint is open.
+    #[error("The circuit breaker is open.")]
+    CircuitOpen,
+    /// Local error, the HTTP server answered with an error status instead of a response.
+    #[error("The server answered with an HTTP error status.")]
+    HttpStatus,
+    /// Local error, an io error of the client, e.g. a request the codec can't encode.
+    #[error("Local io error: {0}.")]
+    Io(std::io::ErrorKind),
     /// Reserved for implementation-defined server-errors.
     #[error("Server error({0}),{1}")]
     ServerError(i64, String),
 }
 
+impl ErrorCode {
+    /// Returns the numeric error code.
+    ///
+    /// Local errors have no code of their own, they are reported as [`ErrorCode::InternalError`]
+    /// when forwarded to a peer, see [`Error::to_remote`].
+    pub fn code(&self) -> i64 {
+        match self {
+            Self::ParseError => -32700,
+            Self::InvalidRequest => -32600,
+            Self::MethodNotFound => -32601,
+            Self::InvalidParams => -32602,
+            Self::InternalError => -32603,
+            Self::RequestCancelled => -32800,
+            Self::ConnectionLost
+            | Self::Timeout
+            | Self::CircuitOpen
+            | Self::HttpStatus
+            | Self::Io(_) => -32603,
+            Self::ServerError(code, _) => *code,
+        }
+    }
+
+    /// Returns true for errors raised by the client itself, which are never sent nor
+    /// received on the wire.
+    pub fn is_local(&self) -> bool {
+        matches!(
+            self,
+            Self::ConnectionLost
+                | Self::Timeout
+                | Self::CircuitOpen
+                | Self::HttpStatus
+                | Self::Io(_)
+        )
+    }
+
+    /// Returns true if `self` and `other` are the same error, server error messages aside.
+    pub fn matches(&self, other: &ErrorCode) -> bool {
+        match (self, other) {
+            (Self::ServerError(code, _), Self::ServerError(other, _)) => code == other,
+            (Self::Io(kind), Self::Io(other)) => kind == other,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+
+    /// Returns the variant name, e.g. `MethodNotFound`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ParseError => "ParseError",
+            Self::InvalidRequest => "InvalidRequest",
+            Self::MethodNotFound => "MethodNotFound",
+            Self::InvalidParams => "InvalidParams",
+            Self::InternalError => "InternalError",
+            Self::RequestCancelled => "RequestCancelled",
+            Self::ConnectionLost => "ConnectionLost",
+            Self::Timeout => "Timeout",
+            Self::CircuitOpen => "CircuitOpen",
+            Self::HttpStatus => "HttpStatus",
+            Self::Io(_) => "Io",
+            Self::ServerError(_, _) => "ServerError",
+        }
+    }
+
+    /// Returns the error code of numeric `code`, `None` if `code` is neither predefined
+    /// nor in the reserved implementation-defined server-errors range.
+    ///
+    /// Local errors are never returned.
+    pub fn from_code(code: i64) -> Option<Self> {
+        match code {
+            -32700 => Some(ErrorCode::ParseError),
+            -32600 => Some(ErrorCode::InvalidRequest),
+            -32601 => Some(ErrorCode::MethodNotFound),
+            -32602 => Some(ErrorCode::InvalidParams),
+            -32603 => Some(ErrorCode::InternalError),
+            -32800 => Some(ErrorCode::RequestCancelled),
+            -32099..=-32000 => Some(ErrorCode::ServerError(code, "".to_owned())),
+            _ => None,
+        }
+    }
+}
+
+/// Local errors can't be serialized, see [`Error::to_remote`].
 impl serde::Serialize for ErrorCode {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        match self {
-            Self::ParseError => serializer.serialize_i64(-32700),
-            Self::InvalidRequest => serializer.serialize_i64(-32600),
-            Self::MethodNotFound => serializer.serialize_i64(-32601),
-            Self::InvalidParams => serializer.serialize_i64(-32602),
-            Self::InternalError => serializer.serialize_i64(-32603),
-            Self::ServerError(code, _) => serializer.serialize_i64(*code),
+        if self.is_local() {
+            return Err(serde::ser::Error::custom(format!(
+                "local error {} has no JSONRPC error code",
+                self.name()
+            )));
         }
+
+        serializer.serialize_i64(self.code())
     }
 }
 
@@ -224,22 +408,8 @@ impl<'de> serde::Deserialize<'de> for ErrorCode {
     {
         let code = deserializer.deserialize_i64(visitor::ErrorCodeVisitor)?;
 
-        match code {
-            -32700 => Ok(ErrorCode::ParseError),
-            -32600 => Ok(ErrorCode::InvalidRequest),
-            -32601 => Ok(ErrorCode::MethodNotFound),
-            -32602 => Ok(ErrorCode::InvalidParams),
-            -32603 => Ok(ErrorCode::InternalError),
-            _ => {
-                // Check reserved implementation-defined server-errors range.
-                if code <= -32000 && code >= -32099 {
-                    Ok(ErrorCode::ServerError(code, "".to_owned()))
-                } else {
-                    Err(format!("Invalid JSONRPC error code {}", code))
-                        .map_err(serde::de::Error::custom)
-                }
-            }
-        }
+        ErrorCode::from_code(code)
+            .ok_or_else(|| serde::de::Error::custom(format!("Invalid JSONRPC error code {}", code)))
     }
 }
 
@@ -327,7 +497,7 @@ mod tests {
     use serde::{Deserialize, Serialize};
     use serde_json::json;
 
-    use super::Request;
+    use super::{Error, ErrorCode, RawResponse, Request, RequestV1, ResponseV1};
 
     #[test]
     fn test_array_params() {
@@ -420,4 +590,141 @@ mod tests {
         assert_eq!(request.params.id, 20);
         assert_eq!(request.params.name, "hello");
     }
+
+    #[test]
+    fn test_raw_response() {
+        let frame = r#"{"id":1,"jsonrpc":"2.0","result":{"name":"world","list":[1, 2]}}"#;
+
+        let response = serde_json::from_str::<RawResponse>(frame).expect("parse raw response");
+
+        assert_eq!(
+            response.result.as_ref().map(|result| result.get()),
+            Some(r#"{"name":"world","list":[1, 2]}"#)
+        );
+
+        // Forwarding keeps the result bytes untouched.
+        assert_eq!(serde_json::to_string(&response).unwrap(), frame);
+
+        let response = serde_json::from_str::<RawResponse>(
+            r#"{"id":2,"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found","data":[1]}}"#,
+        )
+        .expect("parse raw error response");
+
+        let error = response.error.expect("error response");
+
+        assert_eq!(error.code, ErrorCode::MethodNotFound);
+        assert_eq!(
+            error.data.map(|data| data.get().to_owned()),
+            Some("[1]".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_v1_objects() {
+        let request = RequestV1 {
+            id: None,
+            method: "hello",
+            params: [10],
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({"id": null, "method": "hello", "params": [10]})
+        );
+
+        let request = serde_json::from_value::<RequestV1<String, Vec<i32>>>(
+            json!({"id": 1, "method": "hello", "params": [10]}),
+        )
+        .expect("parse 1.0 request");
+
+        assert_eq!(request.id, Some(1));
+
+        let response = ResponseV1::<String, i32, ()> {
+            id: 1,
+            result: None,
+            error: Some(Error {
+                code: ErrorCode::MethodNotFound,
+                message: "Method not found".to_owned(),
+                data: None,
+            }),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "id": 1,
+                "result": null,
+                "error": {"code": -32601, "message": "Method not found", "data": null},
+            })
+        );
+    }
+
+    #[test]
+    fn test_local_errors() {
+        let err = Error {
+            code: ErrorCode::Timeout,
+            message: "Response timeout".to_owned(),
+            data: Some(json!({"retry": true})),
+        };
+
+        // Local errors never go on the wire as is.
+        assert!(serde_json::to_value(&err).is_err());
+
+        assert_eq!(
+            serde_json::to_value(err.to_remote()).unwrap(),
+            json!({"code": -32603, "message": "Response timeout", "data": {"retry": true}})
+        );
+
+        // Codes formerly used by local errors are not known codes.
+        for code in -32903..=-32900 {
+            assert_eq!(ErrorCode::from_code(code), None);
+        }
+
+        // Nor received, whatever the members of the error object.
+        let remote: Error<String, serde_json::Value> = serde_json::from_value(
+            json!({"code": -32603, "message": "failed", "local": "Timeout"}),
+        )
+        .unwrap();
+
+        assert_eq!(remote.code, ErrorCode::InternalError);
+
+        assert!(ErrorCode::ServerError(-32000, "a".to_owned())
+            .matches(&ErrorCode::ServerError(-32000, "".to_owned())));
+        assert!(!ErrorCode::Timeout.matches(&ErrorCode::InternalError));
+    }
+
+    #[test]
+    fn test_io_errors() {
+        let err = Error::from(std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout"));
+
+        assert_eq!(err.code, ErrorCode::Io(std::io::ErrorKind::TimedOut));
+        assert!(err.is_io(std::io::ErrorKind::TimedOut));
+        assert!(!err.is_io(std::io::ErrorKind::InvalidData));
+        assert!(!ErrorCode::Io(std::io::ErrorKind::TimedOut)
+            .matches(&ErrorCode::Io(std::io::ErrorKind::InvalidData)));
+
+        // The io error kind is never sent to a peer.
+        assert_eq!(
+            serde_json::to_value(err.to_remote()).unwrap(),
+            json!({"code": -32603, "message": "Underly io error: timeout", "data": null})
+        );
+
+        // Nor taken from the error data of a peer.
+        let remote: Error<String, serde_json::Value> = serde_json::from_value(
+            json!({"code": -32603, "message": "failed", "data": {"io": "TimedOut"}}),
+        )
+        .unwrap();
+
+        assert!(!remote.is_io(std::io::ErrorKind::TimedOut));
+        assert_eq!(remote.map_timeout().code, ErrorCode::InternalError);
+
+        assert_eq!(err.clone().map_timeout().code, ErrorCode::Timeout);
+
+        let err = Error::from(std::io::Error::new(std::io::ErrorKind::InvalidData, "bad"));
+
+        assert_eq!(
+            err.map_timeout().code,
+            ErrorCode::Io(std::io::ErrorKind::InvalidData)
+        );
+    }
 }
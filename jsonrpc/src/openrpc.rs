@@ -0,0 +1,359 @@
+//! OpenRPC service description, served by the built-in [`DISCOVER`] method
+//!
+//! See <https://spec.open-rpc.org>.
+
+use std::collections::{BTreeMap, HashMap};
+
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{InstanceType, Schema, SchemaObject, SingleOrVec},
+    JsonSchema, Map,
+};
+use serde_json::{json, Value};
+
+/// Method returning the OpenRPC document of a [`Server`](crate::server::Server).
+pub const DISCOVER: &str = "rpc.discover";
+
+/// Version of the OpenRPC specification followed by generated documents.
+pub const OPENRPC_VERSION: &str = "1.2.6";
+
+/// `info` object of the document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Info {
+    pub title: String,
+    /// Version of the described API.
+    pub version: String,
+}
+
+impl Default for Info {
+    fn default() -> Self {
+        Self {
+            title: "JSON-RPC API".to_owned(),
+            version: "0.0.0".to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const DRAFT_07: &str = "http://json-schema.org/draft-07/schema#";
+
+const DEFINITIONS: &str = "#/components/schemas/";
+
+#[derive(Debug, Clone)]
+struct MethodSchema {
+    params: Schema,
+    result: Schema,
+}
+
+/// Generate the schemas of one method.
+type Generate = fn(&mut SchemaGenerator) -> MethodSchema;
+
+fn generate<P, R>(generator: &mut SchemaGenerator) -> MethodSchema
+where
+    P: JsonSchema,
+    R: JsonSchema,
+{
+    MethodSchema {
+        params: generator.subschema_for::<P>(),
+        result: generator.subschema_for::<R>(),
+    }
+}
+
+/// Param and result schemas of the server methods.
+///
+/// Keeps the definitions rather than the generator, which is not `Sync`. All methods are
+/// generated again by one generator on change, which names apart distinct types of the
+/// same name, e.g. `Transfer` and `Transfer2`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Registry {
+    pub info: Info,
+    generators: BTreeMap<String, Generate>,
+    definitions: Map<String, Schema>,
+    methods: HashMap<String, MethodSchema>,
+}
+
+impl Registry {
+    /// Record the schemas of `method` taking `P` and returning `R`.
+    pub fn register<P, R>(&mut self, method: &str)
+    where
+        P: JsonSchema,
+        R: JsonSchema,
+    {
+        self.generators.insert(method.to_owned(), generate::<P, R>);
+
+        self.generate();
+    }
+
+    /// Forget the schemas of `method`, registered again without schema.
+    pub fn remove(&mut self, method: &str) {
+        if self.generators.remove(method).is_some() {
+            self.generate();
+        }
+    }
+
+    /// Generate the schemas of all methods in name order, so names are stable.
+    fn generate(&mut self) {
+        let mut generator = SchemaSettings::draft07()
+            .with(|settings| settings.definitions_path = DEFINITIONS.to_owned())
+            .into_generator();
+
+        self.methods = self
+            .generators
+            .iter()
+            .map(|(method, generate)| (method.clone(), generate(&mut generator)))
+            .collect();
+
+        self.definitions = generator.take_definitions();
+    }
+
+    /// Returns the self-contained params schema of `method`, embedding the definitions it references.
+    #[cfg(feature = "validation")]
+    pub fn params_schema(&self, method: &str) -> Option<Value> {
+        let mut schema = serde_json::to_value(&self.methods.get(method)?.params).ok()?;
+
+        if let Value::Object(object) = &mut schema {
+            object.insert("$schema".to_owned(), json!(DRAFT_07));
+            object.insert(
+                "components".to_owned(),
+                json!({"schemas": self.definitions}),
+            );
+        }
+
+        Some(schema)
+    }
+
+    /// Build the document describing `methods`, sorted by name.
+    ///
+    /// Methods registered without schema take any value as one optional `params` param and
+    /// return any result.
+    pub fn document<'a, I>(&self, methods: I) -> Value
+    where
+        I: IntoIterator<Item = &'a String>,
+    {
+        let mut methods = methods.into_iter().collect::<Vec<_>>();
+
+        methods.sort();
+
+        let methods = methods
+            .into_iter()
+            .map(|name| match self.methods.get(name) {
+                Some(schema) => {
+                    let (structure, params) = self.params(&schema.params);
+
+                    json!({
+                        "name": name,
+                        "paramStructure": structure,
+                        "params": params,
+                        "result": {"name": "result", "schema": schema.result},
+                    })
+                }
+                None => json!({
+                    "name": name,
+                    "paramStructure": "either",
+                    "params": [param("params", &Schema::Object(Default::default()), false)],
+                    "result": {"name": "result", "schema": {}},
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "openrpc": OPENRPC_VERSION,
+            "info": {
+                "title": self.info.title,
+                "version": self.info.version,
+            },
+            "methods": methods,
+            "components": {
+                "schemas": self.definitions,
+            },
+        })
+    }
+
+    /// Content descriptors of params `schema` with the param structure.
+    ///
+    /// Structs are described by name, tuples by position, unit without param,
+    /// any other type as one `params` param.
+    fn params(&self, schema: &Schema) -> (&'static str, Vec<Value>) {
+        let resolved = match schema {
+            Schema::Object(SchemaObject {
+                reference: Some(reference),
+                ..
+            }) => reference
+                .strip_prefix(DEFINITIONS)
+                .and_then(|name| self.definitions.get(name))
+                .unwrap_or(schema),
+            schema => schema,
+        };
+
+        let object = match resolved {
+            Schema::Object(object) => object,
+            Schema::Bool(_) => return ("either", vec![param("params", schema, false)]),
+        };
+
+        if let Some(validation) = &object.object {
+            if !validation.properties.is_empty() {
+                let params = validation
+                    .properties
+                    .iter()
+                    .map(|(name, schema)| param(name, schema, validation.required.contains(name)))
+                    .collect();
+
+                return ("by-name", params);
+            }
+        }
+
+        if let Some(SingleOrVec::Vec(items)) = object.array.as_ref().and_then(|a| a.items.as_ref())
+        {
+            let params = items
+                .iter()
+                .enumerate()
+                .map(|(index, schema)| param(&format!("arg{}", index), schema, true))
+                .collect();
+
+            return ("by-position", params);
+        }
+
+        if object.instance_type == Some(SingleOrVec::Single(Box::new(InstanceType::Null))) {
+            return ("either", vec![]);
+        }
+
+        ("either", vec![param("params", schema, true)])
+    }
+}
+
+fn param(name: &str, schema: &Schema, required: bool) -> Value {
+    json!({"name": name, "schema": schema, "required": required})
+}
+
+#[cfg(test)]
+mod tests {
+    use async_timer_rs::hashed::Timeout;
+    use futures::future;
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+
+    use crate::{harness::Harness, server::Server};
+
+    use super::{Registry, DISCOVER};
+
+    #[derive(Deserialize, JsonSchema)]
+    struct Transfer {
+        to: String,
+        amount: u64,
+        memo: Option<String>,
+    }
+
+    #[futures_test::test]
+    async fn test_discover() {
+        let mut server = Server::new();
+
+        server
+            .info("wallet", "1.0.0")
+            .handle_with_schema("transfer", |_, params: Transfer| {
+                future::ready(Ok(params.amount > 0
+                    && !params.to.is_empty()
+                    && params.memo.is_none()))
+            })
+            .handle_with_schema("add", |_, (lhs, rhs): (i64, i64)| {
+                future::ready(Ok(lhs + rhs))
+            })
+            .handle("echo", |_, params: Value| future::ready(Ok(params)));
+
+        let document = Harness::new(server)
+            .run(|mut client| async move {
+                client
+                    .call::<_, Value, Timeout>(DISCOVER, (), None)
+                    .await
+                    .unwrap()
+            })
+            .await;
+
+        assert_eq!(document["openrpc"], "1.2.6");
+        assert_eq!(
+            document["info"],
+            json!({"title": "wallet", "version": "1.0.0"})
+        );
+
+        let methods = document["methods"].as_array().unwrap();
+
+        assert_eq!(
+            methods
+                .iter()
+                .map(|m| m["name"].clone())
+                .collect::<Vec<_>>(),
+            vec!["add", "echo", "transfer"]
+        );
+
+        assert_eq!(methods[0]["paramStructure"], "by-position");
+        assert_eq!(methods[0]["params"][1]["name"], "arg1");
+        assert_eq!(methods[0]["result"]["schema"]["type"], "integer");
+
+        assert_eq!(
+            methods[1]["params"],
+            json!([{"name": "params", "schema": {}, "required": false}])
+        );
+
+        assert_eq!(methods[2]["paramStructure"], "by-name");
+        assert_eq!(
+            methods[2]["params"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| (
+                    p["name"].as_str().unwrap(),
+                    p["required"].as_bool().unwrap()
+                ))
+                .collect::<Vec<_>>(),
+            vec![("amount", true), ("memo", false), ("to", true)]
+        );
+
+        assert!(document["components"]["schemas"]["Transfer"].is_object());
+    }
+
+    mod v2 {
+        use schemars::JsonSchema;
+
+        /// Type of the same name as [`super::Transfer`].
+        #[allow(dead_code)]
+        #[derive(JsonSchema)]
+        pub struct Transfer {
+            pub to: Vec<String>,
+        }
+    }
+
+    #[test]
+    fn test_name_collision() {
+        let mut registry = Registry::default();
+
+        registry.register::<Transfer, ()>("transfer");
+        registry.register::<v2::Transfer, ()>("transfer_v2");
+        registry.register::<(Transfer,), ()>("transfer_all");
+
+        let document = registry.document(
+            ["transfer", "transfer_all", "transfer_v2"]
+                .map(ToOwned::to_owned)
+                .iter(),
+        );
+
+        let schemas = &document["components"]["schemas"];
+
+        assert!(schemas["Transfer"]["properties"]["amount"].is_object());
+        assert!(schemas["Transfer2"]["properties"]["to"]["items"].is_object());
+
+        let methods = &document["methods"];
+
+        assert_eq!(
+            methods[1]["params"][0]["schema"]["$ref"],
+            "#/components/schemas/Transfer"
+        );
+        assert_eq!(methods[2]["params"][0]["name"], "to");
+
+        // Definitions of a removed method go away.
+        registry.remove("transfer_v2");
+
+        let document = registry.document(["transfer".to_owned()].iter());
+
+        assert!(document["components"]["schemas"]["Transfer2"].is_null());
+    }
+}
@@ -0,0 +1,809 @@
+//! QUIC transport, each call runs on its own bidirectional stream.
+//!
+//! A slow call never blocks the others, and cancelling a call resets its stream. The
+//! client sends requests on bidirectional streams, answered on the same stream, and
+//! notifications on unidirectional streams. The server pushes notifications on
+//! unidirectional streams too. Each stream carries exactly one JSON frame.
+//!
+//! Runs within a tokio runtime.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    io,
+    pin::{pin, Pin},
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+use bytes::Bytes;
+use futures::{
+    channel::oneshot,
+    future::{self, BoxFuture, Either},
+    stream::FuturesUnordered,
+    FutureExt, Sink, Stream, StreamExt,
+};
+use quinn::{
+    crypto::rustls::{QuicClientConfig, QuicServerConfig},
+    ClientConfig, Connection, ConnectionError, RecvStream, SendStream, ServerConfig, VarInt,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    extensions::Extensions,
+    object::{Error, ErrorCode, Version},
+    server::CANCEL_REQUEST,
+    session::Failed,
+    tls::{CertificateDer, PeerCertificates, TlsClient, TlsServer},
+};
+
+/// Default max size of one inbound frame, 16 MiB.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Application error code of the streams reset by cancellation.
+pub const STREAM_CANCELLED: VarInt = VarInt::from_u32(0x1);
+
+/// Client transport over QUIC `connection`.
+///
+/// A [`CANCEL_REQUEST`] notification is not sent to the server, the stream of the call
+/// is reset instead and the call fails with [`ErrorCode::RequestCancelled`].
+/// Calls failing with the connection fail with [`ErrorCode::ConnectionLost`].
+///
+/// Failed calls are reported by a [`Failed`] stream error.
+pub struct QuicClient {
+    connection: Connection,
+    max_frame_size: usize,
+    /// Next notification pushed by the server, `None` once the connection is closed.
+    accept: Option<BoxFuture<'static, Result<RecvStream, ConnectionError>>>,
+    /// Pending calls and stream reads, returning the inbound frames.
+    calls: FuturesUnordered<BoxFuture<'static, io::Result<Vec<Bytes>>>>,
+    cancels: HashMap<u64, oneshot::Sender<()>>,
+    frames: VecDeque<io::Result<Bytes>>,
+    waker: Option<Waker>,
+}
+
+impl QuicClient {
+    /// Create new transport over established `connection`.
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            accept: Some(accept_uni(connection.clone())),
+            connection,
+            max_frame_size: MAX_FRAME_SIZE,
+            calls: Default::default(),
+            cancels: Default::default(),
+            frames: Default::default(),
+            waker: None,
+        }
+    }
+
+    /// Answer the calls with [`ErrorCode::ConnectionLost`] on response frames larger
+    /// than `size`, default is [`MAX_FRAME_SIZE`].
+    pub fn max_frame_size(mut self, size: usize) -> Self {
+        self.max_frame_size = size;
+        self
+    }
+}
+
+impl Stream for QuicClient {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(frame) = self.frames.pop_front() {
+                return Poll::Ready(Some(frame));
+            }
+
+            if let Some(Poll::Ready(accepted)) = self.accept.as_mut().map(|a| a.poll_unpin(cx)) {
+                match accepted {
+                    Ok(recv) => {
+                        let read = read(recv, self.max_frame_size)
+                            .map(|frame| Ok(frame.into_iter().collect()));
+
+                        self.calls.push(read.boxed());
+
+                        self.accept = Some(accept_uni(self.connection.clone()));
+                    }
+                    Err(err) => {
+                        self.accept = None;
+
+                        if !is_closed(&err) {
+                            return Poll::Ready(Some(Err(into_io_error(err))));
+                        }
+                    }
+                }
+
+                continue;
+            }
+
+            match self.calls.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(frames))) => self.frames.extend(frames.into_iter().map(Ok)),
+                Poll::Ready(Some(Err(err))) => self.frames.push_back(Err(err)),
+                Poll::Ready(None) if self.accept.is_none() => return Poll::Ready(None),
+                Poll::Ready(None) | Poll::Pending => {
+                    self.waker = Some(cx.waker().clone());
+
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+impl Sink<Bytes> for QuicClient {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.connection.close_reason() {
+            Some(err) => Poll::Ready(Err(into_io_error(err))),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, frame: Bytes) -> io::Result<()> {
+        let value = serde_json::from_slice::<Value>(&frame).unwrap_or_default();
+
+        // Drop the cancel handles of finished calls.
+        self.cancels.retain(|_, cancel| !cancel.is_canceled());
+
+        if value["method"] == CANCEL_REQUEST {
+            // JSON-RPC 1.0 params are wrapped in an array.
+            let params = match &value["params"] {
+                Value::Array(params) => params.first().unwrap_or(&Value::Null),
+                params => params,
+            };
+
+            if let Some(cancel) = params["id"]
+                .as_u64()
+                .and_then(|id| self.cancels.remove(&id))
+            {
+                _ = cancel.send(());
+            }
+
+            return Ok(());
+        }
+
+        let ids = ids(&value);
+
+        let call = match (ids.as_slice(), value["id"].as_u64()) {
+            ([], _) => notify(self.connection.clone(), frame)
+                .map(|_| Ok(vec![]))
+                .boxed(),
+            (_, id) => {
+                let (cancel, cancelled) = oneshot::channel();
+
+                if let Some(id) = id {
+                    self.cancels.insert(id, cancel);
+                }
+
+                call(
+                    self.connection.clone(),
+                    frame,
+                    ids.iter().filter_map(Value::as_u64).collect(),
+                    cancelled,
+                    self.max_frame_size,
+                )
+                .boxed()
+            }
+        };
+
+        self.calls.push(call);
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.connection.close(VarInt::from_u32(0), b"");
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Request frame read by [`QuicServer`], with the stream of the response for calls.
+type Request = (Bytes, Option<SendStream>);
+
+/// Server transport over QUIC `connection`, serve it with
+/// [`Server::accept_with`](crate::server::Server::accept_with).
+///
+/// Each response is written to the stream of its request. A call whose stream is
+/// stopped or reset by the client is cancelled like by a [`CANCEL_REQUEST`] notification.
+pub struct QuicServer {
+    connection: Connection,
+    max_frame_size: usize,
+    /// Next call, `None` once the connection is closed.
+    accept_bi: Option<BoxFuture<'static, Result<(SendStream, RecvStream), ConnectionError>>>,
+    /// Next notification, `None` once the connection is closed.
+    accept_uni: Option<BoxFuture<'static, Result<RecvStream, ConnectionError>>>,
+    /// Pending request reads, with the stream of the response.
+    reads: FuturesUnordered<BoxFuture<'static, Option<Request>>>,
+    /// Streams waiting for the response, by request id.
+    streams: HashMap<String, SendStream>,
+    /// Streams of requests without valid id, answered in order by error responses.
+    anonymous: VecDeque<SendStream>,
+    /// Fire with the request id when the client stops a stream.
+    stops: FuturesUnordered<BoxFuture<'static, Option<String>>>,
+    writes: FuturesUnordered<BoxFuture<'static, ()>>,
+    waker: Option<Waker>,
+}
+
+impl QuicServer {
+    /// Create new transport over accepted `connection`.
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            accept_bi: Some(accept_bi(connection.clone())),
+            accept_uni: Some(accept_uni(connection.clone())),
+            connection,
+            max_frame_size: MAX_FRAME_SIZE,
+            reads: Default::default(),
+            streams: Default::default(),
+            anonymous: Default::default(),
+            stops: Default::default(),
+            writes: Default::default(),
+            waker: None,
+        }
+    }
+
+    /// Drop request frames larger than `size`, default is [`MAX_FRAME_SIZE`].
+    pub fn max_frame_size(mut self, size: usize) -> Self {
+        self.max_frame_size = size;
+        self
+    }
+
+    /// Returns the connection extensions of `connection`, holding the [`PeerCertificates`]
+    /// of authenticated clients.
+    pub fn extensions(connection: &Connection) -> Extensions {
+        let mut extensions = Extensions::new();
+
+        let chain = connection
+            .peer_identity()
+            .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok());
+
+        if let Some(chain) = chain {
+            extensions.insert(PeerCertificates(*chain));
+        }
+
+        extensions
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Stream for QuicServer {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        // Drive the response writes.
+        while let Poll::Ready(Some(())) = this.writes.poll_next_unpin(cx) {}
+
+        while let Poll::Ready(Some(stopped)) = this.stops.poll_next_unpin(cx) {
+            let Some(id) = stopped.filter(|id| this.streams.remove(id).is_some()) else {
+                continue;
+            };
+
+            if let Ok(id) = id.parse::<u64>() {
+                log::debug!("stream of request {} stopped", id);
+
+                let frame = json!({
+                    "jsonrpc": Version,
+                    "method": CANCEL_REQUEST,
+                    "params": {"id": id},
+                });
+
+                return Poll::Ready(Some(Ok(frame.to_string().into())));
+            }
+        }
+
+        loop {
+            if let Some(Poll::Ready(accepted)) = this.accept_bi.as_mut().map(|a| a.poll_unpin(cx)) {
+                match accepted {
+                    Ok((send, recv)) => {
+                        let read = read(recv, this.max_frame_size)
+                            .map(|frame| frame.map(|frame| (frame, Some(send))));
+
+                        this.reads.push(read.boxed());
+
+                        this.accept_bi = Some(accept_bi(this.connection.clone()));
+                    }
+                    Err(err) => {
+                        this.accept_bi = None;
+
+                        if !is_closed(&err) {
+                            return Poll::Ready(Some(Err(into_io_error(err))));
+                        }
+                    }
+                }
+
+                continue;
+            }
+
+            if let Some(Poll::Ready(accepted)) = this.accept_uni.as_mut().map(|a| a.poll_unpin(cx))
+            {
+                match accepted {
+                    Ok(recv) => {
+                        let read = read(recv, this.max_frame_size)
+                            .map(|frame| frame.map(|frame| (frame, None)));
+
+                        this.reads.push(read.boxed());
+
+                        this.accept_uni = Some(accept_uni(this.connection.clone()));
+                    }
+                    Err(_) => this.accept_uni = None,
+                }
+
+                continue;
+            }
+
+            match this.reads.poll_next_unpin(cx) {
+                Poll::Ready(Some(Some((frame, send)))) => {
+                    if let Some(send) = send {
+                        let value = serde_json::from_slice::<Value>(&frame).unwrap_or_default();
+
+                        match id_key(&value) {
+                            Some(key) => {
+                                let stopped = send.stopped();
+
+                                this.streams.insert(key.clone(), send);
+
+                                let stop = async move {
+                                    match stopped.await {
+                                        Ok(Some(_)) => Some(key),
+                                        _ => None,
+                                    }
+                                };
+
+                                this.stops.push(stop.boxed());
+                            }
+                            None => this.anonymous.push_back(send),
+                        }
+                    }
+
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+                Poll::Ready(Some(None)) => continue,
+                Poll::Ready(None) if this.accept_bi.is_none() && this.accept_uni.is_none() => {
+                    return Poll::Ready(None)
+                }
+                Poll::Ready(None) | Poll::Pending => {
+                    this.waker = Some(cx.waker().clone());
+
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+impl Sink<Bytes> for QuicServer {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.connection.close_reason() {
+            Some(err) => Poll::Ready(Err(into_io_error(err))),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, frame: Bytes) -> io::Result<()> {
+        let value = serde_json::from_slice::<Value>(&frame).unwrap_or_default();
+
+        let write = match (id_key(&value), value.get("method")) {
+            // Notification pushed to the client.
+            (None, Some(_)) => notify(self.connection.clone(), frame).boxed(),
+            (None, None) => match self.anonymous.pop_front() {
+                Some(send) => respond(send, frame).boxed(),
+                None => {
+                    log::warn!("drop response without request stream");
+
+                    return Ok(());
+                }
+            },
+            (Some(key), _) => match self.streams.remove(&key) {
+                Some(send) => respond(send, frame).boxed(),
+                None => {
+                    log::debug!("drop response of cancelled request {}", key);
+
+                    return Ok(());
+                }
+            },
+        };
+
+        self.writes.push(write);
+
+        self.wake();
+
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while let Poll::Ready(Some(())) = self.writes.poll_next_unpin(cx) {}
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.connection.close(VarInt::from_u32(0), b"");
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Returns the QUIC config of client `tls`, for [`quinn::Endpoint::set_default_client_config`].
+pub fn client_config(tls: &TlsClient) -> io::Result<ClientConfig> {
+    let crypto = QuicClientConfig::try_from(tls.config()).map_err(io::Error::other)?;
+
+    Ok(ClientConfig::new(Arc::new(crypto)))
+}
+
+/// Returns the QUIC config of server `tls`, for [`quinn::Endpoint::server`].
+pub fn server_config(tls: &TlsServer) -> io::Result<ServerConfig> {
+    let crypto = QuicServerConfig::try_from(tls.config()).map_err(io::Error::other)?;
+
+    Ok(ServerConfig::with_crypto(Arc::new(crypto)))
+}
+
+fn accept_bi(
+    connection: Connection,
+) -> BoxFuture<'static, Result<(SendStream, RecvStream), ConnectionError>> {
+    async move { connection.accept_bi().await }.boxed()
+}
+
+fn accept_uni(connection: Connection) -> BoxFuture<'static, Result<RecvStream, ConnectionError>> {
+    async move { connection.accept_uni().await }.boxed()
+}
+
+/// Read one frame from `recv`, `None` on failure or empty stream.
+async fn read(mut recv: RecvStream, max_frame_size: usize) -> Option<Bytes> {
+    match recv.read_to_end(max_frame_size).await {
+        Ok(frame) if frame.is_empty() => None,
+        Ok(frame) => Some(frame.into()),
+        Err(err) => {
+            log::debug!("read quic stream error: {}", err);
+
+            None
+        }
+    }
+}
+
+/// Send `frame` on a new unidirectional stream.
+async fn notify(connection: Connection, frame: Bytes) {
+    let result = async {
+        let mut send = connection.open_uni().await.map_err(into_io_error)?;
+
+        send.write_all(&frame).await?;
+
+        send.finish().map_err(io::Error::other)
+    };
+
+    if let Err(err) = result.await {
+        log::debug!("send quic notification error: {}", err);
+    }
+}
+
+/// Write response `frame` to `send` and finish the stream.
+async fn respond(mut send: SendStream, frame: Bytes) {
+    let result = async {
+        send.write_all(&frame).await?;
+
+        send.finish().map_err(io::Error::other)
+    };
+
+    if let Err(err) = result.await {
+        log::debug!("send quic response error: {}", err);
+    }
+}
+
+/// Send request `frame` of calls `ids` on a new bidirectional stream, returns the
+/// inbound frames.
+async fn call(
+    connection: Connection,
+    frame: Bytes,
+    ids: Vec<u64>,
+    cancelled: oneshot::Receiver<()>,
+    max_frame_size: usize,
+) -> io::Result<Vec<Bytes>> {
+    let (mut send, mut recv) = match connection.open_bi().await {
+        Ok(streams) => streams,
+        Err(err) => return Err(connection_lost(ids, err)),
+    };
+
+    let response = {
+        let request = pin!(async {
+            send.write_all(&frame).await?;
+
+            send.finish().map_err(io::Error::other)?;
+
+            recv.read_to_end(max_frame_size)
+                .await
+                .map_err(io::Error::other)
+        });
+
+        match future::select(request, cancelled).await {
+            Either::Left((response, _)) => Some(response),
+            Either::Right((Ok(()), _)) => None,
+            // Cancel handle dropped, wait for the response.
+            Either::Right((Err(_), request)) => Some(request.await),
+        }
+    };
+
+    match response {
+        Some(Ok(response)) if response.is_empty() => Ok(vec![]),
+        Some(Ok(response)) => Ok(vec![response.into()]),
+        Some(Err(err)) => Err(connection_lost(ids, err)),
+        None => {
+            _ = send.reset(STREAM_CANCELLED);
+            _ = recv.stop(STREAM_CANCELLED);
+
+            let error = Error {
+                code: ErrorCode::RequestCancelled,
+                message: "Request cancelled".to_owned(),
+                data: None,
+            };
+
+            Err(Failed { ids, error }.into())
+        }
+    }
+}
+
+/// Returns the non null ids of the request or batch `value`.
+fn ids(value: &Value) -> Vec<Value> {
+    let ids = match value {
+        Value::Array(requests) => requests.iter().map(|r| r["id"].clone()).collect(),
+        request => vec![request["id"].clone()],
+    };
+
+    ids.into_iter().filter(|id| !id.is_null()).collect()
+}
+
+/// Returns the key routing the request or response `value` to its stream, the first id.
+fn id_key(value: &Value) -> Option<String> {
+    ids(value).first().map(Value::to_string)
+}
+
+/// Returns the [`Failed`] error of the calls `ids` lost with `err`.
+fn connection_lost(ids: Vec<u64>, err: impl Display) -> io::Error {
+    let error = Error {
+        message: format!("QUIC call failed: {}", err),
+        ..Error::connection_lost()
+    };
+
+    Failed { ids, error }.into()
+}
+
+/// Returns true if `err` is a graceful close by either peer.
+fn is_closed(err: &ConnectionError) -> bool {
+    matches!(
+        err,
+        ConnectionError::ApplicationClosed(_) | ConnectionError::LocallyClosed
+    )
+}
+
+fn into_io_error(err: ConnectionError) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use async_timer_rs::hashed::Timeout;
+    use futures::{channel::oneshot, future, StreamExt};
+    use quinn::{Connection, Endpoint};
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, Issuer, KeyPair};
+    use serde_json::json;
+
+    use crate::{
+        cancel::CancellationToken,
+        client::Client,
+        object::ErrorCode,
+        result::RPCResult,
+        server::{Context, Server},
+        session::{Notification, Session},
+        tls::{PeerCertificates, RootCertStore, TlsClient, TlsServer},
+    };
+
+    use super::{client_config, server_config, QuicClient, QuicServer};
+
+    /// Serve `server` with mutual TLS on a local endpoint, returns the client connection
+    /// and the DER of its certificate.
+    async fn connect(server: Server) -> (Connection, Vec<u8>) {
+        let mut params = CertificateParams::new(vec![]).unwrap();
+
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+
+        let ca_key = KeyPair::generate().unwrap();
+
+        let ca_cert = params.self_signed(&ca_key).unwrap().der().clone();
+
+        let issuer = Issuer::new(params, ca_key);
+
+        let mut roots = RootCertStore::empty();
+
+        roots.add(ca_cert).unwrap();
+
+        let issue = |name: &str| {
+            let key = KeyPair::generate().unwrap();
+
+            let cert = CertificateParams::new(vec![name.to_owned()])
+                .unwrap()
+                .signed_by(&key, &issuer)
+                .unwrap();
+
+            (vec![cert.der().clone()], key.into())
+        };
+
+        let (chain, key) = issue("localhost");
+
+        let (client_chain, client_key) = issue("client");
+
+        let tls = TlsServer::with_client_auth(chain, key, roots.clone()).unwrap();
+
+        let endpoint =
+            Endpoint::server(server_config(&tls).unwrap(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let addr = endpoint.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Some(incoming) = endpoint.accept().await {
+                let server = server.clone();
+
+                tokio::spawn(async move {
+                    let connection = incoming.await.unwrap();
+
+                    let extensions = QuicServer::extensions(&connection);
+
+                    _ = server
+                        .accept_with(QuicServer::new(connection), extensions)
+                        .await;
+                });
+            }
+        });
+
+        let cert = client_chain[0].to_vec();
+
+        let tls = TlsClient::with_identity(roots, client_chain, client_key).unwrap();
+
+        let mut endpoint = Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+
+        endpoint.set_default_client_config(client_config(&tls).unwrap());
+
+        let connection = endpoint.connect(addr, "localhost").unwrap().await.unwrap();
+
+        (connection, cert)
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        let (release, released) = oneshot::channel::<()>();
+
+        let released = Arc::new(Mutex::new(Some(released)));
+
+        let mut server = Server::new();
+
+        server
+            .handle("slow", move |_, _: ()| {
+                let released = released.lock().unwrap().take().unwrap();
+
+                async move {
+                    _ = released.await;
+
+                    Ok("slow")
+                }
+            })
+            .handle("fast", move |mut context: Context, _: ()| {
+                context.peer.try_notify("event", [1]).unwrap();
+
+                let cert = context
+                    .extensions
+                    .get::<PeerCertificates>()
+                    .and_then(|certs| certs.end_entity())
+                    .map(|cert| cert.to_vec());
+
+                future::ready(Ok(cert))
+            });
+
+        let (connection, cert) = connect(server).await;
+
+        let (client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let mut notifications = session.notifications();
+
+        let test = async move {
+            let mut slow_client = client.clone();
+
+            let slow = tokio::spawn(async move {
+                slow_client
+                    .call::<_, String, Timeout>("slow", (), None)
+                    .await
+            });
+
+            // Answered while the slow call is still pending on its own stream.
+            let result = client
+                .clone()
+                .call::<_, Option<Vec<u8>>, Timeout>("fast", (), None)
+                .await;
+
+            assert_eq!(result.unwrap(), Some(cert));
+
+            assert_eq!(
+                notifications.next().await,
+                Some(Notification {
+                    method: "event".to_owned(),
+                    params: json!([1]),
+                })
+            );
+
+            release.send(()).unwrap();
+
+            assert_eq!(slow.await.unwrap().unwrap(), "slow");
+        };
+
+        let (_, _) = future::join(session.run(QuicClient::new(connection)), test).await;
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let token = Arc::new(Mutex::new(None));
+
+        let handler_token = token.clone();
+
+        let mut server = Server::new();
+
+        server.handle("wait", move |context: Context, _: ()| {
+            *handler_token.lock().unwrap() = Some(context.cancellation.clone());
+
+            future::pending::<RPCResult<()>>()
+        });
+
+        let (connection, _) = connect(server).await;
+
+        let (client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let test = async move {
+            let mut call_client = client.clone();
+
+            let id = client.next_id();
+
+            let call = tokio::spawn(async move {
+                call_client
+                    .call_with_id::<_, (), Timeout>(id, "wait", (), None)
+                    .await
+            });
+
+            // Let the request reach the server.
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            client.clone().cancel(id).await.unwrap();
+
+            let err = call.await.unwrap().unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::RequestCancelled);
+
+            // The server sees the stream reset and fires the cancellation token.
+            let token: Option<CancellationToken> = token.lock().unwrap().clone();
+
+            token.unwrap().cancelled().await;
+        };
+
+        let (_, _) = future::join(session.run(QuicClient::new(connection)), test).await;
+    }
+}
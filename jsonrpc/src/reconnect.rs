@@ -0,0 +1,288 @@
+//! Automatic reconnecting client session
+
+use std::{future::Future, io};
+
+use async_timer_rs::Timer;
+use bytes::Bytes;
+use futures::{
+    future::{self, Either},
+    FutureExt,
+};
+use librpc::transport::Transport;
+
+use crate::{
+    backoff::Backoff,
+    client::{Output, Responder},
+    codec::{Json, RPCCodec},
+    session::Session,
+};
+
+/// How to handle calls made while the connection is being re-established.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReconnectMode {
+    /// Keep the calls queued and send them once reconnected.
+    #[default]
+    Queue,
+    /// Fail the calls immediately with [`ErrorCode::ConnectionLost`](crate::object::ErrorCode::ConnectionLost).
+    FailFast,
+}
+
+/// Re-establish the client transport with exponential backoff whenever it drops.
+pub struct Reconnect<F, C = Json> {
+    connect: F,
+    backoff: Backoff,
+    mode: ReconnectMode,
+    codec: C,
+}
+
+impl<F, Fut, T> Reconnect<F>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+    T: Transport<Bytes>,
+{
+    /// Create new reconnecting wrapper, `connect` opens a new transport on each call.
+    pub fn new(connect: F) -> Self {
+        Self {
+            connect,
+            backoff: Default::default(),
+            mode: Default::default(),
+            codec: Json,
+        }
+    }
+}
+
+impl<F, Fut, T, C> Reconnect<F, C>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+    T: Transport<Bytes>,
+    C: RPCCodec,
+{
+    /// Set response codec, which must match the client one.
+    pub fn codec<Codec: RPCCodec>(self, codec: Codec) -> Reconnect<F, Codec> {
+        Reconnect {
+            connect: self.connect,
+            backoff: self.backoff,
+            mode: self.mode,
+            codec,
+        }
+    }
+
+    /// Set reconnect backoff policy.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set how to handle calls made while reconnecting.
+    pub fn mode(mut self, mode: ReconnectMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Run client session, returns when all [`Client`](crate::client::Client) instances are dropped.
+    pub async fn run<Tm>(mut self, output: Output, responder: Responder)
+    where
+        Tm: Timer + Unpin,
+    {
+        let mut session = Session::with_codec(output, responder, self.codec.clone());
+
+        let mut attempt = 0;
+
+        loop {
+            let connect = (self.connect)();
+
+            let Some(connected) = self.offline(&mut session, connect).await else {
+                return;
+            };
+
+            match connected {
+                Ok(transport) => {
+                    attempt = 0;
+
+                    match session.run(transport).await {
+                        Ok(_) => return,
+                        Err(err) => log::warn!("connection lost: {}", err),
+                    }
+                }
+                Err(err) => log::warn!("connect failed: {}", err),
+            }
+
+            let delay = self.backoff.delay(attempt);
+
+            attempt = attempt.saturating_add(1);
+
+            log::debug!("reconnect in {:?}", delay);
+
+            if self.offline(&mut session, Tm::new(delay)).await.is_none() {
+                return;
+            }
+        }
+    }
+
+    /// Drive `future` while disconnected, e.g. the backoff delay or the connect attempt,
+    /// handling the calls made meanwhile with [`ReconnectMode`].
+    ///
+    /// Returns `None` if all clients are dropped first.
+    async fn offline<W>(&self, session: &mut Session<C>, future: W) -> Option<W::Output>
+    where
+        W: Future,
+    {
+        let mode = self.mode;
+
+        let drain = async move {
+            while let Some((id, frame)) = session.next().await {
+                match (mode, id) {
+                    (ReconnectMode::Queue, _) => session.queue((id, frame)),
+                    (ReconnectMode::FailFast, Some(id)) => session.fail(id),
+                    (ReconnectMode::FailFast, None) => {
+                        log::debug!("drop notification, connection lost")
+                    }
+                }
+            }
+        };
+
+        match future::select(Box::pin(future), drain.boxed_local()).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration};
+
+    use async_timer_rs::hashed::Timeout;
+    use bytes::Bytes;
+    use futures::{
+        channel::{
+            mpsc::{Receiver, Sender},
+            oneshot,
+        },
+        future, SinkExt, StreamExt,
+    };
+    use librpc::transport::memory::pipe;
+    use serde_json::json;
+
+    use crate::{backoff::Backoff, client::Client, object::ErrorCode, object::Request};
+
+    use super::{Reconnect, ReconnectMode};
+
+    /// Answer the next request of `output` on `input` with its params.
+    async fn echo(input: &mut Sender<Bytes>, output: &mut Receiver<Bytes>) {
+        let frame = output.next().await.expect("request frame");
+
+        let request: Request<String, String> = serde_json::from_slice(&frame).unwrap();
+
+        input
+            .send(
+                json!({"jsonrpc":"2.0","id":request.id,"result":request.params})
+                    .to_string()
+                    .into(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[futures_test::test]
+    async fn test_reconnect() {
+        _ = pretty_env_logger::try_init();
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let (first, first_input, mut first_output) = pipe(10);
+        let (second, mut second_input, mut second_output) = pipe(10);
+
+        let mut transports = vec![first, second].into_iter();
+
+        let reconnect =
+            Reconnect::new(move || {
+                future::ready(transports.next().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotConnected, "no more transport")
+                }))
+            })
+            .backoff(Backoff {
+                initial: Duration::from_millis(10),
+                ..Default::default()
+            });
+
+        let test = async {
+            // The first connection drops after receiving the request.
+            let server = async move {
+                first_output.next().await.expect("request frame");
+                drop(first_input);
+            };
+
+            let (result, _) = future::join(
+                client.call::<_, String, Timeout>("echo", "hello", None),
+                server,
+            )
+            .await;
+
+            assert_eq!(result.unwrap_err().code, ErrorCode::ConnectionLost);
+
+            let (result, _) = future::join(
+                client.call::<_, String, Timeout>("echo", "world", None),
+                echo(&mut second_input, &mut second_output),
+            )
+            .await;
+
+            assert_eq!(result.unwrap(), "world");
+
+            drop(client);
+        };
+
+        future::join(reconnect.run::<Timeout>(output, responder), test).await;
+    }
+
+    #[futures_test::test]
+    async fn test_fail_fast_while_connecting() {
+        let (mut client, output, responder) = Client::new(10);
+
+        let (transport, mut input, mut frames) = pipe(10);
+
+        let (connected, connecting) = oneshot::channel::<()>();
+
+        let mut connect = Some((connecting, transport));
+
+        let reconnect = Reconnect::new(move || {
+            let next = connect.take();
+
+            async move {
+                let (connecting, transport) = next.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotConnected, "no more transport")
+                })?;
+
+                // Slow connect, established once the test says so.
+                _ = connecting.await;
+
+                Ok(transport)
+            }
+        })
+        .mode(ReconnectMode::FailFast);
+
+        let test = async {
+            let result = client
+                .call::<_, String, Timeout>("echo", "hello", None)
+                .await;
+
+            assert_eq!(result.unwrap_err().code, ErrorCode::ConnectionLost);
+
+            connected.send(()).unwrap();
+
+            let (result, _) = future::join(
+                client.call::<_, String, Timeout>("echo", "world", None),
+                echo(&mut input, &mut frames),
+            )
+            .await;
+
+            assert_eq!(result.unwrap(), "world");
+
+            drop(client);
+        };
+
+        future::join(reconnect.run::<Timeout>(output, responder), test).await;
+    }
+}
@@ -0,0 +1,232 @@
+//! Client call retry policy
+
+use std::{collections::HashSet, io};
+
+use crate::{backoff::Backoff, object::ErrorCode, result::RPCError};
+
+/// Declarative retry policy for [`Client::call_with_retry`](crate::client::Client::call_with_retry).
+///
+/// Only methods in the idempotent allowlist are ever retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay between two attempts.
+    pub backoff: Backoff,
+    /// Error codes considered as transient, see [`ErrorCode::matches`].
+    pub retryable: Vec<ErrorCode>,
+    /// Kinds of local io errors considered as transient, see [`RPCError::is_io`].
+    pub retryable_io: Vec<io::ErrorKind>,
+    /// Methods safe to send more than once.
+    pub idempotent: HashSet<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Default::default(),
+            retryable: vec![ErrorCode::Timeout, ErrorCode::ConnectionLost],
+            retryable_io: vec![],
+            idempotent: Default::default(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Add `method` to the idempotent allowlist.
+    pub fn idempotent(mut self, method: &str) -> Self {
+        self.idempotent.insert(method.to_owned());
+        self
+    }
+
+    /// Treat `code` as retryable.
+    pub fn retryable(mut self, code: ErrorCode) -> Self {
+        self.retryable.push(code);
+        self
+    }
+
+    /// Treat local io errors of `kind` as retryable.
+    pub fn retryable_io(mut self, kind: io::ErrorKind) -> Self {
+        self.retryable_io.push(kind);
+        self
+    }
+
+    /// Returns true if the failed `attempt` (counting from 0) of `method` should be retried.
+    pub fn should_retry(&self, method: &str, attempt: u32, err: &RPCError) -> bool {
+        attempt + 1 < self.max_attempts
+            && self.idempotent.contains(method)
+            && (self.retryable.iter().any(|code| code.matches(&err.code))
+                || self.retryable_io.iter().any(|kind| err.is_io(*kind)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use async_timer_rs::hashed::Timeout;
+    use futures::future;
+    use librpc::transport::{
+        fault::{Faults, Faulty},
+        memory::pair,
+    };
+
+    use crate::{
+        backoff::Backoff,
+        client::Client,
+        object::{Error, ErrorCode},
+        server::{Context, Server},
+        session::Session,
+    };
+
+    use super::RetryPolicy;
+
+    #[futures_test::test]
+    async fn test_retry() {
+        let ids = Arc::new(Mutex::new(vec![]));
+
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let mut server = Server::new();
+
+        let handler_ids = ids.clone();
+
+        let handler = move |context: Context, params: String| {
+            handler_ids.lock().unwrap().push(context.id.unwrap());
+
+            // Every odd call fails with a transient server error.
+            let result = match counter.fetch_add(1, Ordering::SeqCst) % 2 {
+                0 => Err(Error {
+                    code: ErrorCode::ServerError(-32000, "Busy".to_owned()),
+                    message: "Server busy".to_owned(),
+                    data: None,
+                }),
+                _ => Ok(params),
+            };
+
+            future::ready(result)
+        };
+
+        server.handle("get", handler.clone()).handle("put", handler);
+
+        let (client_transport, server_transport) = pair(10);
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let policy = RetryPolicy {
+            backoff: Backoff {
+                initial: Duration::from_millis(10),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .idempotent("get")
+        .retryable(ErrorCode::ServerError(-32000, "".to_owned()));
+
+        let test = async move {
+            let result = client
+                .call_with_retry::<_, String, Timeout>("get", "hello", None, &policy)
+                .await;
+
+            assert_eq!(result.unwrap(), "hello");
+
+            // Not in the idempotent allowlist
+            let result = client
+                .call_with_retry::<_, String, Timeout>("put", "hello", None, &policy)
+                .await;
+
+            assert_eq!(
+                result.unwrap_err().code,
+                ErrorCode::ServerError(-32000, "".to_owned())
+            );
+        };
+
+        let (server_result, session_result, _) = future::join3(
+            server.accept(server_transport),
+            session.run(client_transport),
+            test,
+        )
+        .await;
+
+        server_result.unwrap();
+        session_result.unwrap();
+
+        // Retried call gets fresh id
+        assert_eq!(*ids.lock().unwrap(), vec![0, 1, 2]);
+    }
+
+    #[futures_test::test]
+    async fn test_retry_lost_frames() {
+        let mut server = Server::new();
+
+        server.handle("get", |_, params: u32| future::ready(Ok(params)));
+
+        let (client_transport, server_transport) = pair(10);
+
+        // Lose 30% of requests and responses.
+        let client_transport =
+            Faulty::<_, Timeout>::new(client_transport, Faults::new(3).drop(0.3));
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let policy = RetryPolicy {
+            max_attempts: 20,
+            backoff: Backoff {
+                initial: Duration::from_millis(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .idempotent("get");
+
+        let test = async move {
+            for i in 0..10 {
+                let result = client
+                    .call_with_retry::<_, u32, Timeout>(
+                        "get",
+                        i,
+                        Some(Duration::from_millis(20)),
+                        &policy,
+                    )
+                    .await;
+
+                assert_eq!(result.unwrap(), i);
+            }
+        };
+
+        let (_, _, _) = future::join3(
+            server.accept(server_transport),
+            session.run(client_transport),
+            test,
+        )
+        .await;
+    }
+
+    #[test]
+    fn test_retry_io() {
+        let policy = RetryPolicy::default()
+            .idempotent("get")
+            .retryable_io(io::ErrorKind::ConnectionReset);
+
+        let reset = Error::from(io::Error::from(io::ErrorKind::ConnectionReset));
+
+        assert_eq!(reset.code, ErrorCode::Io(io::ErrorKind::ConnectionReset));
+        assert!(policy.should_retry("get", 0, &reset));
+
+        let invalid = Error::from(io::Error::from(io::ErrorKind::InvalidData));
+
+        assert!(!policy.should_retry("get", 0, &invalid));
+    }
+}
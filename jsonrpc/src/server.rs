@@ -0,0 +1,980 @@
+//! JSONRPC V2.0 server types
+
+use std::{collections::HashMap, future::Future, io, sync::Arc};
+
+use bytes::Bytes;
+use futures::{
+    channel::mpsc::{channel, Sender},
+    future::{self, BoxFuture, Either},
+    stream::FuturesUnordered,
+    FutureExt, SinkExt, StreamExt,
+};
+use librpc::{buffer::BufferPool, transport::Transport};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    cancel::CancellationToken,
+    codec::{Codec, Json, RPCCodec},
+    extensions::Extensions,
+    object::{Error, ErrorCode, Protocol, Request, RequestV1, Version},
+    result::{RPCError, RPCResult},
+};
+
+#[cfg(feature = "openrpc")]
+use crate::openrpc::{Registry, DISCOVER};
+#[cfg(feature = "validation")]
+use crate::validation::{invalid_params, Schema, SchemaError};
+
+/// Method name of the cancel notification, `params` is [`CancelParams`].
+pub const CANCEL_REQUEST: &str = "$/cancelRequest";
+
+/// Params of [`CANCEL_REQUEST`] notification.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct CancelParams {
+    /// Id of the request to cancel.
+    pub id: u64,
+}
+
+/// Context passed to server method handlers.
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// Request id, `None` for notification.
+    pub id: Option<u64>,
+    /// Fired when the client cancels this request or disconnects.
+    pub cancellation: CancellationToken,
+    /// Client of the connection carrying the call.
+    pub peer: Peer,
+    /// Extensions of the connection or HTTP request carrying the call, e.g. the
+    /// [`PeerAddr`](crate::http::server::PeerAddr) of HTTP clients.
+    pub extensions: Extensions,
+}
+
+impl Context {
+    fn new(id: Option<u64>, peer: Peer, extensions: Extensions) -> Self {
+        Self {
+            id,
+            cancellation: CancellationToken::new(),
+            peer,
+            extensions,
+        }
+    }
+}
+
+/// Handle pushing notifications to the client of one connection, e.g. subscription events.
+///
+/// Handlers keep a clone of [`Context::peer`] to notify the client after returning.
+/// Notifications wait in a queue of [`Server::notification_queue`] frames, plus one per clone.
+#[derive(Debug, Clone, Default)]
+pub struct Peer {
+    sender: Option<Sender<(String, Value)>>,
+}
+
+impl Peer {
+    /// Send notification `method` with `params` to the client, waiting while the queue of
+    /// the connection is full.
+    ///
+    /// Fails with [`ErrorCode::ConnectionLost`] once the connection is gone, or if the call
+    /// has no connection, e.g. handled by [`Server::respond`].
+    pub async fn notify<P>(&mut self, method: &str, params: P) -> RPCResult<()>
+    where
+        P: Serialize,
+    {
+        let params = serde_json::to_value(params)?;
+
+        let sender = self.sender.as_mut().ok_or_else(Error::connection_lost)?;
+
+        sender
+            .send((method.to_owned(), params))
+            .await
+            .map_err(|_| Error::connection_lost())
+    }
+
+    /// Send notification `method` with `params` to the client without waiting.
+    ///
+    /// Fails like [`Peer::notify`], or with [`ErrorCode::InternalError`] if the queue of the
+    /// connection is full, e.g. the client stopped reading.
+    pub fn try_notify<P>(&mut self, method: &str, params: P) -> RPCResult<()>
+    where
+        P: Serialize,
+    {
+        let params = serde_json::to_value(params)?;
+
+        let sender = self.sender.as_mut().ok_or_else(Error::connection_lost)?;
+
+        sender
+            .try_send((method.to_owned(), params))
+            .map_err(|err| match err.is_full() {
+                true => Error {
+                    code: ErrorCode::InternalError,
+                    message: "Notification queue full".to_owned(),
+                    data: None,
+                },
+                false => Error::connection_lost(),
+            })
+    }
+}
+
+/// Protocol generations accepted by [`Server`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProtocolMode {
+    /// JSON-RPC 1.0 only.
+    V1,
+    /// JSON-RPC 2.0 only.
+    #[default]
+    V2,
+    /// Detect per request, a request with `jsonrpc` member is 2.0, otherwise 1.0.
+    /// Each request is answered in its own generation.
+    Auto,
+}
+
+type Handler<V> = Arc<dyn Fn(Context, V) -> BoxFuture<'static, RPCResult<V>> + Send + Sync>;
+
+/// Default [`Server::notification_queue`].
+pub const NOTIFICATION_QUEUE: usize = 64;
+
+/// JSONRPC V2.0 server, routing requests to registered method handlers.
+#[derive(Clone)]
+pub struct Server<C: RPCCodec = Json> {
+    handlers: HashMap<String, Handler<C::Value>>,
+    buffers: BufferPool,
+    codec: C,
+    mode: ProtocolMode,
+    notification_queue: usize,
+    #[cfg(feature = "openrpc")]
+    registry: Registry,
+    #[cfg(feature = "validation")]
+    schemas: HashMap<String, Schema>,
+}
+
+impl<C> Default for Server<C>
+where
+    C: RPCCodec + Default,
+{
+    fn default() -> Self {
+        Self::with_codec(C::default())
+    }
+}
+
+impl Server {
+    /// Create new server without any method handler.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<C> Server<C>
+where
+    C: RPCCodec,
+{
+    /// Create new server without any method handler, encoding objects with `codec`.
+    pub fn with_codec(codec: C) -> Self {
+        Self {
+            handlers: Default::default(),
+            buffers: Default::default(),
+            codec,
+            mode: Default::default(),
+            notification_queue: NOTIFICATION_QUEUE,
+            #[cfg(feature = "openrpc")]
+            registry: Default::default(),
+            #[cfg(feature = "validation")]
+            schemas: Default::default(),
+        }
+    }
+
+    /// Codec encoding objects of this server.
+    pub(crate) fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Set accepted protocol generations, default is [`ProtocolMode::V2`].
+    pub fn mode(&mut self, mode: ProtocolMode) -> &mut Self {
+        self.mode = mode;
+
+        self
+    }
+
+    /// Set the number of [`Peer`] notifications waiting to be sent per connection, default
+    /// is [`NOTIFICATION_QUEUE`].
+    pub fn notification_queue(&mut self, size: usize) -> &mut Self {
+        self.notification_queue = size;
+
+        self
+    }
+
+    /// Register `handler` for `method`, replacing any previous one.
+    ///
+    /// Params and result are decoded and encoded directly by the server codec. A handler
+    /// returning [`RPCCodec::Value`], e.g. from [`Client::call_raw`](crate::client::Client::call_raw),
+    /// has its result forwarded as is.
+    pub fn handle<P, R, F, Fut>(&mut self, method: &str, handler: F) -> &mut Self
+    where
+        for<'b> P: Deserialize<'b> + Send + 'static,
+        R: Serialize + 'static,
+        F: Fn(Context, P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = RPCResult<R>> + Send + 'static,
+    {
+        let codec = self.codec.clone();
+
+        let handler =
+            move |context: Context, params: C::Value| match codec.decode_value::<P>(params) {
+                Ok(params) => {
+                    let codec = codec.clone();
+
+                    handler(context, params)
+                        .map(move |result| Ok(codec.encode_value(&result?)?))
+                        .boxed()
+                }
+                Err(err) => future::ready(Err(Error {
+                    code: ErrorCode::InvalidParams,
+                    message: format!("Invalid params: {}", err),
+                    data: None,
+                }))
+                .boxed(),
+            };
+
+        self.handlers.insert(method.to_owned(), Arc::new(handler));
+
+        #[cfg(feature = "openrpc")]
+        self.registry.remove(method);
+
+        self
+    }
+
+    /// Register `handler` like [`Server::handle`], describing its params and result
+    /// in the [OpenRPC document](Server::openrpc).
+    #[cfg(feature = "openrpc")]
+    pub fn handle_with_schema<P, R, F, Fut>(&mut self, method: &str, handler: F) -> &mut Self
+    where
+        for<'b> P: Deserialize<'b> + schemars::JsonSchema + Send + 'static,
+        R: Serialize + schemars::JsonSchema + 'static,
+        F: Fn(Context, P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = RPCResult<R>> + Send + 'static,
+    {
+        self.handle(method, handler);
+
+        self.registry.register::<P, R>(method);
+
+        self
+    }
+
+    /// Register `handler` like [`Server::handle_with_schema`], validating params against
+    /// the schema derived from `P` before calling it.
+    #[cfg(feature = "validation")]
+    pub fn handle_validated<P, R, F, Fut>(&mut self, method: &str, handler: F) -> &mut Self
+    where
+        for<'b> P: Deserialize<'b> + schemars::JsonSchema + Send + 'static,
+        R: Serialize + schemars::JsonSchema + 'static,
+        F: Fn(Context, P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = RPCResult<R>> + Send + 'static,
+    {
+        self.handle_with_schema(method, handler);
+
+        let schema = self
+            .registry
+            .params_schema(method)
+            .expect("registered schema");
+
+        self.validate(method, &schema)
+            .expect("schema derived from params type")
+    }
+
+    /// Validate the params of `method` against JSON `schema` before calling its handler,
+    /// replacing any previous schema. The schema is kept when the handler is replaced.
+    ///
+    /// Fails if `schema` is not a valid JSON Schema, the previous schema is kept then.
+    #[cfg(feature = "validation")]
+    pub fn validate(&mut self, method: &str, schema: &Value) -> Result<&mut Self, SchemaError> {
+        let schema = Schema::compile(schema).map_err(|message| SchemaError {
+            method: method.to_owned(),
+            message,
+        })?;
+
+        self.schemas.insert(method.to_owned(), schema);
+
+        Ok(self)
+    }
+
+    /// Set the `info` of the OpenRPC document.
+    #[cfg(feature = "openrpc")]
+    pub fn info(&mut self, title: &str, version: &str) -> &mut Self {
+        self.registry.info = crate::openrpc::Info {
+            title: title.to_owned(),
+            version: version.to_owned(),
+        };
+
+        self
+    }
+
+    /// Returns the OpenRPC document describing all registered methods.
+    ///
+    /// The document is also returned by the built-in [`DISCOVER`] method, unless a
+    /// handler is registered with that name.
+    #[cfg(feature = "openrpc")]
+    pub fn openrpc(&self) -> Value {
+        self.registry.document(self.handlers.keys())
+    }
+
+    /// Serve one connection until the peer disconnects.
+    ///
+    /// Requests are handled concurrently. A [`CANCEL_REQUEST`] notification fires the
+    /// cancellation token of the matching request, which is then answered with
+    /// [`ErrorCode::RequestCancelled`]. All pending tokens fire when the connection drops.
+    ///
+    /// A request reusing the id of a request still in flight is rejected with
+    /// [`ErrorCode::InvalidRequest`].
+    pub async fn accept<T>(&self, transport: T) -> RPCResult<()>
+    where
+        T: Transport<Bytes>,
+    {
+        self.accept_with(transport, Extensions::new()).await
+    }
+
+    /// Serve one connection like [`Server::accept`], passing `extensions` to the handler
+    /// [`Context`] of each call, e.g. the peer identity of a TLS connection.
+    pub async fn accept_with<T>(&self, transport: T, extensions: Extensions) -> RPCResult<()>
+    where
+        T: Transport<Bytes>,
+    {
+        let (mut output, input) = transport.split();
+
+        let mut input = input.fuse();
+
+        let mut tokens = HashMap::<u64, CancellationToken>::new();
+
+        let mut calls = FuturesUnordered::new();
+
+        let (sender, notifications) = channel(self.notification_queue);
+
+        let peer = Peer {
+            sender: Some(sender),
+        };
+
+        let mut notifications = notifications.fuse();
+
+        let result = loop {
+            futures::select! {
+                frame = input.next() => match frame {
+                    Some(Ok(frame)) => match self.parse(&frame) {
+                        Ok((_, request)) if request.method == CANCEL_REQUEST => {
+                            self.cancel(&tokens, request.params);
+                        }
+                        Ok((protocol, request)) => match request.id {
+                            Some(id) if tokens.contains_key(&id) => {
+                                let err = Error {
+                                    code: ErrorCode::InvalidRequest,
+                                    message: format!("Duplicate request id {}", id),
+                                    data: None,
+                                };
+
+                                let frame = error_frame(
+                                    &self.buffers,
+                                    &self.codec,
+                                    protocol,
+                                    Some(id),
+                                    err,
+                                );
+
+                                // Keep the token of the request in flight.
+                                calls.push(future::ready((None, Some(frame))).boxed());
+                            }
+                            id => {
+                                let context = Context::new(id, peer.clone(), extensions.clone());
+
+                                if let Some(id) = id {
+                                    tokens.insert(id, context.cancellation.clone());
+                                }
+
+                                calls.push(self.call(protocol, request, context));
+                            }
+                        },
+                        Err((protocol, err)) => {
+                            let frame =
+                                error_frame(&self.buffers, &self.codec, protocol, None, err);
+
+                            calls.push(future::ready((None, Some(frame))).boxed());
+                        }
+                    },
+                    Some(Err(err)) => break Err(err.into()),
+                    None => break Ok(()),
+                },
+                (id, frame) = calls.select_next_some() => {
+                    if let Some(id) = id {
+                        tokens.remove(&id);
+                    }
+
+                    if let Some(frame) = frame {
+                        if let Err(err) = output.send(frame).await {
+                            break Err(err.into());
+                        }
+                    }
+                },
+                (method, params) = notifications.select_next_some() => {
+                    let frame = self.notification_frame(&method, params);
+
+                    if let Err(err) = output.send(frame).await {
+                        break Err(err.into());
+                    }
+                },
+            }
+        };
+
+        // The connection is gone, notify all handlers still running.
+        for token in tokens.values() {
+            token.cancel();
+        }
+
+        result
+    }
+
+    /// Handle one request `frame` outside of any connection, e.g. from an HTTP body.
+    ///
+    /// Returns the response frame, `None` for notifications. [`CANCEL_REQUEST`] is ignored,
+    /// the request is cancelled by dropping the returned future.
+    pub async fn respond(&self, frame: &[u8]) -> Option<Bytes> {
+        self.respond_with(frame, &Extensions::new()).await
+    }
+
+    /// Handle one request `frame` like [`Server::respond`], passing `extensions` to the
+    /// handler [`Context`].
+    pub async fn respond_with(&self, frame: &[u8], extensions: &Extensions) -> Option<Bytes> {
+        match self.parse(frame) {
+            Ok((_, request)) if request.method == CANCEL_REQUEST => None,
+            Ok((protocol, request)) => {
+                let context = Context::new(request.id, Peer::default(), extensions.clone());
+
+                let (_, frame) = self.call(protocol, request, context).await;
+
+                frame
+            }
+            Err((protocol, err)) => {
+                Some(error_frame(&self.buffers, &self.codec, protocol, None, err))
+            }
+        }
+    }
+
+    /// Parse request `frame`, returns the detected protocol generation with the request,
+    /// or with the error on failure.
+    ///
+    /// Params are decoded as [`RPCCodec::Value`], not converted through JSON.
+    #[allow(clippy::type_complexity)]
+    fn parse(
+        &self,
+        frame: &[u8],
+    ) -> Result<(Protocol, Request<String, Option<C::Value>>), (Protocol, RPCError)> {
+        let protocol = match self.mode {
+            ProtocolMode::V1 => Protocol::V1,
+            ProtocolMode::V2 => Protocol::V2,
+            ProtocolMode::Auto => match self.codec.decode::<Generation>(frame) {
+                Ok(Generation { jsonrpc: Some(_) }) => Protocol::V2,
+                Ok(Generation { jsonrpc: None }) => Protocol::V1,
+                Err(err) => return Err(self.parse_error(Protocol::V2, frame, err)),
+            },
+        };
+
+        let request = match protocol {
+            Protocol::V1 => self
+                .codec
+                .decode::<RequestV1<String, Option<C::Value>>>(frame)
+                .map(|request| Request {
+                    id: request.id,
+                    jsonrpc: Version,
+                    method: request.method,
+                    params: request.params,
+                }),
+            Protocol::V2 => self.codec.decode(frame),
+        };
+
+        request
+            .map(|request| (protocol, request))
+            .map_err(|err| self.parse_error(protocol, frame, err))
+    }
+
+    /// Returns the error of the request `frame` failed to decode with `err`.
+    fn parse_error(
+        &self,
+        protocol: Protocol,
+        frame: &[u8],
+        err: io::Error,
+    ) -> (Protocol, RPCError) {
+        // Tell the malformed frames from the well-formed non-request ones.
+        let (code, message) = match self.codec.decode::<IgnoredAny>(frame) {
+            Ok(_) => (ErrorCode::InvalidRequest, "Invalid request"),
+            Err(_) => (ErrorCode::ParseError, "Parse error"),
+        };
+
+        (
+            protocol,
+            Error {
+                code,
+                message: format!("{}: {}", message, err),
+                data: None,
+            },
+        )
+    }
+
+    /// Assembly the notification frame pushed by a [`Peer`].
+    fn notification_frame(&self, method: &str, params: Value) -> Bytes {
+        let notification = match self.mode {
+            ProtocolMode::V1 => json!({
+                "id": null,
+                "method": method,
+                "params": params,
+            }),
+            _ => json!({
+                "jsonrpc": Version,
+                "method": method,
+                "params": params,
+            }),
+        };
+
+        self.buffers
+            .frame(|buf| self.codec.encode(&notification, buf))
+            .expect("Inner error, assembly json notification")
+    }
+
+    fn cancel(&self, tokens: &HashMap<u64, CancellationToken>, params: Option<C::Value>) {
+        // JSON-RPC 1.0 params are wrapped in an array.
+        let params = params.and_then(|params| {
+            self.codec
+                .decode_value::<CancelParams>(params.clone())
+                .or_else(|_| {
+                    self.codec
+                        .decode_value::<(CancelParams,)>(params)
+                        .map(|(params,)| params)
+                })
+                .ok()
+        });
+
+        match params {
+            Some(params) => {
+                if let Some(token) = tokens.get(&params.id) {
+                    log::debug!("cancel request {}", params.id);
+                    token.cancel();
+                }
+            }
+            None => log::warn!("drop invalid {} notification", CANCEL_REQUEST),
+        }
+    }
+
+    /// Invoke the method handler, returns request id and response frame.
+    fn call(
+        &self,
+        protocol: Protocol,
+        request: Request<String, Option<C::Value>>,
+        context: Context,
+    ) -> BoxFuture<'static, (Option<u64>, Option<Bytes>)> {
+        let id = context.id;
+
+        let cancellation = context.cancellation.clone();
+
+        let handler = match self.handlers.get(&request.method) {
+            Some(handler) => handler.clone(),
+            #[cfg(feature = "openrpc")]
+            None if request.method == DISCOVER => {
+                let document = self
+                    .codec
+                    .encode_value(&self.openrpc())
+                    .map_err(RPCError::from);
+
+                Arc::new(move |_, _| future::ready(document.clone()).boxed())
+            }
+            None => {
+                let err = Error {
+                    code: ErrorCode::MethodNotFound,
+                    message: format!("Method not found: {}", request.method),
+                    data: None,
+                };
+
+                return future::ready((
+                    id,
+                    id.map(|_| error_frame(&self.buffers, &self.codec, protocol, id, err)),
+                ))
+                .boxed();
+            }
+        };
+
+        let params = match request.params {
+            Some(params) => params,
+            None => self
+                .codec
+                .encode_value(&())
+                .expect("Inner error, assembly null params"),
+        };
+
+        #[cfg(feature = "validation")]
+        let handler = match self.schemas.get(&request.method).map(|schema| {
+            // Params of binary codecs may not map to JSON, e.g. maps keyed by arrays.
+            let params = serde_json::to_value(&params).map_err(|err| Error {
+                code: ErrorCode::InvalidParams,
+                message: format!("Invalid params: {}", err),
+                data: None,
+            })?;
+
+            schema.validate(&params).map_err(invalid_params)
+        }) {
+            Some(Err(err)) => Arc::new(move |_, _| future::ready(Err(err.clone())).boxed()),
+            _ => handler,
+        };
+
+        let call = handler(context, params);
+
+        let buffers = self.buffers.clone();
+        let codec = self.codec.clone();
+
+        async move {
+            // Check cancellation first, a cancelled request never answers with result.
+            let result = match future::select(cancellation.cancelled(), call).await {
+                Either::Left(_) => Err(Error {
+                    code: ErrorCode::RequestCancelled,
+                    message: "Request cancelled".to_owned(),
+                    data: None,
+                }),
+                Either::Right((result, _)) => result,
+            };
+
+            // Notifications never get a response, even on error.
+            let id = match id {
+                Some(id) => id,
+                None => {
+                    if let Err(err) = result {
+                        log::warn!("notification handler error: {}", err);
+                    }
+
+                    return (None, None);
+                }
+            };
+
+            let frame = match result {
+                Ok(result) => buffers
+                    .frame(|buf| codec.encode_result(protocol, id, &result, buf))
+                    .expect("Inner error, assembly json response"),
+                Err(err) => error_frame(&buffers, &codec, protocol, Some(id), err),
+            };
+
+            (Some(id), Some(frame))
+        }
+        .boxed()
+    }
+}
+
+/// Probe of the protocol generation of a request, see [`ProtocolMode::Auto`].
+#[derive(Deserialize)]
+struct Generation {
+    jsonrpc: Option<IgnoredAny>,
+}
+
+/// Assembly error response frame, `id` is `null` if the request id can't be detected.
+fn error_frame<C: Codec>(
+    buffers: &BufferPool,
+    codec: &C,
+    protocol: Protocol,
+    id: Option<u64>,
+    err: RPCError,
+) -> Bytes {
+    let response = match protocol {
+        Protocol::V1 => json!({
+            "id": id,
+            "result": null,
+            "error": err.to_remote(),
+        }),
+        Protocol::V2 => json!({
+            "id": id,
+            "jsonrpc": Version,
+            "error": err.to_remote(),
+        }),
+    };
+
+    buffers
+        .frame(|buf| codec.encode(&response, buf))
+        .expect("Inner error, assembly json response")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytes::Bytes;
+    use futures::{channel::mpsc::Receiver, future, SinkExt, StreamExt};
+    use librpc::transport::memory::pipe;
+    use serde_json::{json, Value};
+
+    use crate::{object::ErrorCode, result::RPCResult};
+
+    use super::{Context, ProtocolMode, Server, CANCEL_REQUEST};
+
+    async fn recv(output: &mut Receiver<Bytes>) -> Value {
+        serde_json::from_slice(&output.next().await.expect("response frame")).unwrap()
+    }
+
+    #[futures_test::test]
+    async fn test_call() {
+        let mut server = Server::new();
+
+        server.handle("echo", |_, params: String| async move { Ok(params) });
+
+        let (transport, mut input, mut output) = pipe(10);
+
+        let client = async move {
+            input
+                .send(
+                    json!({"jsonrpc":"2.0","id":1,"method":"echo","params":"hello"})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                recv(&mut output).await,
+                json!({"jsonrpc":"2.0","id":1,"result":"hello"})
+            );
+
+            input
+                .send(
+                    json!({"jsonrpc":"2.0","id":2,"method":"hello","params":[]})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(recv(&mut output).await["error"]["code"], json!(-32601));
+        };
+
+        let (result, _) = future::join(server.accept(transport), client).await;
+
+        result.expect("server exit");
+    }
+
+    #[futures_test::test]
+    async fn test_auto_protocol() {
+        let mut server = Server::new();
+
+        server
+            .mode(ProtocolMode::Auto)
+            .handle("echo", |_, params: Vec<String>| async move { Ok(params) });
+
+        let (transport, mut input, mut output) = pipe(10);
+
+        let client = async move {
+            // 1.0 notification is never answered.
+            input
+                .send(
+                    json!({"id":null,"method":"echo","params":["ignored"]})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            input
+                .send(
+                    json!({"id":1,"method":"echo","params":["hello"]})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                recv(&mut output).await,
+                json!({"id":1,"result":["hello"],"error":null})
+            );
+
+            input
+                .send(
+                    json!({"jsonrpc":"2.0","id":2,"method":"echo","params":["world"]})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                recv(&mut output).await,
+                json!({"jsonrpc":"2.0","id":2,"result":["world"]})
+            );
+
+            input
+                .send(
+                    json!({"id":3,"method":"hello","params":[]})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            let response = recv(&mut output).await;
+
+            assert_eq!(response["result"], Value::Null);
+            assert_eq!(response["error"]["code"], json!(-32601));
+            assert!(response.get("jsonrpc").is_none());
+        };
+
+        let (result, _) = future::join(server.accept(transport), client).await;
+
+        result.expect("server exit");
+    }
+
+    #[futures_test::test]
+    async fn test_cancel_request() {
+        let mut server = Server::new();
+
+        server.handle("wait", |context: Context, _: Value| async move {
+            context.cancellation.cancelled().await;
+
+            RPCResult::<()>::Ok(())
+        });
+
+        let (transport, mut input, mut output) = pipe(10);
+
+        let client = async move {
+            let wait = json!({"jsonrpc":"2.0","id":7,"method":"wait"}).to_string();
+
+            let cancel =
+                json!({"jsonrpc":"2.0","method":CANCEL_REQUEST,"params":{"id":7}}).to_string();
+
+            // The duplicate id is rejected, the first request keeps its token.
+            for frame in [&wait, &wait, &cancel] {
+                input.send(frame.clone().into()).await.unwrap();
+            }
+
+            let mut codes = vec![];
+
+            for _ in 0..2 {
+                let response = recv(&mut output).await;
+
+                assert_eq!(response["id"], json!(7));
+
+                codes.push(
+                    serde_json::from_value::<ErrorCode>(response["error"]["code"].clone()).unwrap(),
+                );
+            }
+
+            codes.sort_by_key(ErrorCode::code);
+
+            assert_eq!(
+                codes,
+                vec![ErrorCode::RequestCancelled, ErrorCode::InvalidRequest]
+            );
+
+            // The id is free again once answered.
+            for frame in [&wait, &cancel] {
+                input.send(frame.clone().into()).await.unwrap();
+            }
+
+            let response = recv(&mut output).await;
+
+            assert_eq!(
+                serde_json::from_value::<ErrorCode>(response["error"]["code"].clone()).unwrap(),
+                ErrorCode::RequestCancelled
+            );
+        };
+
+        let (result, _) = future::join(server.accept(transport), client).await;
+
+        result.expect("server exit");
+    }
+
+    #[futures_test::test]
+    async fn test_disconnect_cancel() {
+        let tokens = Arc::new(Mutex::new(vec![]));
+
+        let mut server = Server::new();
+
+        let handler_tokens = tokens.clone();
+
+        server.handle("wait", move |context: Context, _: Value| {
+            handler_tokens
+                .lock()
+                .unwrap()
+                .push(context.cancellation.clone());
+
+            future::pending::<RPCResult<()>>()
+        });
+
+        let (transport, mut input, _output) = pipe(10);
+
+        let client = async move {
+            input
+                .send(
+                    json!({"jsonrpc":"2.0","id":1,"method":"wait"})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            // Drop input half, the server sees the disconnection.
+        };
+
+        let (result, _) = future::join(server.accept(transport), client).await;
+
+        result.expect("server exit");
+
+        let tokens = tokens.lock().unwrap();
+
+        assert_eq!(tokens.len(), 1);
+        assert!(tokens[0].is_cancelled());
+    }
+
+    #[futures_test::test]
+    async fn test_notification_queue() {
+        let mut server = Server::new();
+
+        server.notification_queue(2);
+
+        server.handle("subscribe", |mut context: Context, _: Value| {
+            // Nothing is sent before the handler returns, the queue fills up.
+            let queued = (0..10)
+                .take_while(|i| context.peer.try_notify("tick", [i]).is_ok())
+                .count();
+
+            async move {
+                // Waits for the server to send the queued notifications.
+                context.peer.notify("tick", [queued]).await?;
+
+                Ok(queued)
+            }
+        });
+
+        let (transport, mut input, mut output) = pipe(10);
+
+        let client = async move {
+            input
+                .send(
+                    json!({"jsonrpc":"2.0","id":1,"method":"subscribe"})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            let mut ticks = vec![];
+
+            let mut queued = None;
+
+            // The response may overtake the notifications still queued.
+            while queued.is_none_or(|queued| ticks.len() <= queued) {
+                let frame = recv(&mut output).await;
+
+                match frame["method"].as_str() {
+                    Some("tick") => ticks.push(frame["params"][0].clone()),
+                    _ => queued = Some(frame["result"].as_u64().unwrap() as usize),
+                }
+            }
+
+            let queued = queued.unwrap();
+
+            // One slot per peer clone on top of the queue size.
+            assert!((2..10).contains(&queued));
+            assert_eq!(ticks.len(), queued + 1);
+            assert_eq!(ticks[queued], json!(queued));
+        };
+
+        let (result, _) = future::join(server.accept(transport), client).await;
+
+        result.expect("server exit");
+    }
+}
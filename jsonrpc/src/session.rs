@@ -0,0 +1,498 @@
+//! JSONRPC client connection session
+
+use std::{
+    collections::{HashSet, VecDeque},
+    io,
+};
+
+use bytes::Bytes;
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    SinkExt, StreamExt,
+};
+use librpc::transport::Transport;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    client::{Output, Responder},
+    codec::{Json, RPCCodec},
+    object::Error,
+    result::{RPCError, RPCResult},
+};
+
+/// Notification pushed by the server, see [`Session::notifications`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Notification {
+    /// Method name.
+    pub method: String,
+    /// Params, `null` if omitted.
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// Calls answered by the client transport itself, e.g. on an HTTP request failure.
+///
+/// Carried by an [`io::Error`] of the transport stream, see [`Failed::of`]. Unlike other
+/// stream errors it doesn't break the [`Session`], which answers the calls with `error`.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("calls {ids:?} failed, {error}")]
+pub struct Failed {
+    /// Ids of the failed calls.
+    pub ids: Vec<u64>,
+    /// Error answering the calls.
+    pub error: RPCError,
+}
+
+impl Failed {
+    /// Returns the failed calls carried by `err`, if any.
+    pub fn of(err: &io::Error) -> Option<&Failed> {
+        err.get_ref().and_then(|err| err.downcast_ref())
+    }
+}
+
+impl From<Failed> for io::Error {
+    fn from(failed: Failed) -> Self {
+        io::Error::other(failed)
+    }
+}
+
+/// Pump [`Client`](crate::client::Client) requests over a transport and complete
+/// the pending calls with the received responses.
+pub struct Session<C = Json> {
+    codec: C,
+    output: Output,
+    responder: Responder,
+    /// Ids of calls sent over the current transport and not answered yet.
+    pending: HashSet<u64>,
+    /// Size of `pending` triggering the removal of calls nobody waits for anymore.
+    prune_at: usize,
+    /// Frames taken from the client while disconnected, sent first on next run.
+    queue: VecDeque<(Option<u64>, Bytes)>,
+    notifications: Option<UnboundedSender<Notification>>,
+}
+
+impl Session {
+    /// Create new session with the [`Output`] and [`Responder`] returned by
+    /// [`Client::new`](crate::client::Client::new).
+    pub fn new(output: Output, responder: Responder) -> Self {
+        Self::with_codec(output, responder, Json)
+    }
+}
+
+impl<C> Session<C>
+where
+    C: RPCCodec,
+{
+    /// Create new session decoding responses with `codec`, which must match the client one.
+    pub fn with_codec(output: Output, responder: Responder, codec: C) -> Self {
+        Self {
+            codec,
+            output,
+            responder,
+            pending: Default::default(),
+            prune_at: PRUNE_AT,
+            queue: Default::default(),
+            notifications: None,
+        }
+    }
+
+    /// Returns the receiver of server-pushed notifications, replacing any previous one.
+    ///
+    /// Notifications are dropped while no receiver is alive.
+    pub fn notifications(&mut self) -> UnboundedReceiver<Notification> {
+        let (sender, receiver) = unbounded();
+
+        self.notifications = Some(sender);
+
+        receiver
+    }
+
+    /// Run this session over `transport`.
+    ///
+    /// Returns `Ok(())` when all [`Client`](crate::client::Client) instances are dropped,
+    /// after closing `transport`.
+    /// Returns an error if the transport is broken, in-flight calls fail with
+    /// [`ErrorCode::ConnectionLost`](crate::object::ErrorCode::ConnectionLost) in that case.
+    pub async fn run<T>(&mut self, transport: T) -> RPCResult<()>
+    where
+        T: Transport<Bytes>,
+    {
+        let result = self.pump(transport).await;
+
+        if result.is_err() {
+            self.fail_pending();
+        }
+
+        result
+    }
+
+    /// Take the next outbound frame, `None` if all clients are dropped.
+    pub(crate) async fn next(&mut self) -> Option<(Option<u64>, Bytes)> {
+        self.output.next().await
+    }
+
+    /// Keep `frame` to be sent first on next run.
+    pub(crate) fn queue(&mut self, frame: (Option<u64>, Bytes)) {
+        self.queue.push_back(frame);
+    }
+
+    /// Complete the call `id` with [`ErrorCode::ConnectionLost`](crate::object::ErrorCode::ConnectionLost) error.
+    pub(crate) fn fail(&self, id: u64) {
+        self.responder.complete(id, Err(Error::connection_lost()));
+    }
+
+    async fn pump<T>(&mut self, transport: T) -> RPCResult<()>
+    where
+        T: Transport<Bytes>,
+    {
+        let (mut sink, stream) = transport.split();
+
+        while let Some((id, frame)) = self.queue.pop_front() {
+            self.sent(id);
+
+            sink.send(frame).await?;
+        }
+
+        let mut stream = stream.fuse();
+
+        loop {
+            futures::select! {
+                frame = self.output.next() => match frame {
+                    Some((id, frame)) => {
+                        self.sent(id);
+
+                        sink.send(frame).await?;
+                    }
+                    None => {
+                        sink.close().await?;
+
+                        return Ok(());
+                    }
+                },
+                frame = stream.next() => match frame {
+                    Some(Ok(frame)) => self.complete(frame),
+                    Some(Err(err)) => match Failed::of(&err) {
+                        Some(failed) => self.failed(failed),
+                        None => return Err(err.into()),
+                    },
+                    None => return Err(Error::connection_lost()),
+                },
+            }
+        }
+    }
+
+    /// Track call `id` sent over the current transport.
+    ///
+    /// Calls dropped or timed out without answer are removed once `pending` doubled.
+    fn sent(&mut self, id: Option<u64>) {
+        let Some(id) = id else {
+            return;
+        };
+
+        self.pending.insert(id);
+
+        if self.pending.len() >= self.prune_at {
+            self.responder.retain_pending(&mut self.pending);
+
+            self.prune_at = (self.pending.len() * 2).max(PRUNE_AT);
+        }
+    }
+
+    fn complete(&mut self, frame: Bytes) {
+        let (id, result) = match self.codec.decode_response(&frame) {
+            Ok(response) => response,
+            Err(err) => {
+                match self.codec.decode::<Notification>(&frame) {
+                    Ok(notification) => self.notify(notification),
+                    Err(_) => log::warn!("drop invalid response frame: {}", err),
+                }
+
+                return;
+            }
+        };
+
+        self.pending.remove(&id);
+
+        self.responder.complete(id, result);
+    }
+
+    fn failed(&mut self, failed: &Failed) {
+        for id in &failed.ids {
+            self.pending.remove(id);
+
+            self.responder.complete(*id, Err(failed.error.clone()));
+        }
+    }
+
+    fn notify(&mut self, notification: Notification) {
+        let sent = match &self.notifications {
+            Some(sender) => sender.unbounded_send(notification).is_ok(),
+            None => false,
+        };
+
+        if !sent {
+            log::debug!("drop notification, no receiver");
+
+            self.notifications = None;
+        }
+    }
+
+    fn fail_pending(&mut self) {
+        for id in self.pending.drain() {
+            self.responder.complete(id, Err(Error::connection_lost()));
+        }
+    }
+}
+
+/// Minimal size of [`Session::pending`] before pruning.
+const PRUNE_AT: usize = 64;
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_timer_rs::{hashed::Timeout, Timer};
+    use futures::{future, SinkExt, StreamExt};
+    use librpc::transport::{
+        memory::{pair, pipe},
+        record::{Recorder, Replay},
+    };
+    use serde_json::{json, Value};
+
+    use crate::{
+        client::Client,
+        object::{ErrorCode, Protocol},
+        server::{Context, ProtocolMode, Server},
+    };
+
+    use super::{Notification, Session};
+
+    #[futures_test::test]
+    async fn test_call_raw() {
+        let mut backend = Server::new();
+
+        backend.handle("get", |_: Context, _: ()| {
+            future::ready(Ok(json!({"name": "world", "list": [1, 2]})))
+        });
+
+        let (backend_client, backend_output, backend_responder) = Client::new(10);
+
+        // Proxy forwarding backend results without decoding them.
+        let mut proxy = Server::new();
+
+        proxy.handle("get", move |_: Context, _: ()| {
+            let mut client = backend_client.clone();
+
+            async move { client.call_raw::<_, Timeout>("get", (), None).await }
+        });
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let (backend_transport, backend_server_transport) = pair(10);
+        let (proxy_transport, proxy_server_transport) = pair(10);
+
+        let mut backend_session = Session::new(backend_output, backend_responder);
+        let mut session = Session::new(output, responder);
+
+        let test = async move {
+            let mut raw_client = client.clone();
+
+            let result = client
+                .call::<_, Value, Timeout>("get", (), None)
+                .await
+                .unwrap();
+
+            assert_eq!(result, json!({"name": "world", "list": [1, 2]}));
+
+            let result = raw_client
+                .call_raw::<_, Timeout>("get", (), None)
+                .await
+                .unwrap();
+
+            assert_eq!(result.get(), r#"{"list":[1,2],"name":"world"}"#);
+        };
+
+        let (_, _, _, _, _) = future::join5(
+            async move { backend.accept(backend_server_transport).await },
+            backend_session.run(backend_transport),
+            // Drop the proxy with its backend client once the connection is closed.
+            async move { proxy.accept(proxy_server_transport).await },
+            session.run(proxy_transport),
+            test,
+        )
+        .await;
+    }
+
+    #[futures_test::test]
+    async fn test_call_v1() {
+        let mut server = Server::new();
+
+        server
+            .mode(ProtocolMode::V1)
+            .handle("add", |_: Context, (lhs, rhs): (i64, i64)| {
+                future::ready(Ok(lhs + rhs))
+            });
+
+        let (client, output, responder) = Client::new(10);
+
+        let mut client = client.protocol(Protocol::V1);
+
+        let (client_transport, server_transport) = pair(10);
+
+        let mut session = Session::new(output, responder);
+
+        let test = async move {
+            let sum = client
+                .call::<_, i64, Timeout>("add", (1, 2), None)
+                .await
+                .unwrap();
+
+            assert_eq!(sum, 3);
+
+            let err = client
+                .call::<_, i64, Timeout>("sub", (1, 2), None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::MethodNotFound);
+
+            client.notification("add", (1, 2)).await.unwrap();
+        };
+
+        let (_, _, _) = future::join3(
+            server.accept(server_transport),
+            session.run(client_transport),
+            test,
+        )
+        .await;
+    }
+
+    #[futures_test::test]
+    async fn test_record_replay() {
+        let path = std::env::temp_dir().join(format!("librpc-replay-{}.jsonl", std::process::id()));
+
+        let mut server = Server::new();
+
+        server.handle("add", |_: Context, (lhs, rhs): (i64, i64)| {
+            future::ready(Ok(lhs + rhs))
+        });
+
+        let calls = |mut client: Client| async move {
+            let sum = client.call::<_, i64, Timeout>("add", (1, 2), None).await;
+
+            assert_eq!(sum.unwrap(), 3);
+
+            let err = client
+                .call::<_, i64, Timeout>("sub", (1, 2), None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::MethodNotFound);
+        };
+
+        // Record against the real server.
+        let (client, output, responder) = Client::new(10);
+
+        let (client_transport, server_transport) = pair(10);
+
+        let client_transport = Recorder::create(client_transport, &path).unwrap();
+
+        let mut session = Session::new(output, responder);
+
+        let (_, _, _) = future::join3(
+            server.accept(server_transport),
+            session.run(client_transport),
+            calls(client),
+        )
+        .await;
+
+        // Replay without server.
+        let (client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let (_, _) = future::join(session.run(Replay::open(&path).unwrap()), calls(client)).await;
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[futures_test::test]
+    async fn test_notifications() {
+        let (client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let mut notifications = session.notifications();
+
+        let (transport, mut input, _output) = pipe(10);
+
+        let test = async move {
+            input
+                .send(
+                    json!({"jsonrpc":"2.0","method":"tick","params":[1]})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                notifications.next().await,
+                Some(Notification {
+                    method: "tick".to_owned(),
+                    params: json!([1])
+                })
+            );
+
+            drop(client);
+        };
+
+        let (_, _) = future::join(session.run(transport), test).await;
+    }
+
+    #[futures_test::test]
+    async fn test_timed_out_calls() {
+        let (client, output, responder) = Client::new(100);
+
+        let mut session = Session::new(output, responder);
+
+        // Server never answering in time.
+        let (transport, mut input, _output) = pipe(200);
+
+        let test = async move {
+            for _ in 0..2 {
+                let calls = (0..100).map(|_| {
+                    let mut client = client.clone();
+
+                    async move {
+                        let timeout = Some(Timeout::new(Duration::from_millis(10)));
+
+                        client.call::<_, (), _>("sleep", (), timeout).await
+                    }
+                });
+
+                for result in future::join_all(calls).await {
+                    assert_eq!(result.unwrap_err().code, ErrorCode::Timeout);
+                }
+            }
+
+            // Late response to a timed out call is dropped.
+            input
+                .send(
+                    json!({"jsonrpc":"2.0","id":0,"result":null})
+                        .to_string()
+                        .into(),
+                )
+                .await
+                .unwrap();
+
+            drop(client);
+        };
+
+        let (_, _) = future::join(session.run(transport), test).await;
+
+        // The first 100 calls are forgotten while sending the next ones.
+        assert!(session.pending.len() <= 100);
+    }
+}
@@ -0,0 +1,401 @@
+//! TLS over any byte stream with rustls, e.g. under [`Lines`](crate::lines::Lines) or
+//! [`WebSocket`](crate::ws::WebSocket).
+//!
+//! Servers pass [`TlsServer::extensions`] to [`Server::accept_with`](crate::server::Server::accept_with),
+//! handlers then read the [`PeerCertificates`] of mutual TLS clients from
+//! [`Context::extensions`](crate::server::Context::extensions).
+
+use std::{io, sync::Arc};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{
+    rustls::{
+        crypto::{ring, CryptoProvider},
+        pki_types::ServerName,
+        server::WebPkiClientVerifier,
+        ClientConfig, ServerConfig,
+    },
+    TlsAcceptor, TlsConnector,
+};
+
+pub use tokio_rustls::{
+    client::TlsStream as ClientTlsStream,
+    rustls::{
+        self,
+        pki_types::{CertificateDer, PrivateKeyDer},
+        RootCertStore,
+    },
+    server::TlsStream as ServerTlsStream,
+};
+
+use crate::extensions::Extensions;
+
+/// Certificate chain presented by the TLS peer, end entity first.
+///
+/// Inserted in the handler [`Context::extensions`](crate::server::Context::extensions) of
+/// connections accepted with client authentication.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCertificates(pub Vec<CertificateDer<'static>>);
+
+impl PeerCertificates {
+    /// Returns the certificate identifying the peer.
+    pub fn end_entity(&self) -> Option<&CertificateDer<'static>> {
+        self.0.first()
+    }
+}
+
+/// TLS client, connector of [`ClientTlsStream`].
+#[derive(Clone)]
+pub struct TlsClient {
+    connector: TlsConnector,
+}
+
+impl TlsClient {
+    /// Client trusting server certificates issued by `roots`.
+    pub fn new(roots: RootCertStore) -> io::Result<Self> {
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        Ok(Self::from_config(Arc::new(config)))
+    }
+
+    /// Client trusting server certificates issued by `roots`, authenticated with certificate
+    /// `chain` and its private `key` for mutual TLS.
+    pub fn with_identity(
+        roots: RootCertStore,
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> io::Result<Self> {
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_root_certificates(roots)
+            .with_client_auth_cert(chain, key)
+            .map_err(io::Error::other)?;
+
+        Ok(Self::from_config(Arc::new(config)))
+    }
+
+    /// Client with custom rustls `config`.
+    pub fn from_config(config: Arc<ClientConfig>) -> Self {
+        Self {
+            connector: TlsConnector::from(config),
+        }
+    }
+
+    /// Returns the rustls config, e.g. to build a QUIC endpoint.
+    pub fn config(&self) -> Arc<ClientConfig> {
+        self.connector.config().clone()
+    }
+
+    /// Run the TLS handshake over `stream` with server `domain`, which the server
+    /// certificate must be valid for.
+    pub async fn connect<S>(&self, domain: &str, stream: S) -> io::Result<ClientTlsStream<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let domain = ServerName::try_from(domain.to_owned())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        self.connector.connect(domain, stream).await
+    }
+}
+
+/// TLS server, acceptor of [`ServerTlsStream`].
+#[derive(Clone)]
+pub struct TlsServer {
+    acceptor: TlsAcceptor,
+}
+
+impl TlsServer {
+    /// Server authenticated with certificate `chain` and its private `key`.
+    pub fn new(
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> io::Result<Self> {
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .map_err(io::Error::other)?;
+
+        Ok(Self::from_config(Arc::new(config)))
+    }
+
+    /// Server like [`TlsServer::new`] requiring clients to present a certificate issued
+    /// by `roots`.
+    pub fn with_client_auth(
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+        roots: RootCertStore,
+    ) -> io::Result<Self> {
+        let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider())
+            .build()
+            .map_err(io::Error::other)?;
+
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(chain, key)
+            .map_err(io::Error::other)?;
+
+        Ok(Self::from_config(Arc::new(config)))
+    }
+
+    /// Server with custom rustls `config`.
+    pub fn from_config(config: Arc<ServerConfig>) -> Self {
+        Self {
+            acceptor: TlsAcceptor::from(config),
+        }
+    }
+
+    /// Returns the rustls config, e.g. to build a QUIC endpoint.
+    pub fn config(&self) -> Arc<ServerConfig> {
+        self.acceptor.config().clone()
+    }
+
+    /// Run the TLS handshake over client connection `stream`.
+    pub async fn accept<S>(&self, stream: S) -> io::Result<ServerTlsStream<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.acceptor.accept(stream).await
+    }
+
+    /// Returns the connection extensions of `stream`, holding the [`PeerCertificates`]
+    /// of authenticated clients.
+    pub fn extensions<S>(stream: &ServerTlsStream<S>) -> Extensions {
+        let mut extensions = Extensions::new();
+
+        if let Some(chain) = stream.get_ref().1.peer_certificates() {
+            extensions.insert(PeerCertificates(
+                chain.iter().map(|cert| cert.clone().into_owned()).collect(),
+            ));
+        }
+
+        extensions
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use async_timer_rs::hashed::Timeout;
+    use futures::future;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, Issuer, KeyPair};
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[cfg(feature = "ws")]
+    use crate::ws::WebSocket;
+    use crate::{
+        client::Client,
+        lines::Lines,
+        server::{Context, Server},
+        session::Session,
+    };
+
+    use super::{
+        CertificateDer, PeerCertificates, PrivateKeyDer, RootCertStore, TlsClient, TlsServer,
+    };
+
+    /// Certificate chain and private key.
+    type Identity = (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>);
+
+    /// Self-signed CA issuing the test identities.
+    struct Ca {
+        cert: CertificateDer<'static>,
+        issuer: Issuer<'static, KeyPair>,
+    }
+
+    impl Ca {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(vec![]).unwrap();
+
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+
+            let key = KeyPair::generate().unwrap();
+
+            let cert = params.self_signed(&key).unwrap().der().clone();
+
+            Self {
+                cert,
+                issuer: Issuer::new(params, key),
+            }
+        }
+
+        fn roots(&self) -> RootCertStore {
+            let mut roots = RootCertStore::empty();
+
+            roots.add(self.cert.clone()).unwrap();
+
+            roots
+        }
+
+        fn issue(&self, name: &str) -> Identity {
+            let params = CertificateParams::new(vec![name.to_owned()]).unwrap();
+
+            let key = KeyPair::generate().unwrap();
+
+            let cert = params.signed_by(&key, &self.issuer).unwrap();
+
+            (vec![cert.der().clone()], key.into())
+        }
+    }
+
+    /// Serve `server` with `tls` on a local port, over WebSocket if `ws` else over lines.
+    async fn serve(server: Server, tls: TlsServer, ws: bool) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+
+                let server = server.clone();
+
+                let tls = tls.clone();
+
+                tokio::spawn(async move {
+                    let Ok(stream) = tls.accept(stream).await else {
+                        return;
+                    };
+
+                    let extensions = TlsServer::extensions(&stream);
+
+                    match ws {
+                        #[cfg(feature = "ws")]
+                        true => {
+                            let transport = WebSocket::accept(stream).await.unwrap();
+
+                            _ = server.accept_with(transport, extensions).await;
+                        }
+                        _ => _ = server.accept_with(Lines::new(stream), extensions).await,
+                    }
+                });
+            }
+        });
+
+        addr
+    }
+
+    fn whoami(server: &mut Server) {
+        server.handle("whoami", |context: Context, _: ()| {
+            let cert = context
+                .extensions
+                .get::<PeerCertificates>()
+                .and_then(|certs| certs.end_entity())
+                .map(|cert| cert.to_vec());
+
+            future::ready(Ok(cert))
+        });
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls() {
+        let ca = Ca::new();
+
+        let (chain, key) = ca.issue("localhost");
+
+        let (client_chain, client_key) = ca.issue("client");
+
+        let mut server = Server::new();
+
+        whoami(&mut server);
+
+        let tls = TlsServer::with_client_auth(chain, key, ca.roots()).unwrap();
+
+        let addr = serve(server, tls, false).await;
+
+        let tls = TlsClient::with_identity(ca.roots(), client_chain.clone(), client_key).unwrap();
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+
+        let stream = tls.connect("localhost", stream).await.unwrap();
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let test = async move {
+            let cert = client
+                .call::<_, Option<Vec<u8>>, Timeout>("whoami", (), None)
+                .await;
+
+            assert_eq!(cert.unwrap(), Some(client_chain[0].to_vec()));
+        };
+
+        let (_, _) = future::join(session.run(Lines::new(stream)), test).await;
+
+        // Anonymous clients are rejected by the server during the handshake.
+        let tls = TlsClient::new(ca.roots()).unwrap();
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+
+        let rejected = async {
+            let mut stream = tls.connect("localhost", stream).await?;
+
+            // TLS 1.3 clients learn about the rejection with the first read.
+            tokio::io::AsyncReadExt::read(&mut stream, &mut [0; 1]).await
+        };
+
+        assert!(rejected.await.is_err());
+
+        // Server certificates of unknown issuers are rejected by the client.
+        let tls = TlsClient::new(Ca::new().roots()).unwrap();
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+
+        assert!(tls.connect("localhost", stream).await.is_err());
+    }
+
+    #[cfg(feature = "ws")]
+    #[tokio::test]
+    async fn test_websocket() {
+        let ca = Ca::new();
+
+        let (chain, key) = ca.issue("localhost");
+
+        let mut server = Server::new();
+
+        whoami(&mut server);
+
+        let addr = serve(server, TlsServer::new(chain, key).unwrap(), true).await;
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+
+        let stream = TlsClient::new(ca.roots())
+            .unwrap()
+            .connect("localhost", stream)
+            .await
+            .unwrap();
+
+        let transport = WebSocket::handshake(&format!("wss://localhost:{}", addr.port()), stream)
+            .await
+            .unwrap();
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let test = async move {
+            let cert = client
+                .call::<_, Option<Vec<u8>>, Timeout>("whoami", (), None)
+                .await;
+
+            assert_eq!(cert.unwrap(), None);
+        };
+
+        let (_, _) = future::join(session.run(transport), test).await;
+    }
+}
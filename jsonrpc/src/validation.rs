@@ -0,0 +1,222 @@
+//! JSON Schema validation of request params
+//!
+//! Params failing the schema of their method are answered with [`ErrorCode::InvalidParams`],
+//! the error `data` lists every [`ValidationError`].
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    object::{Error, ErrorCode},
+    result::RPCError,
+};
+
+/// One validation failure, listed in the `data` of the [`ErrorCode::InvalidParams`] error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationError {
+    /// JSON pointer to the invalid value within params.
+    pub path: String,
+    /// JSON pointer to the failed keyword within the schema.
+    pub schema_path: String,
+    /// Human readable description of the failure.
+    pub message: String,
+}
+
+/// Params schema rejected by [`Server::validate`](crate::server::Server::validate).
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid params schema of {method}: {message}")]
+pub struct SchemaError {
+    /// Method the schema was registered for.
+    pub method: String,
+    /// Why the schema is not a valid JSON Schema.
+    pub message: String,
+}
+
+/// Compiled params schema.
+#[derive(Clone)]
+pub struct Schema {
+    validator: Arc<jsonschema::Validator>,
+}
+
+impl Schema {
+    /// Compile `schema`, the draft is detected from `$schema`, default is the latest one.
+    pub fn compile(schema: &Value) -> Result<Self, String> {
+        let validator = jsonschema::validator_for(schema).map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            validator: Arc::new(validator),
+        })
+    }
+
+    /// Validate `params`, returns all the failures if any.
+    pub fn validate(&self, params: &Value) -> Result<(), Vec<ValidationError>> {
+        let errors = self
+            .validator
+            .iter_errors(params)
+            .map(|err| ValidationError {
+                path: err.instance_path().to_string(),
+                schema_path: err.schema_path().to_string(),
+                message: err.to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
+/// Returns the [`ErrorCode::InvalidParams`] error of `errors`.
+pub(crate) fn invalid_params(errors: Vec<ValidationError>) -> RPCError {
+    let message = match errors.as_slice() {
+        [err] => format!("Invalid params: {}", err.message),
+        errors => format!("Invalid params: {} validation errors", errors.len()),
+    };
+
+    Error {
+        code: ErrorCode::InvalidParams,
+        message,
+        data: Some(json!(errors)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_timer_rs::hashed::Timeout;
+    use futures::future;
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+
+    use crate::{harness::Harness, object::ErrorCode, server::Server};
+
+    use super::ValidationError;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct Transfer {
+        to: String,
+        #[schemars(range(min = 1))]
+        amount: u64,
+        currency: Currency,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    enum Currency {
+        #[serde(rename = "ETH")]
+        Eth,
+    }
+
+    #[futures_test::test]
+    async fn test_validate() {
+        let mut server = Server::new();
+
+        server
+            .handle_validated("transfer", |_, params: Transfer| {
+                future::ready(Ok(params.amount > 0
+                    && !params.to.is_empty()
+                    && matches!(params.currency, Currency::Eth)))
+            })
+            .handle("echo", |_, params: Value| future::ready(Ok(params)))
+            .validate(
+                "echo",
+                &json!({"type": "array", "items": {"type": "string"}, "maxItems": 2}),
+            )
+            .unwrap();
+
+        let err = server
+            .validate("echo", &json!({"type": 1}))
+            .map(|_| ())
+            .unwrap_err();
+
+        assert_eq!(err.method, "echo");
+
+        Harness::new(server)
+            .run(|mut client| async move {
+                let ok = client
+                    .call::<_, bool, Timeout>(
+                        "transfer",
+                        json!({"to": "alice", "amount": 1, "currency": "ETH"}),
+                        None,
+                    )
+                    .await;
+
+                assert!(ok.unwrap());
+
+                let err = client
+                    .call::<_, bool, Timeout>(
+                        "transfer",
+                        json!({"to": "alice", "amount": 0, "currency": "BTC"}),
+                        None,
+                    )
+                    .await
+                    .unwrap_err();
+
+                assert_eq!(err.code, ErrorCode::InvalidParams);
+
+                let errors: Vec<ValidationError> =
+                    serde_json::from_value(err.data.unwrap()).unwrap();
+
+                let mut paths = errors.iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+
+                paths.sort();
+
+                assert_eq!(paths, vec!["/amount", "/currency"]);
+
+                let err = client
+                    .call::<_, Value, Timeout>("echo", json!(["a", 1, "c"]), None)
+                    .await
+                    .unwrap_err();
+
+                assert_eq!(err.code, ErrorCode::InvalidParams);
+
+                let errors: Vec<ValidationError> =
+                    serde_json::from_value(err.data.unwrap()).unwrap();
+
+                assert_eq!(errors.len(), 2);
+                assert!(errors.iter().any(|e| e.path == "/1"));
+                assert!(errors.iter().any(|e| e.schema_path == "/maxItems"));
+
+                let ok = client
+                    .call::<_, Value, Timeout>("echo", json!(["a"]), None)
+                    .await;
+
+                assert_eq!(ok.unwrap(), json!(["a"]));
+            })
+            .await;
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[futures_test::test]
+    async fn test_validate_not_json() {
+        use std::collections::BTreeMap;
+
+        use crate::codec::MessagePack;
+
+        let mut server = Server::with_codec(MessagePack);
+
+        server
+            .handle("sum", |_, params: BTreeMap<Vec<u8>, u8>| {
+                future::ready(Ok(params.values().sum::<u8>()))
+            })
+            // Accepts any JSON value.
+            .validate("sum", &json!({}))
+            .unwrap();
+
+        Harness::new(server)
+            .run(|mut client| async move {
+                // Array keys have no JSON counterpart.
+                let err = client
+                    .call::<_, u8, Timeout>("sum", BTreeMap::from([(vec![1u8], 2u8)]), None)
+                    .await
+                    .unwrap_err();
+
+                assert_eq!(err.code, ErrorCode::InvalidParams);
+                assert!(err.message.starts_with("Invalid params: "));
+            })
+            .await;
+    }
+}
@@ -0,0 +1,320 @@
+//! WebSocket transport, each frame is one text message.
+//!
+//! Runs within a tokio runtime.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Error as WsError, Message,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+
+/// Close frame received from the peer, with any code but normal closure.
+///
+/// Carried by the [`io::Error`] ending the [`WebSocket`] stream, see [`Closed::of`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("WebSocket closed with code {code}, {reason}")]
+pub struct Closed {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl Closed {
+    /// Returns the close frame carried by `err`, if any.
+    pub fn of(err: &io::Error) -> Option<&Closed> {
+        err.get_ref().and_then(|err| err.downcast_ref())
+    }
+}
+
+/// Transport over a WebSocket connection, for both client and server.
+///
+/// Outbound frames are sent as text messages, or binary messages if not UTF-8.
+/// Inbound text and binary messages are frames. Pings are answered with pongs.
+///
+/// The stream ends on normal closure, a close frame with any other code ends it with
+/// an [`io::ErrorKind::ConnectionAborted`] error carrying [`Closed`].
+pub struct WebSocket<S> {
+    inner: WebSocketStream<S>,
+}
+
+impl<S> WebSocket<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Wrap established WebSocket connection `inner`.
+    pub fn new(inner: WebSocketStream<S>) -> Self {
+        Self { inner }
+    }
+
+    /// Accept the WebSocket handshake of client connection `stream`.
+    pub async fn accept(stream: S) -> io::Result<Self> {
+        let inner = tokio_tungstenite::accept_async(stream)
+            .await
+            .map_err(into_io_error)?;
+
+        Ok(Self::new(inner))
+    }
+
+    /// Run the client WebSocket handshake with the server at `url` over connected `stream`,
+    /// e.g. a [`ClientTlsStream`](crate::tls::ClientTlsStream) for `wss` URLs.
+    pub async fn handshake(url: &str, stream: S) -> io::Result<Self> {
+        let (inner, _) = tokio_tungstenite::client_async(url, stream)
+            .await
+            .map_err(into_io_error)?;
+
+        Ok(Self::new(inner))
+    }
+
+    /// Close the connection with `code` and `reason`.
+    pub async fn close(&mut self, code: u16, reason: &str) -> io::Result<()> {
+        let frame = CloseFrame {
+            code: CloseCode::from(code),
+            reason: reason.into(),
+        };
+
+        self.inner.close(Some(frame)).await.map_err(into_io_error)
+    }
+
+    /// Returns the wrapped connection.
+    pub fn into_inner(self) -> WebSocketStream<S> {
+        self.inner
+    }
+}
+
+impl WebSocket<MaybeTlsStream<TcpStream>> {
+    /// Connect to the server at `url`, e.g. `ws://127.0.0.1:8080/rpc`.
+    pub async fn connect(url: &str) -> io::Result<Self> {
+        let (inner, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .map_err(into_io_error)?;
+
+        Ok(Self::new(inner))
+    }
+}
+
+impl<S> Stream for WebSocket<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let message = match self.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(message))) => message,
+                Poll::Ready(Some(Err(WsError::ConnectionClosed | WsError::AlreadyClosed))) => {
+                    return Poll::Ready(None)
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(into_io_error(err)))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            match message {
+                Message::Text(text) => return Poll::Ready(Some(Ok(text.into()))),
+                Message::Binary(frame) => return Poll::Ready(Some(Ok(frame))),
+                Message::Close(Some(frame)) if frame.code != CloseCode::Normal => {
+                    let closed = Closed {
+                        code: frame.code.into(),
+                        reason: frame.reason.as_str().to_owned(),
+                    };
+
+                    return Poll::Ready(Some(Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        closed,
+                    ))));
+                }
+                Message::Close(_) => return Poll::Ready(None),
+                // Pongs are queued by tungstenite and flushed with the next read or write.
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+            }
+        }
+    }
+}
+
+impl<S> Sink<Bytes> for WebSocket<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.inner.poll_ready_unpin(cx).map_err(into_io_error)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, frame: Bytes) -> io::Result<()> {
+        let message = match frame.clone().try_into() {
+            Ok(text) => Message::Text(text),
+            Err(_) => Message::Binary(frame),
+        };
+
+        self.inner.start_send_unpin(message).map_err(into_io_error)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.inner.poll_flush_unpin(cx).map_err(into_io_error)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.inner.poll_close_unpin(cx).map_err(into_io_error)
+    }
+}
+
+fn into_io_error(err: WsError) -> io::Error {
+    match err {
+        WsError::Io(err) => err,
+        WsError::ConnectionClosed | WsError::AlreadyClosed => {
+            io::Error::new(io::ErrorKind::BrokenPipe, err)
+        }
+        err => io::Error::other(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, time::Duration};
+
+    use async_timer_rs::hashed::Timeout;
+    use bytes::Bytes;
+    use futures::{future, SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message;
+
+    use crate::{
+        client::Client,
+        server::{Context, Server},
+        session::{Notification, Session},
+    };
+
+    use super::{Closed, WebSocket};
+
+    /// Serve `server` over WebSocket on a local port.
+    async fn serve(server: Server) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+
+                let server = server.clone();
+
+                tokio::spawn(async move {
+                    let transport = WebSocket::accept(stream).await.unwrap();
+
+                    _ = server.accept(transport).await;
+                });
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_subscription() {
+        let mut server = Server::new();
+
+        server.handle("subscribe", |mut context: Context, count: u32| {
+            // Push events after answering the call.
+            tokio::spawn(async move {
+                for i in 0..count {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+
+                    context.peer.notify("tick", [i]).await.unwrap();
+                }
+            });
+
+            future::ready(Ok("subscribed"))
+        });
+
+        let addr = serve(server).await;
+
+        let transport = WebSocket::connect(&format!("ws://{}", addr)).await.unwrap();
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let mut notifications = session.notifications();
+
+        let test = async move {
+            let result = client
+                .call::<_, String, Timeout>("subscribe", 3, None)
+                .await;
+
+            assert_eq!(result.unwrap(), "subscribed");
+
+            for i in 0..3 {
+                assert_eq!(
+                    notifications.next().await,
+                    Some(Notification {
+                        method: "tick".to_owned(),
+                        params: serde_json::json!([i]),
+                    })
+                );
+            }
+        };
+
+        let (_, _) = future::join(session.run(transport), test).await;
+    }
+
+    #[tokio::test]
+    async fn test_ping_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+
+            let mut transport = WebSocket::accept(stream).await.unwrap();
+
+            assert_eq!(transport.next().await.unwrap().unwrap(), "hello");
+
+            transport.close(4000, "bye").await.unwrap();
+        });
+
+        let (mut raw, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+
+        raw.send(Message::Ping(Bytes::from_static(b"ping")))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            raw.next().await.unwrap().unwrap(),
+            Message::Pong(Bytes::from_static(b"ping"))
+        );
+
+        let mut transport = WebSocket::new(raw);
+
+        transport.send(Bytes::from_static(b"hello")).await.unwrap();
+
+        let err = transport.next().await.unwrap().unwrap_err();
+
+        assert_eq!(
+            Closed::of(&err),
+            Some(&Closed {
+                code: 4000,
+                reason: "bye".to_owned(),
+            })
+        );
+
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+//! Pooled message buffer types
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use bytes::{Bytes, BytesMut};
+
+/// Frame buffer pool.
+///
+/// Frames are serialized at the end of a shared [`BytesMut`] chunk and split off it as
+/// [`Bytes`] without copy, so small frames share one allocation. The chunk allocation is
+/// reclaimed for new frames once all the frames split off it are dropped.
+///
+/// The chunk is taken out of the pool while a frame is written, a concurrent writer
+/// finding the pool empty serializes into a buffer of its own instead of waiting.
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    min: usize,
+    chunk: Arc<Mutex<BytesMut>>,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(4096)
+    }
+}
+
+impl BufferPool {
+    /// Create new pool allocating chunks of `min` bytes at least.
+    pub fn new(min: usize) -> Self {
+        Self {
+            min,
+            chunk: Default::default(),
+        }
+    }
+
+    /// Write one frame with `write`, returns the frozen frame.
+    pub fn frame<F, E>(&self, write: F) -> Result<Bytes, E>
+    where
+        F: FnOnce(&mut FrameWriter) -> Result<(), E>,
+    {
+        let mut chunk = match self.chunk.try_lock() {
+            Ok(mut chunk) => std::mem::take(&mut *chunk),
+            Err(_) => BytesMut::new(),
+        };
+
+        // Nearly full, start a new chunk, which reuses the allocation if no frame split off
+        // it is alive. Frames larger than the room left grow the chunk.
+        if chunk.capacity() < self.min / 4 {
+            chunk.reserve(self.min);
+        }
+
+        let mut writer = FrameWriter(chunk);
+
+        let result = write(&mut writer);
+
+        let FrameWriter(mut chunk) = writer;
+
+        let frame = result.map(|_| chunk.split().freeze());
+
+        // Failed write leaves nothing behind.
+        chunk.clear();
+
+        if let Ok(mut pooled) = self.chunk.try_lock() {
+            if pooled.capacity() < chunk.capacity() {
+                *pooled = chunk;
+            }
+        }
+
+        frame
+    }
+}
+
+/// Writer appending to the chunk of a [`BufferPool`].
+#[derive(Debug)]
+pub struct FrameWriter(BytesMut);
+
+impl io::Write for FrameWriter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.0.extend_from_slice(buf);
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::BufferPool;
+
+    #[test]
+    fn test_frame() {
+        let pool = BufferPool::new(16);
+
+        let first = pool.frame(|buf| buf.write_all(b"hello")).unwrap();
+
+        let second = pool.frame(|buf| buf.write_all(b"world")).unwrap();
+
+        assert_eq!(first, "hello");
+        assert_eq!(second, "world");
+
+        // Split off the same chunk.
+        assert_eq!(first.as_ptr().wrapping_add(5), second.as_ptr());
+
+        pool.frame(|buf| {
+            buf.write_all(b"partial")?;
+
+            Err::<(), _>(std::io::Error::from(std::io::ErrorKind::Other))
+        })
+        .unwrap_err();
+
+        // Failed write leaves nothing behind.
+        let third = pool.frame(|buf| buf.write_all(b"!")).unwrap();
+
+        assert_eq!(third, "!");
+
+        // Frames larger than the chunk grow a new one.
+        let large = pool.frame(|buf| buf.write_all(&[b'x'; 1024])).unwrap();
+
+        assert_eq!(large.len(), 1024);
+        assert_eq!(first, "hello");
+    }
+
+    #[test]
+    fn test_reclaim() {
+        let pool = BufferPool::new(16);
+
+        let first = pool.frame(|buf| buf.write_all(b"hello")).unwrap();
+
+        let ptr = first.as_ptr();
+
+        drop(first);
+
+        for _ in 0..10 {
+            let frame = pool.frame(|buf| buf.write_all(b"hello")).unwrap();
+
+            // The dropped frames leave room in the chunk for the next ones.
+            assert!(frame.as_ptr() >= ptr && frame.as_ptr() < ptr.wrapping_add(16));
+        }
+    }
+}
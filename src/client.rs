@@ -1,16 +1,23 @@
 //! RPC client type
 
-use crossbeam_channel::{bounded, Receiver};
+use std::marker::PhantomData;
 
 /// Channel client side
 pub struct Client<Payload> {
-    receiver: Receiver<Payload>,
+    payload: PhantomData<Payload>,
+}
+
+impl<Payload> Default for Client<Payload> {
+    fn default() -> Self {
+        Client {
+            payload: PhantomData,
+        }
+    }
 }
 
 impl<Payload> Client<Payload> {
-    /// Create new client instance with channel `cap`
-    pub fn new(cap: usize) -> Self {
-        let (sender, receiver) = bounded(cap);
-        Client { receiver }
+    /// Create new client instance
+    pub fn new() -> Self {
+        Self::default()
     }
 }
@@ -0,0 +1,150 @@
+//! Message encoding types
+
+use std::io;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Serialize rpc objects into frames and back.
+///
+/// Encoding errors are reported as [`io::ErrorKind::InvalidData`] errors.
+pub trait Codec: Clone + Default + Send + Sync + 'static {
+    /// Write the encoded `value` to `writer`.
+    fn encode<T, W>(&self, value: &T, writer: W) -> io::Result<()>
+    where
+        T: Serialize,
+        W: io::Write;
+
+    /// Decode one value from `frame`.
+    fn decode<T>(&self, frame: &[u8]) -> io::Result<T>
+    where
+        T: DeserializeOwned;
+}
+
+#[cfg(any(feature = "json", feature = "msgpack", feature = "cbor"))]
+fn invalid_data<E: std::fmt::Display>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// JSON codec.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    fn encode<T, W>(&self, value: &T, writer: W) -> io::Result<()>
+    where
+        T: Serialize,
+        W: io::Write,
+    {
+        serde_json::to_writer(writer, value).map_err(invalid_data)
+    }
+
+    fn decode<T>(&self, frame: &[u8]) -> io::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_slice(frame).map_err(invalid_data)
+    }
+}
+
+/// MessagePack codec, structs are encoded as maps so optional fields may be skipped.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MessagePack {
+    fn encode<T, W>(&self, value: &T, mut writer: W) -> io::Result<()>
+    where
+        T: Serialize,
+        W: io::Write,
+    {
+        rmp_serde::encode::write_named(&mut writer, value).map_err(invalid_data)
+    }
+
+    fn decode<T>(&self, frame: &[u8]) -> io::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        rmp_serde::from_slice(frame).map_err(invalid_data)
+    }
+}
+
+/// CBOR codec.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn encode<T, W>(&self, value: &T, writer: W) -> io::Result<()>
+    where
+        T: Serialize,
+        W: io::Write,
+    {
+        ciborium::into_writer(value, writer).map_err(invalid_data)
+    }
+
+    fn decode<T>(&self, frame: &[u8]) -> io::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        ciborium::from_reader(frame).map_err(invalid_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::Codec;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Message {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<u64>,
+        method: String,
+        params: Vec<i32>,
+    }
+
+    fn round_trip<C: Codec>(codec: C) {
+        for id in [Some(1), None] {
+            let message = Message {
+                id,
+                method: "hello".to_owned(),
+                params: vec![1, -32601],
+            };
+
+            let mut buf = vec![];
+
+            codec.encode(&message, &mut buf).unwrap();
+
+            assert_eq!(codec.decode::<Message>(&buf).unwrap(), message);
+        }
+
+        let err = codec.decode::<Message>(b"\xff\xff").unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        round_trip(super::Json);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack() {
+        round_trip(super::MessagePack);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor() {
+        round_trip(super::Cbor);
+    }
+}
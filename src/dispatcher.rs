@@ -0,0 +1,72 @@
+//! RPC dispatcher types
+
+use async_timer_rs::Timer;
+use bytes::Bytes;
+use futures::{
+    channel::mpsc::{channel, Receiver, SendError, Sender},
+    SinkExt,
+};
+
+use crate::responder::{Responder, Response};
+
+/// RPC dispatcher
+///
+/// Requests and responses are encoded frames of buffer type `B`, frames are handed
+/// over without copy, so prefer a cheaply cloneable buffer like [`Bytes`].
+#[derive(Debug)]
+pub struct Dispatcher<Error, B = Bytes> {
+    sender: Sender<(Option<u64>, B)>,
+    pub responder: Responder<Error, B>,
+}
+
+impl<Error, B> Clone for Dispatcher<Error, B> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            responder: self.responder.clone(),
+        }
+    }
+}
+
+impl<Error, B> Dispatcher<Error, B>
+where
+    B: AsRef<[u8]> + Send + Sync + 'static,
+    Error: From<SendError>,
+{
+    /// Create new dispatcher and
+    pub fn new(cache_size: usize) -> (Self, Receiver<(Option<u64>, B)>) {
+        let (sender, receiver) = channel(cache_size);
+
+        (
+            Self {
+                sender,
+                responder: Responder::new(),
+            },
+            receiver,
+        )
+    }
+
+    /// Start a new rpc call with sequence id.
+    pub async fn call<T: Timer>(
+        &mut self,
+        id: u64,
+        input: B,
+        timeout: Option<T>,
+    ) -> Result<Response<T, Error, B>, Error> {
+        // Wait for the response before sending, it may arrive before `send` returns.
+        let response = Response::new(id, self.responder.clone(), timeout);
+
+        match self.sender.send((Some(id), input)).await {
+            Ok(_) => Ok(response),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Start send one notification to remote.
+    pub async fn notification(&mut self, input: B) -> Result<(), Error> {
+        match self.sender.send((None, input)).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
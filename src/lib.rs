@@ -1 +1,6 @@
+pub mod buffer;
 pub mod client;
+pub mod codec;
+pub mod dispatcher;
+pub mod responder;
+pub mod transport;
@@ -0,0 +1,247 @@
+//! RPC response associate types.
+
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+};
+
+use async_timer_rs::Timer;
+use bytes::Bytes;
+use futures::FutureExt;
+
+#[derive(Debug)]
+struct DispatcherImpl<Error, B> {
+    /// Ids of the live [`Response`] objects.
+    open: HashSet<u64>,
+    wakers: HashMap<u64, Waker>,
+    completed: HashMap<u64, Result<B, Error>>,
+}
+
+impl<Error, B> Default for DispatcherImpl<Error, B> {
+    fn default() -> Self {
+        Self {
+            open: HashSet::new(),
+            wakers: HashMap::new(),
+            completed: HashMap::new(),
+        }
+    }
+}
+
+/// Rpc message dispatcher, completes calls with response frames of buffer type `B`.
+#[derive(Debug)]
+pub struct Responder<Error, B = Bytes> {
+    inner: Arc<Mutex<DispatcherImpl<Error, B>>>,
+}
+
+impl<Error, B> Clone for Responder<Error, B> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Error, B> Default for Responder<Error, B> {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(DispatcherImpl::default())),
+        }
+    }
+}
+
+impl<Error, B> Responder<Error, B> {
+    /// Create new responder without pending calls.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Emit complete event with [`output`](Result<B>)
+    ///
+    /// Dropped if nobody waits for call `id`, e.g. its [`Response`] timed out.
+    pub fn complete(&self, id: u64, output: Result<B, Error>) {
+        let waker = {
+            let mut inner = self.inner.lock().unwrap();
+
+            if !inner.open.contains(&id) {
+                log::debug!("drop response {}, no pending call", id);
+
+                return;
+            }
+
+            inner.completed.insert(id, output);
+
+            inner.wakers.remove(&id)
+        };
+
+        if let Some(waker) = waker {
+            waker.wake()
+        }
+    }
+
+    /// Remove from `ids` the calls no longer waiting for a response.
+    pub fn retain_pending(&self, ids: &mut HashSet<u64>) {
+        let inner = self.inner.lock().unwrap();
+
+        ids.retain(|id| inner.open.contains(id));
+    }
+
+    /// Returns the number of received responses not read yet.
+    pub fn buffered(&self) -> usize {
+        self.inner.lock().unwrap().completed.len()
+    }
+
+    /// Start waiting for the response of call `id`.
+    fn open(&self, id: u64) {
+        self.inner.lock().unwrap().open.insert(id);
+    }
+
+    /// Stop waiting for the response of call `id`, dropping it if already received.
+    fn close(&self, id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.open.remove(&id);
+        inner.wakers.remove(&id);
+        inner.completed.remove(&id);
+    }
+
+    /// Poll response data once.
+    ///
+    /// # Parameters
+    /// - `id` RPC id for [`responder`](Responder)
+    /// - `waker` [`Waker`] of [`responder`](Responder) [`future`](Future)
+    fn poll_once(&self, id: u64, waker: Waker) -> Poll<Result<B, Error>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(r) = inner.completed.remove(&id) {
+            inner.open.remove(&id);
+
+            return Poll::Ready(r);
+        }
+
+        inner.wakers.insert(id, waker);
+
+        Poll::Pending
+    }
+}
+
+/// Response poller of one call.
+///
+/// Responses arriving after this object is dropped or timed out are discarded.
+pub struct Response<T, Error, B = Bytes> {
+    id: u64,
+    responder: Responder<Error, B>,
+    timeout: Option<T>,
+}
+
+impl<T, Error, B> Response<T, Error, B> {
+    /// Create new response object, responses to `id` are kept from now on.
+    pub fn new(id: u64, responder: Responder<Error, B>, timeout: Option<T>) -> Self {
+        responder.open(id);
+
+        Response {
+            id,
+            responder,
+            timeout,
+        }
+    }
+}
+
+impl<T, Error, B> Future for Response<T, Error, B>
+where
+    T: Timer + Unpin,
+    Error: From<std::io::Error>,
+{
+    type Output = Result<B, Error>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let timer = self.timeout.take();
+
+        if let Some(mut timer) = timer {
+            match timer.poll_unpin(cx) {
+                Poll::Pending => {
+                    self.timeout = Some(timer);
+                }
+                Poll::Ready(_) => {
+                    // Remove pending poll operation and discard late response.
+                    self.responder.close(self.id);
+
+                    // Return timeout error
+                    return Poll::Ready(Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "Response timeout",
+                    )
+                    .into()));
+                }
+            }
+        }
+
+        self.responder.poll_once(self.id, cx.waker().clone())
+    }
+}
+
+impl<T, Error, B> Drop for Response<T, Error, B> {
+    fn drop(&mut self) {
+        self.responder.close(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use async_timer_rs::{hashed::Timeout, Timer};
+    use futures::FutureExt;
+
+    use super::{Responder, Response};
+
+    type TestResponse = Response<Timeout, io::Error, Vec<u8>>;
+
+    #[futures_test::test]
+    async fn test_late_response() {
+        let responder = Responder::<io::Error, Vec<u8>>::new();
+
+        let response = TestResponse::new(1, responder.clone(), None);
+
+        responder.complete(1, Ok(b"hello".to_vec()));
+
+        assert_eq!(response.await.unwrap(), b"hello");
+
+        // Answered already.
+        responder.complete(1, Ok(vec![]));
+
+        // Dropped while waiting.
+        let response = TestResponse::new(2, responder.clone(), None);
+
+        assert!(response.now_or_never().is_none());
+
+        responder.complete(2, Ok(vec![]));
+
+        // Timed out.
+        let timeout = Timeout::new(std::time::Duration::from_millis(10));
+
+        let err = TestResponse::new(3, responder.clone(), Some(timeout))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        responder.complete(3, Ok(vec![]));
+
+        // Answered but never polled.
+        let response = TestResponse::new(4, responder.clone(), None);
+
+        responder.complete(4, Ok(vec![]));
+
+        drop(response);
+
+        let inner = responder.inner.lock().unwrap();
+
+        assert!(inner.open.is_empty());
+        assert!(inner.wakers.is_empty());
+        assert!(inner.completed.is_empty());
+    }
+}
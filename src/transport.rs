@@ -0,0 +1,33 @@
+//! RPC transport types
+
+use futures::{Sink, Stream};
+use serde::{Deserialize, Serialize};
+
+pub mod fault;
+pub mod memory;
+#[cfg(feature = "record")]
+pub mod record;
+
+/// Bidirectional message channel between two rpc peers.
+///
+/// Inbound messages are read from the [`Stream`] half, outbound messages are written
+/// into the [`Sink`] half. An `Err` item or the end of the stream means the peer is gone.
+pub trait Transport<Payload>:
+    Stream<Item = std::io::Result<Payload>> + Sink<Payload, Error = std::io::Error> + Unpin
+{
+}
+
+impl<T, Payload> Transport<Payload> for T where
+    T: Stream<Item = std::io::Result<Payload>> + Sink<Payload, Error = std::io::Error> + Unpin
+{
+}
+
+/// Direction of a frame, relative to the local peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Frame received from the remote peer.
+    Inbound,
+    /// Frame sent to the remote peer.
+    Outbound,
+}
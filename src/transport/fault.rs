@@ -0,0 +1,492 @@
+//! Fault injection transport, for resilience testing.
+
+use std::{
+    collections::VecDeque,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_timer_rs::Timer;
+use bytes::Bytes;
+use futures::{Sink, Stream};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::Transport;
+
+/// Seeded fault schedule of a [`Faulty`] transport.
+///
+/// Each frame, inbound or outbound, gets at most one fault with the configured
+/// probabilities, whose sum must not exceed `1`. Outbound faults are drawn from
+/// `seed` and inbound ones from `seed ^ 1`, so the same seed and the same frame
+/// sequence of one direction always inject the same faults, however both directions
+/// interleave.
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    seed: u64,
+    disconnect: f64,
+    drop: f64,
+    delay: f64,
+    delay_duration: Duration,
+    duplicate: f64,
+    reorder: f64,
+    truncate: f64,
+}
+
+impl Faults {
+    /// Create new schedule without any fault.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    /// Fail the transport with [`io::ErrorKind::ConnectionReset`], all later operations fail too.
+    pub fn disconnect(mut self, probability: f64) -> Self {
+        self.disconnect = probability;
+        self
+    }
+
+    /// Silently lose the frame.
+    pub fn drop(mut self, probability: f64) -> Self {
+        self.drop = probability;
+        self
+    }
+
+    /// Hold the frame for `duration`, frames behind it are held too.
+    pub fn delay(mut self, probability: f64, duration: Duration) -> Self {
+        self.delay = probability;
+        self.delay_duration = duration;
+        self
+    }
+
+    /// Deliver the frame twice.
+    pub fn duplicate(mut self, probability: f64) -> Self {
+        self.duplicate = probability;
+        self
+    }
+
+    /// Deliver the frame after the next one.
+    pub fn reorder(mut self, probability: f64) -> Self {
+        self.reorder = probability;
+        self
+    }
+
+    /// Cut the frame at a random length.
+    pub fn truncate(mut self, probability: f64) -> Self {
+        self.truncate = probability;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fault {
+    None,
+    Disconnect,
+    Drop,
+    Delay,
+    Duplicate,
+    Reorder,
+    Truncate(usize),
+}
+
+/// Frames of one direction, in delivery order.
+struct Queue<Tm> {
+    /// Fault draws of this direction.
+    rng: StdRng,
+    /// Frame with the timer to wait before delivering it.
+    frames: VecDeque<(Option<Tm>, Bytes)>,
+    /// Reordered frame, delivered after the next one.
+    held: Option<Bytes>,
+}
+
+impl<Tm> Queue<Tm>
+where
+    Tm: Timer + Unpin,
+{
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            frames: Default::default(),
+            held: None,
+        }
+    }
+
+    /// Draw the fault of the next frame of `len` bytes.
+    fn roll(&mut self, faults: &Faults, len: usize) -> Fault {
+        let mut draw = self.rng.gen::<f64>();
+
+        for (probability, fault) in [
+            (faults.disconnect, Fault::Disconnect),
+            (faults.drop, Fault::Drop),
+            (faults.delay, Fault::Delay),
+            (faults.duplicate, Fault::Duplicate),
+            (faults.reorder, Fault::Reorder),
+            (faults.truncate, Fault::Truncate(0)),
+        ] {
+            if draw < probability {
+                log::debug!("inject fault {:?}", fault);
+
+                return match fault {
+                    Fault::Truncate(_) => Fault::Truncate(self.rng.gen_range(0..len.max(1))),
+                    fault => fault,
+                };
+            }
+
+            draw -= probability;
+        }
+
+        Fault::None
+    }
+
+    /// Apply `fault` to `frame`, returns false if the transport is disconnected.
+    fn push(&mut self, fault: Fault, frame: Bytes, delay: Duration) -> bool {
+        match fault {
+            Fault::None => self.frames.push_back((None, frame)),
+            Fault::Disconnect => return false,
+            Fault::Drop => {}
+            Fault::Delay => self.frames.push_back((Some(Tm::new(delay)), frame)),
+            Fault::Duplicate => {
+                self.frames.push_back((None, frame.clone()));
+                self.frames.push_back((None, frame));
+            }
+            Fault::Reorder if self.held.is_none() => {
+                self.held = Some(frame);
+                return true;
+            }
+            Fault::Reorder => self.frames.push_back((None, frame)),
+            Fault::Truncate(len) => self.frames.push_back((None, frame.slice(..len))),
+        }
+
+        if fault != Fault::Drop {
+            self.release();
+        }
+
+        true
+    }
+
+    /// Queue the reordered frame.
+    fn release(&mut self) {
+        if let Some(frame) = self.held.take() {
+            self.frames.push_back((None, frame));
+        }
+    }
+
+    /// Pop the next frame if its delay is elapsed.
+    fn poll_pop(&mut self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        match self.frames.front_mut() {
+            Some((Some(timer), _)) => {
+                if Pin::new(timer).poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            }
+            Some((None, _)) => {}
+            None => return Poll::Ready(None),
+        }
+
+        Poll::Ready(self.frames.pop_front().map(|(_, frame)| frame))
+    }
+}
+
+/// Transport wrapper injecting [`Faults`] into both directions of `T`,
+/// delays are measured with timer `Tm`.
+pub struct Faulty<T, Tm> {
+    inner: T,
+    faults: Faults,
+    inbound: Queue<Tm>,
+    outbound: Queue<Tm>,
+    /// Inner stream is ended.
+    ended: bool,
+    disconnected: bool,
+}
+
+impl<T, Tm> Faulty<T, Tm>
+where
+    T: Transport<Bytes>,
+    Tm: Timer + Unpin,
+{
+    /// Wrap `transport` with fault schedule `faults`.
+    pub fn new(transport: T, faults: Faults) -> Self {
+        Self {
+            inner: transport,
+            inbound: Queue::new(faults.seed ^ 1),
+            outbound: Queue::new(faults.seed),
+            faults,
+            ended: false,
+            disconnected: false,
+        }
+    }
+
+    /// Returns the wrapped transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Forward outbound frames to the inner transport.
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.outbound.frames.is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+
+            if let Err(err) = futures::ready!(Pin::new(&mut self.inner).poll_ready(cx)) {
+                return Poll::Ready(Err(err));
+            }
+
+            match futures::ready!(self.outbound.poll_pop(cx)) {
+                Some(frame) => Pin::new(&mut self.inner).start_send(frame)?,
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+fn connection_reset() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionReset, "injected disconnect")
+}
+
+impl<T, Tm> Stream for Faulty<T, Tm>
+where
+    T: Transport<Bytes>,
+    Tm: Timer + Unpin,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if this.disconnected {
+                return Poll::Ready(None);
+            }
+
+            if let Poll::Ready(Some(frame)) = this.inbound.poll_pop(cx) {
+                return Poll::Ready(Some(Ok(frame)));
+            }
+
+            if this.ended {
+                // Deliver the frames still held before the end of stream.
+                this.inbound.release();
+
+                return match this.inbound.frames.is_empty() {
+                    true => Poll::Ready(None),
+                    false => this.inbound.poll_pop(cx).map(|frame| frame.map(Ok)),
+                };
+            }
+
+            match futures::ready!(Pin::new(&mut this.inner).poll_next(cx)) {
+                Some(Ok(frame)) => {
+                    let fault = this.inbound.roll(&this.faults, frame.len());
+
+                    if !this.inbound.push(fault, frame, this.faults.delay_duration) {
+                        this.disconnected = true;
+
+                        return Poll::Ready(Some(Err(connection_reset())));
+                    }
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => this.ended = true,
+            }
+        }
+    }
+}
+
+impl<T, Tm> Sink<Bytes> for Faulty<T, Tm>
+where
+    T: Transport<Bytes>,
+    Tm: Timer + Unpin,
+{
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.disconnected {
+            return Poll::Ready(Err(connection_reset()));
+        }
+
+        self.poll_send(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> io::Result<()> {
+        if self.disconnected {
+            return Err(connection_reset());
+        }
+
+        let this = &mut *self;
+
+        let fault = this.outbound.roll(&this.faults, item.len());
+
+        if !this.outbound.push(fault, item, this.faults.delay_duration) {
+            this.disconnected = true;
+
+            return Err(connection_reset());
+        }
+
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.disconnected {
+            return Poll::Ready(Err(connection_reset()));
+        }
+
+        futures::ready!(self.poll_send(cx))?;
+
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.disconnected {
+            self.outbound.release();
+
+            futures::ready!(self.poll_send(cx))?;
+        }
+
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        task::Poll,
+        time::{Duration, Instant},
+    };
+
+    use async_timer_rs::hashed::Timeout;
+    use bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+
+    use crate::transport::memory::{pair, Memory};
+
+    use super::{Faults, Faulty};
+
+    fn faulty(faults: Faults) -> (Faulty<Memory<Bytes>, Timeout>, Memory<Bytes>) {
+        let (transport, peer) = pair(100);
+
+        (Faulty::new(transport, faults), peer)
+    }
+
+    async fn send_all(transport: &mut Faulty<Memory<Bytes>, Timeout>, frames: &[&'static str]) {
+        for frame in frames {
+            transport
+                .send(Bytes::from_static(frame.as_bytes()))
+                .await
+                .unwrap();
+        }
+
+        transport.close().await.unwrap();
+    }
+
+    #[futures_test::test]
+    async fn test_faults() {
+        let (mut transport, peer) = faulty(Faults::new(1).duplicate(1.0));
+        send_all(&mut transport, &["a"]).await;
+        assert_eq!(peer.collect::<Vec<_>>().await.len(), 2);
+
+        let (mut transport, peer) = faulty(Faults::new(1).reorder(1.0));
+        send_all(&mut transport, &["a", "b", "c"]).await;
+        let frames: Vec<_> = peer.map(Result::unwrap).collect().await;
+        assert_eq!(frames, vec!["b", "a", "c"]);
+
+        let (mut transport, peer) = faulty(Faults::new(1).truncate(1.0));
+        send_all(&mut transport, &["hello"]).await;
+        let frames: Vec<_> = peer.map(Result::unwrap).collect().await;
+        assert!(frames[0].len() < 5 && b"hello".starts_with(&frames[0]));
+
+        let (mut transport, peer) = faulty(Faults::new(1).drop(1.0));
+        send_all(&mut transport, &["a", "b"]).await;
+        assert!(peer.collect::<Vec<_>>().await.is_empty());
+
+        let (mut transport, _peer) = faulty(Faults::new(1).disconnect(1.0));
+        assert!(transport.send(Bytes::from_static(b"a")).await.is_err());
+        assert!(transport.next().await.is_none());
+
+        let (mut transport, mut peer) =
+            faulty(Faults::new(1).delay(1.0, Duration::from_millis(50)));
+        let start = Instant::now();
+        peer.send(Bytes::from_static(b"a")).await.unwrap();
+        assert_eq!(transport.next().await.unwrap().unwrap(), "a");
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[futures_test::test]
+    async fn test_deterministic() {
+        let frames = (0..100)
+            .map(|i| Bytes::from(format!("frame {}", i)))
+            .collect::<Vec<_>>();
+
+        let mut received = vec![];
+
+        for _ in 0..2 {
+            let faults = Faults::new(7)
+                .drop(0.1)
+                .duplicate(0.1)
+                .reorder(0.1)
+                .truncate(0.1);
+
+            let (transport, mut peer) = faulty(faults);
+
+            for frame in &frames {
+                peer.send(frame.clone()).await.unwrap();
+            }
+
+            drop(peer);
+
+            received.push(transport.map(Result::unwrap).collect::<Vec<_>>().await);
+        }
+
+        assert_eq!(received[0], received[1]);
+        assert_ne!(received[0], frames);
+    }
+
+    #[futures_test::test]
+    async fn test_deterministic_interleaving() {
+        let frames = (0..50)
+            .map(|i| Bytes::from(format!("frame {}", i)))
+            .collect::<Vec<_>>();
+
+        let mut received = vec![];
+
+        // Each direction in turn, then both directions alternately.
+        for interleave in [false, true] {
+            let (mut transport, peer) = faulty(Faults::new(7).drop(0.2).truncate(0.2));
+
+            let (mut peer_sink, peer_stream) = peer.split();
+
+            let mut inbound = vec![];
+
+            for frame in &frames {
+                transport.send(frame.clone()).await.unwrap();
+
+                if interleave {
+                    peer_sink.send(frame.clone()).await.unwrap();
+
+                    if let Poll::Ready(Some(frame)) = futures::poll!(transport.next()) {
+                        inbound.push(frame.unwrap());
+                    }
+                }
+            }
+
+            if !interleave {
+                for frame in &frames {
+                    peer_sink.send(frame.clone()).await.unwrap();
+                }
+            }
+
+            // Without delay nor reorder, every inbound frame left is ready.
+            while let Poll::Ready(Some(frame)) = futures::poll!(transport.next()) {
+                inbound.push(frame.unwrap());
+            }
+
+            transport.close().await.unwrap();
+
+            let outbound = peer_stream.map(Result::unwrap).collect::<Vec<_>>().await;
+
+            received.push((inbound, outbound));
+        }
+
+        assert_eq!(received[0], received[1]);
+    }
+}
@@ -0,0 +1,103 @@
+//! In memory transport, connecting two peers in the same process without sockets.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    Sink, SinkExt, Stream, StreamExt,
+};
+
+/// One side of an in memory connection, see [`pair`] and [`pipe`].
+///
+/// Dropping one side ends the stream of the other side, sending to a dropped
+/// peer fails with [`io::ErrorKind::BrokenPipe`].
+#[derive(Debug)]
+pub struct Memory<Payload> {
+    receiver: Receiver<Payload>,
+    sender: Sender<Payload>,
+}
+
+impl<Payload> Stream for Memory<Payload> {
+    type Item = io::Result<Payload>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx).map(|frame| frame.map(Ok))
+    }
+}
+
+impl<Payload> Sink<Payload> for Memory<Payload> {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.sender.poll_ready_unpin(cx).map_err(broken_pipe)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Payload) -> io::Result<()> {
+        self.sender.start_send_unpin(item).map_err(broken_pipe)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.sender.poll_flush_unpin(cx).map_err(broken_pipe)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.sender.poll_close_unpin(cx).map_err(broken_pipe)
+    }
+}
+
+fn broken_pipe<E: std::fmt::Display>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, err.to_string())
+}
+
+/// Create two connected transports, each side buffers up to `cache_size` frames.
+pub fn pair<Payload>(cache_size: usize) -> (Memory<Payload>, Memory<Payload>) {
+    let (first_sender, first_receiver) = channel(cache_size);
+    let (second_sender, second_receiver) = channel(cache_size);
+
+    (
+        Memory {
+            receiver: first_receiver,
+            sender: second_sender,
+        },
+        Memory {
+            receiver: second_receiver,
+            sender: first_sender,
+        },
+    )
+}
+
+/// Create one transport driven by raw channels, returns the transport with the
+/// peer input and output channels.
+///
+/// Useful to feed hand written frames to a server under test.
+pub fn pipe<Payload>(cache_size: usize) -> (Memory<Payload>, Sender<Payload>, Receiver<Payload>) {
+    let (input, receiver) = channel(cache_size);
+    let (sender, output) = channel(cache_size);
+
+    (Memory { receiver, sender }, input, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+
+    use super::pair;
+
+    #[futures_test::test]
+    async fn test_pair() {
+        let (mut first, mut second) = pair::<&str>(1);
+
+        first.send("hello").await.unwrap();
+
+        assert_eq!(second.next().await.unwrap().unwrap(), "hello");
+
+        drop(first);
+
+        assert!(second.next().await.is_none());
+        assert!(second.send("world").await.is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Traffic recording and replay.
+//!
+//! A recording is a JSON lines file, one [`Record`] per frame.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    pin::Pin,
+    sync::mpsc,
+    task::{ready, Context, Poll, Waker},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use futures::{channel::oneshot, FutureExt, Sink, Stream};
+use serde::{Deserialize, Serialize};
+
+use super::{Direction, Transport};
+
+/// One recorded frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Microseconds since UNIX epoch.
+    pub ts: u64,
+    /// Direction relative to the recording peer.
+    pub direction: Direction,
+    /// Frame data, a string for UTF-8 frames, `{"base64": ".."}` otherwise.
+    #[serde(with = "frame")]
+    pub frame: Bytes,
+}
+
+mod frame {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use bytes::Bytes;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Data {
+        Text(String),
+        Binary { base64: String },
+    }
+
+    pub fn serialize<S: Serializer>(frame: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        match std::str::from_utf8(frame) {
+            Ok(text) => text.serialize(serializer),
+            Err(_) => Data::Binary {
+                base64: STANDARD.encode(frame),
+            }
+            .serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        match Data::deserialize(deserializer)? {
+            Data::Text(text) => Ok(text.into()),
+            Data::Binary { base64 } => STANDARD
+                .decode(base64)
+                .map(Into::into)
+                .map_err(de::Error::custom),
+        }
+    }
+}
+
+/// Read all records of recording file `path`.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Record>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+/// Buffered record lines handed to the writer thread at this size without flush.
+const CHUNK: usize = 64 * 1024;
+
+/// Lines for the writer thread, with the sender notified once they are flushed.
+type Chunk = (Vec<u8>, Option<oneshot::Sender<()>>);
+
+/// Transport wrapper writing every frame of `T` as a [`Record`] line.
+///
+/// Lines are buffered in memory and written by a thread, so polling never blocks on the
+/// writer. Flushing or closing the transport waits until the lines are written, lines
+/// buffered when dropped are written in the background.
+///
+/// Recording errors are logged and never fail the wrapped transport.
+pub struct Recorder<T> {
+    inner: T,
+    /// Lines not handed to the writer thread yet.
+    buffer: Vec<u8>,
+    writer: mpsc::Sender<Chunk>,
+    /// Completes once the lines handed over by the pending flush are written.
+    flushed: Option<oneshot::Receiver<()>>,
+}
+
+impl<T> Recorder<T>
+where
+    T: Transport<Bytes>,
+{
+    /// Wrap `transport`, recording into new file `path`.
+    pub fn create<P: AsRef<Path>>(transport: T, path: P) -> io::Result<Self> {
+        Ok(Self::new(transport, File::create(path)?))
+    }
+
+    /// Wrap `transport`, recording into `writer`.
+    pub fn new<W>(transport: T, mut writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<Chunk>();
+
+        thread::spawn(move || {
+            for (lines, flushed) in receiver {
+                let mut result = writer.write_all(&lines);
+
+                if flushed.is_some() {
+                    result = result.and_then(|_| writer.flush());
+                }
+
+                if let Err(err) = result {
+                    log::warn!("write recording failed: {}", err);
+                }
+
+                if let Some(flushed) = flushed {
+                    _ = flushed.send(());
+                }
+            }
+
+            if let Err(err) = writer.flush() {
+                log::warn!("flush recording failed: {}", err);
+            }
+        });
+
+        Self {
+            inner: transport,
+            buffer: vec![],
+            writer: sender,
+            flushed: None,
+        }
+    }
+
+    fn record(&mut self, direction: Direction, frame: &Bytes) {
+        let record = Record {
+            ts: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|ts| ts.as_micros() as u64)
+                .unwrap_or_default(),
+            direction,
+            frame: frame.clone(),
+        };
+
+        if let Err(err) = serde_json::to_writer(&mut self.buffer, &record) {
+            log::warn!("record frame failed: {}", err);
+
+            return;
+        }
+
+        self.buffer.push(b'\n');
+
+        if self.buffer.len() >= CHUNK {
+            self.hand_over(None);
+        }
+    }
+
+    /// Hand the buffered lines to the writer thread.
+    fn hand_over(&mut self, flushed: Option<oneshot::Sender<()>>) {
+        let lines = std::mem::take(&mut self.buffer);
+
+        if self.writer.send((lines, flushed)).is_err() {
+            log::warn!("recording writer is gone");
+        }
+    }
+
+    /// Wait until all lines recorded so far are written.
+    fn poll_written(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let flushed = match &mut self.flushed {
+            Some(flushed) => flushed,
+            None => {
+                let (sender, receiver) = oneshot::channel();
+
+                self.hand_over(Some(sender));
+
+                self.flushed.insert(receiver)
+            }
+        };
+
+        // A gone writer thread drops the sender, nothing left to wait for.
+        _ = ready!(flushed.poll_unpin(cx));
+
+        self.flushed = None;
+
+        Poll::Ready(())
+    }
+}
+
+impl<T> Drop for Recorder<T> {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            _ = self.writer.send((std::mem::take(&mut self.buffer), None));
+        }
+    }
+}
+
+impl<T> Stream for Recorder<T>
+where
+    T: Transport<Bytes>,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            self.record(Direction::Inbound, frame);
+        }
+
+        poll
+    }
+}
+
+impl<T> Sink<Bytes> for Recorder<T>
+where
+    T: Transport<Bytes>,
+{
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> io::Result<()> {
+        self.record(Direction::Outbound, &item);
+
+        Pin::new(&mut self.inner).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(Pin::new(&mut self.inner).poll_flush(cx))?;
+
+        self.poll_written(cx).map(Ok)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(Pin::new(&mut self.inner).poll_close(cx))?;
+
+        self.poll_written(cx).map(Ok)
+    }
+}
+
+/// Fake peer transport playing a recording back.
+///
+/// Each frame sent must equal the next recorded outbound frame, otherwise the send
+/// fails with [`io::ErrorKind::InvalidData`]. Recorded inbound frames are received
+/// as soon as all outbound frames before them were sent, timestamps are ignored.
+/// The stream stays pending once the recording is exhausted.
+#[derive(Debug, Default)]
+pub struct Replay {
+    records: VecDeque<Record>,
+    waker: Option<Waker>,
+}
+
+impl Replay {
+    /// Create new replay transport from `records`.
+    pub fn new<I: IntoIterator<Item = Record>>(records: I) -> Self {
+        Self {
+            records: records.into_iter().collect(),
+            waker: None,
+        }
+    }
+
+    /// Create new replay transport from recording file `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(load(path)?))
+    }
+
+    /// Returns true if all records were replayed.
+    pub fn is_exhausted(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl Stream for Replay {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.records.front() {
+            Some(record) if record.direction == Direction::Inbound => {
+                let record = self.records.pop_front().expect("checked front record");
+
+                Poll::Ready(Some(Ok(record.frame)))
+            }
+            _ => {
+                self.waker = Some(cx.waker().clone());
+
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Sink<Bytes> for Replay {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> io::Result<()> {
+        match self.records.front() {
+            Some(record) if record.direction == Direction::Outbound && record.frame == item => {
+                self.records.pop_front();
+
+                if let Some(waker) = self.waker.take() {
+                    waker.wake();
+                }
+
+                Ok(())
+            }
+            Some(record) if record.direction == Direction::Outbound => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("replay mismatch, expect {:?}, got {:?}", record.frame, item),
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("replay expects no frame, got {:?}", item),
+            )),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+
+    use crate::transport::{memory::pair, Direction};
+
+    use super::{Record, Recorder, Replay};
+
+    /// Writer into a buffer shared with the test.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[futures_test::test]
+    async fn test_record_replay() {
+        let (transport, mut peer) = pair(10);
+
+        let buf = Shared::default();
+
+        let mut recorder = Recorder::new(transport, buf.clone());
+
+        recorder.send(Bytes::from_static(b"ping")).await.unwrap();
+
+        // Written once flushed by the send.
+        assert!(!buf.0.lock().unwrap().is_empty());
+
+        peer.send(Bytes::from_static(&[0xff, 0x00])).await.unwrap();
+        recorder.next().await.unwrap().unwrap();
+
+        recorder.close().await.unwrap();
+
+        let buf = buf.0.lock().unwrap().clone();
+
+        let records = String::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Record>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Outbound);
+        assert_eq!(records[1].frame, Bytes::from_static(&[0xff, 0x00]));
+
+        let mut replay = Replay::new(records.clone());
+
+        assert!(replay.send(Bytes::from_static(b"pong")).await.is_err());
+
+        replay.send(Bytes::from_static(b"ping")).await.unwrap();
+        assert_eq!(replay.next().await.unwrap().unwrap(), records[1].frame);
+        assert!(replay.is_exhausted());
+    }
+}
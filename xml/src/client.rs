@@ -0,0 +1,69 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use async_timer_rs::Timer;
+use bytes::Bytes;
+use futures::channel::mpsc::Receiver;
+use librpc::{buffer::BufferPool, dispatcher::Dispatcher};
+use librpc_json::result::{RPCError, RPCResult};
+
+use crate::{codec, value::Value};
+
+/// XML-RPC client
+#[derive(Debug, Clone)]
+pub struct Client {
+    id_gen: Arc<AtomicU64>,
+    buffers: BufferPool,
+    dispatcher: Dispatcher<RPCError>,
+}
+
+pub type Responder = librpc::responder::Responder<RPCError>;
+pub type Output = Receiver<(Option<u64>, Bytes)>;
+
+impl Client {
+    /// Create new XML-RPC client instance with sending cache quene length.
+    pub fn new(cache_size: usize) -> (Self, Output, Responder) {
+        let (dispatcher, receiver) = Dispatcher::new(cache_size);
+
+        let responder = dispatcher.responder.clone();
+
+        (
+            Client {
+                id_gen: Default::default(),
+                buffers: Default::default(),
+                dispatcher,
+            },
+            receiver,
+            responder,
+        )
+    }
+
+    /// Asynchronous send a `<methodCall>` and wait response, `<fault>` is returned as error.
+    pub async fn call<T>(
+        &mut self,
+        method: &str,
+        params: &[Value],
+        timeout: Option<T>,
+    ) -> RPCResult<Value>
+    where
+        T: Timer + Unpin,
+    {
+        // XML-RPC has no request id, only used to match the response locally.
+        let id = self.id_gen.fetch_add(1, Ordering::SeqCst);
+
+        let data = self
+            .buffers
+            .frame(|buf| codec::encode_call(method, params, buf))?;
+
+        let frame = self
+            .dispatcher
+            .call(id, data, timeout)
+            .await?
+            .await
+            .map_err(RPCError::map_timeout)?;
+
+        codec::decode_response(&frame)
+    }
+}
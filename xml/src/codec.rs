@@ -0,0 +1,607 @@
+//! XML-RPC document encoding
+//!
+//! visit [`here`](http://xmlrpc.com/spec.md) for details
+
+use std::{collections::BTreeMap, fmt::Display, io};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use librpc_json::{
+    object::{Error, ErrorCode},
+    result::{RPCError, RPCResult},
+};
+use quick_xml::{escape::escape, events::Event, Reader};
+
+use crate::value::Value;
+
+/// Maximum nesting of arrays and structs in a decoded document.
+pub const MAX_DEPTH: usize = 128;
+
+/// Decoded `<methodCall>` document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodCall {
+    /// Content of `<methodName>`.
+    pub method: String,
+    /// Values of `<params>`, empty if omitted.
+    pub params: Vec<Value>,
+}
+
+/// Write `<methodCall>` document to `xml`.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] on a NaN or infinite double, which XML-RPC
+/// can not carry.
+pub fn encode_call<W: io::Write>(method: &str, params: &[Value], mut xml: W) -> io::Result<()> {
+    write!(
+        xml,
+        r#"<?xml version="1.0"?><methodCall><methodName>{}</methodName><params>"#,
+        escape(method)
+    )?;
+
+    for param in params {
+        xml.write_all(b"<param>")?;
+        write_value(param, &mut xml)?;
+        xml.write_all(b"</param>")?;
+    }
+
+    xml.write_all(b"</params></methodCall>")
+}
+
+/// Write `<methodResponse>` document to `xml`, errors are encoded as `<fault>`.
+pub fn encode_response<W: io::Write>(result: &RPCResult<Value>, mut xml: W) -> io::Result<()> {
+    xml.write_all(br#"<?xml version="1.0"?><methodResponse>"#)?;
+
+    match result {
+        Ok(value) => {
+            xml.write_all(b"<params><param>")?;
+            write_value(value, &mut xml)?;
+            xml.write_all(b"</param></params>")?;
+        }
+        Err(err) => {
+            xml.write_all(b"<fault>")?;
+            write_value(&fault(err), &mut xml)?;
+            xml.write_all(b"</fault>")?;
+        }
+    }
+
+    xml.write_all(b"</methodResponse>")
+}
+
+/// Decode `<methodCall>` document.
+pub fn decode_call(frame: &[u8]) -> RPCResult<MethodCall> {
+    let mut parser = Parser::new(frame);
+
+    parser.start("methodCall")?;
+    parser.start("methodName")?;
+
+    let method = parser.text("methodName")?.trim().to_owned();
+
+    let mut params = vec![];
+
+    match parser.next()? {
+        Event::Start(start) if start.local_name().as_ref() == b"params" => {
+            params = parser.params()?;
+            parser.end("methodCall")?;
+        }
+        Event::End(end) if end.local_name().as_ref() == b"methodCall" => {}
+        event => return Err(invalid(format!("unexpected {:?}", event))),
+    }
+
+    Ok(MethodCall { method, params })
+}
+
+/// Decode `<methodResponse>` document, `<fault>` is returned as [`RPCError`].
+pub fn decode_response(frame: &[u8]) -> RPCResult<Value> {
+    let mut parser = Parser::new(frame);
+
+    parser.start("methodResponse")?;
+
+    match parser.next()? {
+        Event::Start(start) if start.local_name().as_ref() == b"params" => {
+            let mut params = parser.params()?;
+
+            parser.end("methodResponse")?;
+
+            match params.len() {
+                1 => Ok(params.remove(0)),
+                len => Err(invalid(format!("expect one response param, got {}", len))),
+            }
+        }
+        Event::Start(start) if start.local_name().as_ref() == b"fault" => {
+            parser.start("value")?;
+
+            let value = parser.value()?;
+
+            parser.end("fault")?;
+            parser.end("methodResponse")?;
+
+            Err(fault_error(value)?)
+        }
+        event => Err(invalid(format!("unexpected {:?}", event))),
+    }
+}
+
+/// Returns the `<fault>` struct of `err`, `faultCode` is the numeric [`ErrorCode`].
+pub fn fault(err: &RPCError) -> Value {
+    Value::Struct(BTreeMap::from([
+        ("faultCode".to_owned(), Value::Int(err.code.code())),
+        ("faultString".to_owned(), Value::String(err.message.clone())),
+    ]))
+}
+
+/// Map `<fault>` struct to [`RPCError`].
+///
+/// Fault codes not defined by [`ErrorCode`] map to [`ErrorCode::ServerError`] with the fault string.
+pub fn fault_error(value: Value) -> RPCResult<RPCError> {
+    let mut members = match value {
+        Value::Struct(members) => members,
+        value => return Err(invalid(format!("fault must be struct, got {:?}", value))),
+    };
+
+    let message = match members.remove("faultString") {
+        Some(Value::String(message)) => message,
+        _ => return Err(invalid("fault without faultString")),
+    };
+
+    let code = match members.remove("faultCode") {
+        Some(Value::Int(code)) => code,
+        _ => return Err(invalid("fault without faultCode")),
+    };
+
+    Ok(Error {
+        code: ErrorCode::from_code(code)
+            .unwrap_or_else(|| ErrorCode::ServerError(code, message.clone())),
+        message,
+        data: None,
+    })
+}
+
+fn write_value<W: io::Write>(value: &Value, xml: &mut W) -> io::Result<()> {
+    xml.write_all(b"<value>")?;
+
+    match value {
+        Value::Int(value) if i32::try_from(*value).is_ok() => write!(xml, "<int>{}</int>", value),
+        Value::Int(value) => write!(xml, "<i8>{}</i8>", value),
+        Value::Boolean(value) => write!(xml, "<boolean>{}</boolean>", *value as u8),
+        Value::String(value) => write!(xml, "<string>{}</string>", escape(value)),
+        Value::Double(value) if !value.is_finite() => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("double {} is not finite", value),
+        )),
+        Value::Double(value) => write!(xml, "<double>{}</double>", value),
+        Value::DateTime(value) => write!(
+            xml,
+            "<dateTime.iso8601>{}</dateTime.iso8601>",
+            escape(value)
+        ),
+        Value::Base64(value) => write!(xml, "<base64>{}</base64>", STANDARD.encode(value)),
+        Value::Struct(members) => {
+            xml.write_all(b"<struct>")?;
+
+            for (name, value) in members {
+                write!(xml, "<member><name>{}</name>", escape(name))?;
+                write_value(value, xml)?;
+                xml.write_all(b"</member>")?;
+            }
+
+            xml.write_all(b"</struct>")
+        }
+        Value::Array(values) => {
+            xml.write_all(b"<array><data>")?;
+
+            for value in values {
+                write_value(value, xml)?;
+            }
+
+            xml.write_all(b"</data></array>")
+        }
+        Value::Nil => xml.write_all(b"<nil/>"),
+    }?;
+
+    xml.write_all(b"</value>")
+}
+
+fn parse_error<E: Display>(err: E) -> RPCError {
+    Error {
+        code: ErrorCode::ParseError,
+        message: format!("Invalid xml document: {}", err),
+        data: None,
+    }
+}
+
+fn invalid<E: Display>(err: E) -> RPCError {
+    Error {
+        code: ErrorCode::InvalidRequest,
+        message: format!("Invalid XML-RPC document: {}", err),
+        data: None,
+    }
+}
+
+/// Pull parser over one document.
+struct Parser<'a> {
+    reader: Reader<&'a [u8]>,
+    /// Arrays and structs around the parsed value.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(frame: &'a [u8]) -> Self {
+        let mut reader = Reader::from_reader(frame);
+
+        reader.expand_empty_elements(true);
+
+        Self { reader, depth: 0 }
+    }
+
+    fn raw(&mut self) -> RPCResult<Event<'a>> {
+        match self.reader.read_event().map_err(parse_error)? {
+            Event::Eof => Err(parse_error("unexpected end of document")),
+            event => Ok(event),
+        }
+    }
+
+    /// Next markup event, skipping declaration, comments and whitespace.
+    fn next(&mut self) -> RPCResult<Event<'a>> {
+        loop {
+            match self.raw()? {
+                Event::Decl(_) | Event::Comment(_) | Event::PI(_) | Event::DocType(_) => {}
+                Event::Text(text) if text.iter().all(u8::is_ascii_whitespace) => {}
+                event => return Ok(event),
+            }
+        }
+    }
+
+    fn start(&mut self, name: &str) -> RPCResult<()> {
+        match self.next()? {
+            Event::Start(start) if start.local_name().as_ref() == name.as_bytes() => Ok(()),
+            event => Err(invalid(format!("expect <{}>, got {:?}", name, event))),
+        }
+    }
+
+    fn end(&mut self, name: &str) -> RPCResult<()> {
+        match self.next()? {
+            Event::End(end) if end.local_name().as_ref() == name.as_bytes() => Ok(()),
+            event => Err(invalid(format!("expect </{}>, got {:?}", name, event))),
+        }
+    }
+
+    /// Text content up to the end tag `name`.
+    fn text(&mut self, name: &str) -> RPCResult<String> {
+        let mut content = String::new();
+
+        loop {
+            match self.raw()? {
+                Event::Text(text) => content.push_str(&text.unescape().map_err(parse_error)?),
+                Event::CData(data) => {
+                    content.push_str(std::str::from_utf8(&data).map_err(parse_error)?)
+                }
+                Event::Comment(_) => {}
+                Event::End(end) if end.local_name().as_ref() == name.as_bytes() => {
+                    return Ok(content)
+                }
+                event => return Err(invalid(format!("expect </{}>, got {:?}", name, event))),
+            }
+        }
+    }
+
+    /// Values of `<param>` elements, up to `</params>`.
+    fn params(&mut self) -> RPCResult<Vec<Value>> {
+        let mut params = vec![];
+
+        loop {
+            match self.next()? {
+                Event::Start(start) if start.local_name().as_ref() == b"param" => {
+                    self.start("value")?;
+                    params.push(self.value()?);
+                    self.end("param")?;
+                }
+                Event::End(end) if end.local_name().as_ref() == b"params" => return Ok(params),
+                event => return Err(invalid(format!("expect <param>, got {:?}", event))),
+            }
+        }
+    }
+
+    /// Value after its `<value>` start tag, untyped content is string.
+    fn value(&mut self) -> RPCResult<Value> {
+        let mut content = String::new();
+
+        loop {
+            match self.raw()? {
+                Event::Text(text) => content.push_str(&text.unescape().map_err(parse_error)?),
+                Event::CData(data) => {
+                    content.push_str(std::str::from_utf8(&data).map_err(parse_error)?)
+                }
+                Event::Comment(_) => {}
+                Event::End(end) if end.local_name().as_ref() == b"value" => {
+                    return Ok(Value::String(content))
+                }
+                Event::Start(start) if content.trim().is_empty() => {
+                    let tag = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+
+                    let value = self.typed(&tag)?;
+
+                    self.end("value")?;
+
+                    return Ok(value);
+                }
+                event => return Err(invalid(format!("expect value, got {:?}", event))),
+            }
+        }
+    }
+
+    fn typed(&mut self, tag: &str) -> RPCResult<Value> {
+        match tag {
+            "i4" | "int" | "i8" => self
+                .text(tag)?
+                .trim()
+                .parse()
+                .map(Value::Int)
+                .map_err(invalid),
+            "boolean" => match self.text(tag)?.trim() {
+                "1" => Ok(Value::Boolean(true)),
+                "0" => Ok(Value::Boolean(false)),
+                value => Err(invalid(format!("invalid boolean {}", value))),
+            },
+            "string" => Ok(Value::String(self.text(tag)?)),
+            "double" => match self.text(tag)?.trim().parse::<f64>() {
+                Ok(value) if value.is_finite() => Ok(Value::Double(value)),
+                Ok(value) => Err(invalid(format!("double {} is not finite", value))),
+                Err(err) => Err(invalid(err)),
+            },
+            "dateTime.iso8601" => Ok(Value::DateTime(self.text(tag)?.trim().to_owned())),
+            "base64" => {
+                let content = self
+                    .text(tag)?
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect::<String>();
+
+                STANDARD.decode(content).map(Value::Base64).map_err(invalid)
+            }
+            "nil" => {
+                self.end(tag)?;
+                Ok(Value::Nil)
+            }
+            "array" | "struct" if self.depth == MAX_DEPTH => {
+                Err(invalid(format!("values nested deeper than {}", MAX_DEPTH)))
+            }
+            "array" => {
+                self.depth += 1;
+
+                self.start("data")?;
+
+                let mut values = vec![];
+
+                loop {
+                    match self.next()? {
+                        Event::Start(start) if start.local_name().as_ref() == b"value" => {
+                            values.push(self.value()?)
+                        }
+                        Event::End(end) if end.local_name().as_ref() == b"data" => break,
+                        event => return Err(invalid(format!("expect <value>, got {:?}", event))),
+                    }
+                }
+
+                self.end(tag)?;
+
+                self.depth -= 1;
+
+                Ok(Value::Array(values))
+            }
+            "struct" => {
+                self.depth += 1;
+
+                let mut members = BTreeMap::new();
+
+                loop {
+                    match self.next()? {
+                        Event::Start(start) if start.local_name().as_ref() == b"member" => {
+                            self.start("name")?;
+
+                            let name = self.text("name")?;
+
+                            self.start("value")?;
+
+                            members.insert(name, self.value()?);
+
+                            self.end("member")?;
+                        }
+                        Event::End(end) if end.local_name().as_ref() == b"struct" => break,
+                        event => return Err(invalid(format!("expect <member>, got {:?}", event))),
+                    }
+                }
+
+                self.depth -= 1;
+
+                Ok(Value::Struct(members))
+            }
+            tag => Err(invalid(format!("unknown value type <{}>", tag))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, io};
+
+    use librpc_json::object::{Error, ErrorCode};
+
+    use crate::value::Value;
+
+    use super::{
+        decode_call, decode_response, encode_call, encode_response, MethodCall, MAX_DEPTH,
+    };
+
+    #[test]
+    fn test_call() {
+        let params = vec![
+            Value::Int(41),
+            Value::Int(1 << 40),
+            Value::Boolean(true),
+            Value::String("<a & b>".to_owned()),
+            Value::Double(-12.5),
+            Value::DateTime("19980717T14:08:55".to_owned()),
+            Value::Base64(b"hello".to_vec()),
+            Value::Struct(BTreeMap::from([
+                ("lowerBound".to_owned(), Value::Int(18)),
+                ("upperBound".to_owned(), Value::Nil),
+            ])),
+            Value::Array(vec![Value::Int(12), Value::String("Egypt".to_owned())]),
+        ];
+
+        let mut buf = vec![];
+
+        encode_call("examples.getStateName", &params, &mut buf).unwrap();
+
+        assert_eq!(
+            decode_call(&buf).unwrap(),
+            MethodCall {
+                method: "examples.getStateName".to_owned(),
+                params,
+            }
+        );
+    }
+
+    #[test]
+    fn test_non_finite_double() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let err = encode_call("add", &[Value::Double(value)], &mut vec![]).unwrap_err();
+
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+
+        let err = decode_response(
+            b"<methodResponse><params><param><value><double>inf</double></value></param></params></methodResponse>",
+        )
+        .unwrap_err();
+
+        assert_eq!(err.code, ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let nested = |depth| {
+            (0..depth).fold(Value::Int(1), |value, index| match index % 2 {
+                0 => Value::Array(vec![value]),
+                _ => Value::Struct(BTreeMap::from([("inner".to_owned(), value)])),
+            })
+        };
+
+        let mut buf = vec![];
+
+        encode_call("deep", &[nested(MAX_DEPTH)], &mut buf).unwrap();
+
+        assert_eq!(decode_call(&buf).unwrap().params, vec![nested(MAX_DEPTH)]);
+
+        let mut buf = vec![];
+
+        encode_call("deep", &[nested(MAX_DEPTH + 1)], &mut buf).unwrap();
+
+        assert_eq!(
+            decode_call(&buf).unwrap_err().code,
+            ErrorCode::InvalidRequest
+        );
+    }
+
+    #[test]
+    fn test_decode_spec_call() {
+        let call = decode_call(
+            br#"<?xml version="1.0"?>
+<methodCall>
+   <methodName>examples.getStateName</methodName>
+   <params>
+      <param>
+         <value><i4>41</i4></value>
+      </param>
+      <param>
+         <value> South Dakota </value>
+      </param>
+      <param>
+         <value><array><data/></array></value>
+      </param>
+      <param>
+         <value><base64>
+aGVs
+bG8=
+</base64></value>
+      </param>
+   </params>
+</methodCall>"#,
+        )
+        .unwrap();
+
+        assert_eq!(call.method, "examples.getStateName");
+        assert_eq!(
+            call.params,
+            vec![
+                Value::Int(41),
+                Value::String(" South Dakota ".to_owned()),
+                Value::Array(vec![]),
+                Value::Base64(b"hello".to_vec()),
+            ]
+        );
+
+        let err = decode_call(b"<methodCall><methodName>x</methodName>").unwrap_err();
+
+        assert_eq!(err.code, ErrorCode::ParseError);
+
+        let err = decode_call(b"<methodCall><params></params></methodCall>").unwrap_err();
+
+        assert_eq!(err.code, ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn test_fault() {
+        let mut buf = vec![];
+
+        encode_response(&Ok(Value::String("South Dakota".to_owned())), &mut buf).unwrap();
+
+        assert_eq!(
+            decode_response(&buf).unwrap(),
+            Value::String("South Dakota".to_owned())
+        );
+
+        let mut buf = vec![];
+
+        encode_response(
+            &Err(Error {
+                code: ErrorCode::MethodNotFound,
+                message: "Method not found".to_owned(),
+                data: None,
+            }),
+            &mut buf,
+        )
+        .unwrap();
+
+        let err = decode_response(&buf).unwrap_err();
+
+        assert_eq!(err.code, ErrorCode::MethodNotFound);
+        assert_eq!(err.message, "Method not found");
+
+        // Application defined fault code.
+        let err = decode_response(
+            br#"<?xml version="1.0"?>
+<methodResponse>
+   <fault>
+      <value>
+         <struct>
+            <member>
+               <name>faultCode</name>
+               <value><int>4</int></value>
+            </member>
+            <member>
+               <name>faultString</name>
+               <value><string>Too many parameters.</string></value>
+            </member>
+         </struct>
+      </value>
+   </fault>
+</methodResponse>"#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.code,
+            ErrorCode::ServerError(4, "Too many parameters.".to_owned())
+        );
+        assert_eq!(err.message, "Too many parameters.");
+    }
+}
@@ -0,0 +1,5 @@
+pub mod client;
+pub mod codec;
+pub mod server;
+pub mod session;
+pub mod value;
@@ -0,0 +1,192 @@
+//! XML-RPC server types
+
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream::FuturesOrdered, FutureExt, SinkExt, StreamExt};
+use librpc::{buffer::BufferPool, transport::Transport};
+use librpc_json::{
+    object::{Error, ErrorCode},
+    result::RPCResult,
+};
+
+use crate::{codec, value::Value};
+
+type Handler = Arc<dyn Fn(Vec<Value>) -> BoxFuture<'static, RPCResult<Value>> + Send + Sync>;
+
+/// XML-RPC server, routing `<methodCall>` documents to registered method handlers.
+#[derive(Clone, Default)]
+pub struct Server {
+    handlers: HashMap<String, Handler>,
+    buffers: BufferPool,
+}
+
+impl Server {
+    /// Create new server without any method handler.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Register `handler` for `method`, replacing any previous one.
+    ///
+    /// Handler errors are answered with `<fault>`, see [`codec::fault`].
+    pub fn handle<F, Fut>(&mut self, method: &str, handler: F) -> &mut Self
+    where
+        F: Fn(Vec<Value>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = RPCResult<Value>> + Send + 'static,
+    {
+        self.handlers.insert(
+            method.to_owned(),
+            Arc::new(move |params| handler(params).boxed()),
+        );
+
+        self
+    }
+
+    /// Handle one `<methodCall>` document, returns the `<methodResponse>` document.
+    pub fn process(&self, frame: &[u8]) -> BoxFuture<'static, Bytes> {
+        let call =
+            codec::decode_call(frame).and_then(|call| match self.handlers.get(&call.method) {
+                Some(handler) => Ok(handler(call.params)),
+                None => Err(Error {
+                    code: ErrorCode::MethodNotFound,
+                    message: format!("Method not found: {}", call.method),
+                    data: None,
+                }),
+            });
+
+        let buffers = self.buffers.clone();
+
+        async move {
+            let result = match call {
+                Ok(call) => call.await,
+                Err(err) => Err(err),
+            };
+
+            // Results XML-RPC can not carry, e.g. a NaN double, are answered with a fault.
+            buffers
+                .frame(|buf| codec::encode_response(&result, buf))
+                .or_else(|err| buffers.frame(|buf| codec::encode_response(&Err(err.into()), buf)))
+                .expect("Inner error, assembly xml fault")
+        }
+        .boxed()
+    }
+
+    /// Serve one connection until the peer disconnects.
+    ///
+    /// Calls are handled concurrently, responses are sent in request order.
+    pub async fn accept<T>(&self, transport: T) -> RPCResult<()>
+    where
+        T: Transport<Bytes>,
+    {
+        let (mut output, input) = transport.split();
+
+        let mut input = input.fuse();
+
+        let mut calls = FuturesOrdered::new();
+
+        loop {
+            futures::select! {
+                frame = input.next() => match frame {
+                    Some(Ok(frame)) => calls.push_back(self.process(&frame)),
+                    Some(Err(err)) => return Err(err.into()),
+                    None => return Ok(()),
+                },
+                frame = calls.select_next_some() => {
+                    output.send(frame).await?;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_timer_rs::hashed::Timeout;
+    use futures::future;
+    use librpc::transport::memory::pair;
+    use librpc_json::object::{Error, ErrorCode};
+
+    use crate::{client::Client, session::Session, value::Value};
+
+    use super::Server;
+
+    #[futures_test::test]
+    async fn test_call() {
+        let mut server = Server::new();
+
+        server
+            .handle("sample.add", |params| {
+                let result = match params.as_slice() {
+                    [Value::Int(lhs), Value::Int(rhs)] => Ok(Value::Int(lhs + rhs)),
+                    _ => Err(Error {
+                        code: ErrorCode::InvalidParams,
+                        message: "Expect two ints".to_owned(),
+                        data: None,
+                    }),
+                };
+
+                future::ready(result)
+            })
+            .handle("sample.echo", |mut params| {
+                future::ready(Ok(params.pop().unwrap_or(Value::Nil)))
+            })
+            .handle("sample.nan", |_| future::ready(Ok(Value::Double(f64::NAN))));
+
+        let (client_transport, server_transport) = pair(10);
+
+        let (mut client, output, responder) = Client::new(10);
+
+        let mut session = Session::new(output, responder);
+
+        let test = async move {
+            let mut echo_client = client.clone();
+
+            // Pipelined calls are answered in order.
+            let (sum, echo) = future::join(
+                client.call::<Timeout>("sample.add", &[1.into(), 2.into()], None),
+                echo_client.call::<Timeout>("sample.echo", &["hello".into()], None),
+            )
+            .await;
+
+            assert_eq!(sum.unwrap(), Value::Int(3));
+            assert_eq!(echo.unwrap(), Value::String("hello".to_owned()));
+
+            let err = client
+                .call::<Timeout>("sample.add", &[1.into()], None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::InvalidParams);
+
+            let err = client
+                .call::<Timeout>("sample.unknown", &[], None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::MethodNotFound);
+
+            // Not encodable, neither as request nor as result.
+            let err = client
+                .call::<Timeout>("sample.echo", &[Value::Double(f64::NAN)], None)
+                .await
+                .unwrap_err();
+
+            assert!(err.is_io(std::io::ErrorKind::InvalidData));
+
+            let err = client
+                .call::<Timeout>("sample.nan", &[], None)
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.code, ErrorCode::InternalError);
+        };
+
+        let (_, _, _) = future::join3(
+            server.accept(server_transport),
+            session.run(client_transport),
+            test,
+        )
+        .await;
+    }
+}
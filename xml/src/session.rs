@@ -0,0 +1,85 @@
+//! XML-RPC client connection session
+
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use librpc::transport::Transport;
+use librpc_json::{object::Error, result::RPCResult};
+
+use crate::client::{Output, Responder};
+
+/// Pump [`Client`](crate::client::Client) calls over a transport.
+///
+/// XML-RPC responses carry no request id, the server must answer in request order.
+pub struct Session {
+    output: Output,
+    responder: Responder,
+    /// Ids of calls sent and not answered yet, in sending order.
+    pending: VecDeque<u64>,
+}
+
+impl Session {
+    /// Create new session with the [`Output`] and [`Responder`] returned by
+    /// [`Client::new`](crate::client::Client::new).
+    pub fn new(output: Output, responder: Responder) -> Self {
+        Self {
+            output,
+            responder,
+            pending: Default::default(),
+        }
+    }
+
+    /// Run this session over `transport`.
+    ///
+    /// Returns `Ok(())` when all [`Client`](crate::client::Client) instances are dropped.
+    /// Returns an error if the transport is broken, in-flight calls fail with
+    /// [`ErrorCode::ConnectionLost`](librpc_json::object::ErrorCode::ConnectionLost)
+    /// in that case.
+    pub async fn run<T>(&mut self, transport: T) -> RPCResult<()>
+    where
+        T: Transport<Bytes>,
+    {
+        let result = self.pump(transport).await;
+
+        if result.is_err() {
+            for id in self.pending.drain(..) {
+                self.responder.complete(id, Err(Error::connection_lost()));
+            }
+        }
+
+        result
+    }
+
+    async fn pump<T>(&mut self, transport: T) -> RPCResult<()>
+    where
+        T: Transport<Bytes>,
+    {
+        let (mut sink, stream) = transport.split();
+
+        let mut stream = stream.fuse();
+
+        loop {
+            futures::select! {
+                frame = self.output.next() => match frame {
+                    Some((id, frame)) => {
+                        if let Some(id) = id {
+                            self.pending.push_back(id);
+                        }
+
+                        sink.send(frame).await?;
+                    }
+                    None => return Ok(()),
+                },
+                frame = stream.next() => match frame {
+                    Some(Ok(frame)) => match self.pending.pop_front() {
+                        Some(id) => self.responder.complete(id, Ok(frame)),
+                        None => log::warn!("drop unexpected response frame"),
+                    },
+                    Some(Err(err)) => return Err(err.into()),
+                    None => return Err(Error::connection_lost()),
+                },
+            }
+        }
+    }
+}
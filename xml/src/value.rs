@@ -0,0 +1,117 @@
+//! XML-RPC value types
+
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// XML-RPC `<value>` data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `<i4>`/`<int>`, or the `<i8>` extension when out of 32-bit range.
+    Int(i64),
+    /// `<boolean>`
+    Boolean(bool),
+    /// `<string>`, or untyped value.
+    String(String),
+    /// `<double>`
+    Double(f64),
+    /// `<dateTime.iso8601>`, kept as received.
+    DateTime(String),
+    /// `<base64>`
+    Base64(Vec<u8>),
+    /// `<struct>`
+    Struct(BTreeMap<String, Value>),
+    /// `<array>`
+    Array(Vec<Value>),
+    /// `<nil/>` extension.
+    Nil,
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Double(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Value::Array(value)
+    }
+}
+
+/// Mapping JSON data, numbers out of `i64` range become [`Value::Double`].
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Nil,
+            serde_json::Value::Bool(value) => Value::Boolean(value),
+            serde_json::Value::Number(value) => match value.as_i64() {
+                Some(value) => Value::Int(value),
+                None => Value::Double(value.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(value) => Value::String(value),
+            serde_json::Value::Array(values) => {
+                Value::Array(values.into_iter().map(Into::into).collect())
+            }
+            serde_json::Value::Object(members) => Value::Struct(
+                members
+                    .into_iter()
+                    .map(|(name, value)| (name, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Mapping to JSON data, date time is kept as string and base64 data is encoded as base64 string.
+impl From<Value> for serde_json::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Int(value) => value.into(),
+            Value::Boolean(value) => value.into(),
+            Value::String(value) | Value::DateTime(value) => value.into(),
+            Value::Double(value) => value.into(),
+            Value::Base64(value) => STANDARD.encode(value).into(),
+            Value::Struct(members) => serde_json::Value::Object(
+                members
+                    .into_iter()
+                    .map(|(name, value)| (name, value.into()))
+                    .collect(),
+            ),
+            Value::Array(values) => {
+                serde_json::Value::Array(values.into_iter().map(Into::into).collect())
+            }
+            Value::Nil => serde_json::Value::Null,
+        }
+    }
+}